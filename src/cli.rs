@@ -1,8 +1,19 @@
 use clap::{crate_version, Parser, Subcommand};
 
+use crate::commands::convert::ConvertCommand;
+use crate::commands::delete::DeleteCommand;
+use crate::commands::dotted::DottedCommand;
 use crate::commands::filter::FilterCommand;
+use crate::commands::flatten::FlattenCommand;
+use crate::commands::hash::HashCommand;
+use crate::commands::locate::LocateCommand;
+use crate::commands::minify::MinifyCommand;
 use crate::commands::pointers::PointersCommand;
 use crate::commands::print::PrintCommand;
+use crate::commands::query::QueryCommand;
+use crate::commands::set::SetCommand;
+use crate::commands::transform::TransformCommand;
+use crate::commands::unflatten::UnflattenCommand;
 
 /// Top level command line arguments and configuration settings
 #[derive(Parser)]
@@ -24,4 +35,26 @@ pub enum AppCommand {
     Filter(FilterCommand),
     #[command(about = "Inspecting JSON pointers", long_about = None)]
     Pointers(PointersCommand),
+    #[command(about = "Locating the JSON pointers at a given position", long_about = None)]
+    Locate(LocateCommand),
+    #[command(about = "Setting values by JSON pointer", long_about = None)]
+    Set(SetCommand),
+    #[command(about = "Deleting values by JSON pointer", long_about = None)]
+    Delete(DeleteCommand),
+    #[command(about = "Querying JSON DOM structures using JSONPath", long_about = None)]
+    Query(QueryCommand),
+    #[command(about = "Transforming JSON DOM structures", long_about = None)]
+    Transform(TransformCommand),
+    #[command(about = "Flattening JSON DOM structures into greppable assignments", long_about = None)]
+    Flatten(FlattenCommand),
+    #[command(about = "Rebuilding JSON DOM structures from flattened assignments", long_about = None)]
+    Unflatten(UnflattenCommand),
+    #[command(about = "Flattening JSON DOM structures into dotted-key objects", long_about = None)]
+    Dotted(DottedCommand),
+    #[command(about = "Converting between JSON and other document formats", long_about = None)]
+    Convert(ConvertCommand),
+    #[command(about = "Minifying JSON into its most compact form", long_about = None)]
+    Minify(MinifyCommand),
+    #[command(about = "Hashing the canonical form of JSON documents", long_about = None)]
+    Hash(HashCommand),
 }
@@ -0,0 +1,603 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+use std::path::PathBuf;
+
+use super::documents::parse_documents_with;
+use super::dotted::{flatten_into, key_to_path, IndexStyle};
+use super::{Command, CommandContext};
+use crate::cl_immediate;
+use crate::dom::{insert_at_path, parse_literal, select_pointer};
+use crate::errors::{ChiselError, ChiselResult};
+use crate::escapes::unquote_json_string;
+use crate::formats::bson::{self, ExtendedJson};
+use crate::formats::xml::XmlOptions;
+use crate::formats::{cbor, msgpack, BinaryStyle, DocumentFormat, ReadOptions};
+use crate::render::csv_printer::{field_text, CsvPrinter};
+use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode, Draw};
+use crate::render::pretty_printer::{FormatOptions, PrettyPrinter};
+use crate::render::toml_printer::TomlPrinter;
+use crate::render::xml_printer::XmlPrinter;
+use crate::render::yaml_printer::YamlPrinter;
+use crate::sinks::write_binary_to_stdout;
+use crate::sources::{reader_from_file, reader_from_stdin, source_from_file, source_from_stdin};
+use chisel_json::JsonValue;
+use clap::{Args, ValueEnum};
+
+/// The order in which discovered columns are written
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ColumnOrder {
+    /// the order in which keys are first seen across the records
+    FirstSeen,
+    /// sorted by name
+    Sorted,
+}
+
+/// The different ways in which nested objects and arrays may be written within a field
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum NestedValues {
+    /// flattened into dotted columns, e.g. user.name and tags[0]
+    Flatten,
+    /// embedded as compact JSON text
+    Json,
+}
+
+/// The (decoded) column names and field values of a single record
+type Fields = Vec<(String, String)>;
+
+/// The set of columns discovered across a series of records
+#[derive(Debug, Default)]
+struct Columns {
+    /// The column names, in the order they were first seen
+    names: Vec<String>,
+    /// Every column name seen so far
+    seen: HashSet<String>,
+}
+
+impl Columns {
+    /// Add any columns within a record that haven't already been seen
+    fn observe(&mut self, fields: &Fields) {
+        for (name, _) in fields {
+            if self.seen.insert(name.clone()) {
+                self.names.push(name.clone());
+            }
+        }
+    }
+
+    /// Finish up, putting the columns into the requested order
+    fn into_names(self, order: ColumnOrder) -> Vec<String> {
+        let mut names = self.names;
+        if order == ColumnOrder::Sorted {
+            names.sort();
+        }
+        names
+    }
+}
+
+/// Arrange the fields of a record into columns, leaving any missing fields empty
+fn arrange(columns: &[String], fields: Fields) -> Vec<String> {
+    let fields: HashMap<String, String> = fields.into_iter().collect();
+    columns
+        .iter()
+        .map(|name| fields.get(name).cloned().unwrap_or_default())
+        .collect()
+}
+
+/// A [Command] responsible for converting documents between different formats
+#[derive(Debug, Args)]
+pub struct ConvertCommand {
+    /// Source JSON file.
+    ///
+    /// If not specified, input is assumed to come from stdin.
+    #[arg(last = true, value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Input format
+    ///
+    /// The format of the input document. If not specified, then it's worked out from the extension of the source
+    /// file, falling back to JSON
+    #[arg(value_enum, long, value_name = "FORMAT")]
+    pub from: Option<DocumentFormat>,
+
+    /// Output format
+    ///
+    /// The format that the input should be converted into
+    #[arg(value_enum, short, long, value_name = "FORMAT")]
+    pub to: DocumentFormat,
+
+    /// Newline delimited input
+    ///
+    /// Treat the input as NDJSON, with one document per line. Each document is converted separately, and for YAML
+    /// output each becomes a separate document within a single stream. YAML input containing several documents is
+    /// treated in the same way
+    #[arg(short, long)]
+    pub ndjson: bool,
+
+    /// Selection pointer
+    ///
+    /// A JSON pointer to the part of each document that should be converted, e.g. /statuses. For CSV and TSV output
+    /// this should locate either an array of objects, each of which becomes a row, or a single object
+    #[arg(short, long, value_name = "POINTER")]
+    pub select: Option<String>,
+
+    /// Columns
+    ///
+    /// A comma-separated list of the columns to be written for CSV and TSV output, in order. If not specified, then
+    /// every key found across all of the records is written
+    #[arg(short, long, value_name = "COLUMNS", value_delimiter = ',')]
+    pub columns: Vec<String>,
+
+    /// Column order
+    ///
+    /// The order in which discovered columns are written for CSV and TSV output
+    #[arg(value_enum, long, value_name = "ORDER", default_value = "first-seen")]
+    pub column_order: ColumnOrder,
+
+    /// Nested values
+    ///
+    /// How objects and arrays within records are written for CSV and TSV output. They can either be flattened into
+    /// dotted columns, or embedded as JSON text
+    #[arg(value_enum, long, value_name = "STYLE", default_value = "flatten")]
+    pub nested: NestedValues,
+
+    /// Streaming
+    ///
+    /// Convert NDJSON input into CSV or TSV a line at a time, without holding the whole input in memory. Files are
+    /// read twice, with the first pass being used to discover the columns. For stdin the columns are taken from the
+    /// first record (unless specified), and any keys that only turn up in later records are dropped
+    #[arg(long, requires = "ndjson")]
+    pub stream: bool,
+
+    /// Infer types
+    ///
+    /// For CSV and TSV input, read any fields that are valid JSON numbers, booleans, nulls, objects or arrays as
+    /// such, rather than as strings. Empty fields are read as nulls
+    #[arg(long)]
+    pub infer_types: bool,
+
+    /// Unflatten
+    ///
+    /// For CSV and TSV input, rebuild nested objects and arrays from dotted column names such as user.name and
+    /// tags[0]
+    #[arg(short, long)]
+    pub unflatten: bool,
+
+    /// Newline delimited output
+    ///
+    /// For JSON output, write each record (or each element of an array document) as compact JSON on a line of its
+    /// own, rather than pretty printing the whole document
+    #[arg(short, long)]
+    pub lines: bool,
+
+    /// XML attribute prefix
+    ///
+    /// The prefix given to the keys which represent XML attributes (and namespace declarations)
+    #[arg(long, value_name = "PREFIX", default_value = "@")]
+    pub attribute_prefix: String,
+
+    /// XML text key
+    ///
+    /// The key used to hold the text within XML elements that also have attributes or child elements
+    #[arg(long, value_name = "KEY", default_value = "#text")]
+    pub text_key: String,
+
+    /// XML array elements
+    ///
+    /// A comma-separated list of XML element names which are always read as arrays, even when they only appear once
+    #[arg(long, value_name = "NAMES", value_delimiter = ',')]
+    pub array_elements: Vec<String>,
+
+    /// Strict XML mapping
+    ///
+    /// Reject anything that wouldn't survive a round trip between XML and JSON unchanged. When reading, this covers
+    /// comments, processing instructions, mixed content and interleaved elements, and text is kept exactly as it
+    /// appears rather than being trimmed. When writing, this covers anything other than strings, along with arrays
+    /// that wouldn't be read back as arrays
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Binary values
+    ///
+    /// How the content of the binary values found within MessagePack and CBOR input is read, either as base64
+    /// encoded strings or as arrays of byte values. Binary values are wrapped in an object with a single "@binary"
+    /// member, holding either form, which is written back out as binary
+    #[arg(value_enum, long, value_name = "STYLE", default_value = "base64")]
+    pub binary: BinaryStyle,
+
+    /// Extended JSON mode
+    ///
+    /// How BSON input is read. Canonical mode wraps every value so that its exact BSON type is preserved, whereas
+    /// relaxed mode writes numbers and dates as plain JSON wherever it can. Either mode is accepted when writing BSON
+    #[arg(value_enum, long, value_name = "MODE", default_value = "relaxed")]
+    pub extended_json: ExtendedJson,
+
+    /// Indent space count
+    ///
+    /// Object keys and array values are idented by this amount plus the parent identation amount
+    #[arg(short, long, value_name = "n", default_value = "2")]
+    pub indent: u16,
+
+    /// KV padding count
+    ///
+    /// The number of spaces added to each side of the ":" character in a <key> : <value> pair
+    #[arg(short, long, value_name = "n", default_value = "1")]
+    pub kvpadding: u16,
+}
+
+/// Infer the type of a field read from CSV or TSV input, given its text and raw (quoted) form. Anything which is a
+/// valid JSON literal (other than a string) is taken as is, empty fields become nulls and everything else is left as a
+/// string
+fn infer_type(text: &str, raw: Cow<'static, str>) -> JsonValue<'static> {
+    if text.is_empty() {
+        return JsonValue::Null;
+    }
+    if text.trim() == text && !text.starts_with('"') {
+        if let Ok(value) = parse_literal(text) {
+            return value;
+        }
+    }
+    JsonValue::String(raw)
+}
+
+impl ConvertCommand {
+    /// Parse the input into one or more documents
+    fn parse_documents(&self, buffer: &[u8]) -> ChiselResult<Vec<JsonValue<'static>>> {
+        let format = DocumentFormat::detect(self.from, self.file.as_deref());
+        if self.ndjson && format == DocumentFormat::Json {
+            return String::from_utf8_lossy(buffer)
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(parse_literal)
+                .collect();
+        }
+        let documents = parse_documents_with(format, buffer, &self.read_options())?;
+        match format {
+            DocumentFormat::Csv | DocumentFormat::Tsv => Ok(documents
+                .into_iter()
+                .map(|document| self.import_records(document))
+                .collect()),
+            _ => Ok(documents),
+        }
+    }
+
+    /// Apply any type inference and unflattening to the records read from CSV or TSV input. When unflattening, empty
+    /// fields are dropped, as that's how missing keys end up being written out
+    fn import_records(&self, document: JsonValue<'static>) -> JsonValue<'static> {
+        let JsonValue::Array(records) = document else {
+            return document;
+        };
+        let records = records.into_iter().map(|record| {
+            let JsonValue::Object(pairs) = record else {
+                return record;
+            };
+            let pairs = pairs
+                .into_iter()
+                .filter(|(_, value)| {
+                    !(self.unflatten && matches!(value, JsonValue::String(raw) if raw == "\"\""))
+                })
+                .map(|(key, value)| match (self.infer_types, value) {
+                    (true, JsonValue::String(raw)) => {
+                        (key, infer_type(&unquote_json_string(&raw), raw))
+                    }
+                    (_, value) => (key, value),
+                });
+            match self.unflatten {
+                true => {
+                    let mut root = JsonValue::Object(vec![]);
+                    for (key, value) in pairs {
+                        let path =
+                            key_to_path(&unquote_json_string(&key), ".", IndexStyle::Brackets);
+                        insert_at_path(&mut root, &path, value);
+                    }
+                    root
+                }
+                false => JsonValue::Object(pairs.collect()),
+            }
+        });
+        JsonValue::Array(records.collect())
+    }
+
+    /// The selection pointer, which defaults to the whole document
+    fn selection(&self) -> &str {
+        self.select.as_deref().unwrap_or_default()
+    }
+
+    /// The options controlling the mapping between XML and JSON
+    fn xml_options(&self) -> XmlOptions {
+        XmlOptions {
+            attribute_prefix: self.attribute_prefix.clone(),
+            text_key: self.text_key.clone(),
+            array_elements: self.array_elements.clone(),
+            strict: self.strict,
+        }
+    }
+
+    /// The options controlling how documents are read
+    fn read_options(&self) -> ReadOptions {
+        ReadOptions {
+            xml: self.xml_options(),
+            binary: self.binary,
+            extended_json: self.extended_json,
+        }
+    }
+
+    /// The formatting options to be passed through to the various printers
+    fn format_options(&self) -> FormatOptions {
+        FormatOptions {
+            indent: self.indent,
+            kvpadding: self.kvpadding,
+            ..FormatOptions::default()
+        }
+    }
+
+    /// Render documents as JSON, with each one on a new line
+    fn render_json(
+        &self,
+        context: &CommandContext,
+        documents: Vec<JsonValue<'static>>,
+    ) -> ChiselResult<()> {
+        let printer = PrettyPrinter::new(context.clone_render_pipeline(), self.format_options());
+        if self.lines {
+            for document in documents {
+                match document {
+                    JsonValue::Array(values) => values
+                        .iter()
+                        .try_for_each(|value| printer.render_json_line(value))?,
+                    document => printer.render_json_line(&document)?,
+                }
+            }
+            return Ok(());
+        }
+        for document in documents {
+            printer.render_json(document)?;
+            context
+                .render_pipeline
+                .send(cl_immediate!(Draw::NewLine))
+                .or(Err(ChiselError::DisplayListFailed))?;
+        }
+        Ok(())
+    }
+
+    /// Render documents as YAML. Multiple documents are each preceded by a document start marker
+    fn render_yaml(
+        &self,
+        context: &CommandContext,
+        documents: Vec<JsonValue<'static>>,
+    ) -> ChiselResult<()> {
+        let printer = YamlPrinter::new(context.clone_render_pipeline(), self.format_options());
+        let multiple = self.ndjson || documents.len() > 1;
+        for document in documents {
+            if multiple {
+                printer.render_document_start()?;
+            }
+            printer.render_yaml(document)?;
+        }
+        Ok(())
+    }
+
+    /// Render a document as TOML. There's no way of holding several documents within a single TOML file, so only
+    /// a single document is accepted
+    fn render_toml(
+        &self,
+        context: &CommandContext,
+        mut documents: Vec<JsonValue<'static>>,
+    ) -> ChiselResult<()> {
+        if documents.len() != 1 {
+            return Err(ChiselError::FormatNotSupported(String::from(
+                "multiple documents within a single TOML file",
+            )));
+        }
+        TomlPrinter::new(context.clone_render_pipeline()).render_toml(documents.remove(0))
+    }
+
+    /// Render a document as XML. As with TOML, only a single document is accepted
+    fn render_xml(
+        &self,
+        context: &CommandContext,
+        mut documents: Vec<JsonValue<'static>>,
+    ) -> ChiselResult<()> {
+        if documents.len() != 1 {
+            return Err(ChiselError::FormatNotSupported(String::from(
+                "multiple documents within a single XML file",
+            )));
+        }
+        let printer = XmlPrinter::new(
+            context.clone_render_pipeline(),
+            self.format_options(),
+            self.xml_options(),
+        );
+        printer.render_xml(documents.remove(0))
+    }
+
+    /// Render the documents as MessagePack, CBOR or BSON, which are written back to back and sent straight to stdout
+    fn render_binary(&self, documents: Vec<JsonValue<'static>>) -> ChiselResult<()> {
+        let mut buffer = vec![];
+        for document in &documents {
+            match self.to {
+                DocumentFormat::Cbor => cbor::encode(document, &mut buffer)?,
+                DocumentFormat::Bson => bson::encode(document, &mut buffer)?,
+                _ => msgpack::encode(document, &mut buffer)?,
+            }
+        }
+        write_binary_to_stdout(&buffer)
+    }
+
+    /// Split a (selected) document into records. Arrays hold a record per element, and anything else is taken to be a
+    /// single record. Each record is paired up with its pointer, for the purposes of error reporting
+    fn records(&self, document: JsonValue<'static>) -> Vec<(String, JsonValue<'static>)> {
+        match document {
+            JsonValue::Array(values) => values
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| (format!("{}/{}", self.selection(), index), value))
+                .collect(),
+            value => vec![(self.selection().to_string(), value)],
+        }
+    }
+
+    /// Break a record down into its fields, flattening or embedding any nested values as required
+    fn fields(&self, pointer: &str, record: JsonValue<'static>) -> ChiselResult<Fields> {
+        let JsonValue::Object(pairs) = record else {
+            return Err(ChiselError::NotRepresentable(format!(
+                "record at \"{}\" isn't an object",
+                pointer
+            )));
+        };
+        let mut fields = vec![];
+        for (key, value) in pairs {
+            let name = unquote_json_string(&key);
+            match self.nested {
+                NestedValues::Flatten => {
+                    let mut flattened = vec![];
+                    flatten_into(name, value, ".", IndexStyle::Brackets, &mut flattened);
+                    fields.extend(
+                        flattened
+                            .into_iter()
+                            .map(|(key, value)| (unquote_json_string(&key), field_text(&value))),
+                    );
+                }
+                NestedValues::Json => fields.push((name, field_text(&value))),
+            }
+        }
+        Ok(fields)
+    }
+
+    /// Render documents as either CSV or TSV, with a column for every key found across all of the records
+    fn render_delimited(
+        &self,
+        context: &CommandContext,
+        documents: Vec<JsonValue<'static>>,
+    ) -> ChiselResult<()> {
+        let mut records = vec![];
+        for document in documents {
+            for (pointer, record) in self.records(document) {
+                records.push(self.fields(&pointer, record)?);
+            }
+        }
+
+        let columns = match self.columns.is_empty() {
+            true => {
+                let mut columns = Columns::default();
+                records.iter().for_each(|fields| columns.observe(fields));
+                columns.into_names(self.column_order)
+            }
+            false => self.columns.clone(),
+        };
+
+        let printer = CsvPrinter::new(
+            context.clone_render_pipeline(),
+            self.to == DocumentFormat::Tsv,
+        );
+        printer.render_record(&columns)?;
+        for fields in records {
+            printer.render_record(&arrange(&columns, fields))?;
+        }
+        Ok(())
+    }
+
+    /// Read NDJSON input a line at a time, handing the fields of each record over to a callback
+    fn for_each_record(
+        &self,
+        reader: Box<dyn BufRead>,
+        mut callback: impl FnMut(Fields) -> ChiselResult<()>,
+    ) -> ChiselResult<()> {
+        for line in reader.lines() {
+            let line = line.or(Err(ChiselError::InvalidInput))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let document = select_pointer(parse_literal(&line)?, self.selection())?;
+            for (pointer, record) in self.records(document) {
+                callback(self.fields(&pointer, record)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stream NDJSON input out as either CSV or TSV, a record at a time
+    fn stream_delimited(&self, context: &CommandContext) -> ChiselResult<()> {
+        // columns are either given, discovered by an initial pass over the file, or taken from the first record
+        let mut columns = match (self.columns.is_empty(), &self.file) {
+            (false, _) => Some(self.columns.clone()),
+            (true, Some(path)) => {
+                let mut columns = Columns::default();
+                self.for_each_record(reader_from_file(path)?, |fields| {
+                    columns.observe(&fields);
+                    Ok(())
+                })?;
+                Some(columns.into_names(self.column_order))
+            }
+            (true, None) => None,
+        };
+
+        let printer = CsvPrinter::new(
+            context.clone_render_pipeline(),
+            self.to == DocumentFormat::Tsv,
+        );
+        if let Some(columns) = &columns {
+            printer.render_record(columns)?;
+        }
+
+        let reader = match &self.file {
+            Some(path) => reader_from_file(path)?,
+            None => reader_from_stdin()?,
+        };
+        let mut known: Option<HashSet<String>> = None;
+        let mut warned = false;
+        self.for_each_record(reader, |fields| {
+            let columns = columns.get_or_insert_with(|| {
+                let mut first = Columns::default();
+                first.observe(&fields);
+                known = Some(first.seen.clone());
+                let names = first.into_names(self.column_order);
+                let _ = printer.render_record(&names);
+                names
+            });
+            if let Some(known) = &known {
+                if !warned && fields.iter().any(|(name, _)| !known.contains(name)) {
+                    eprintln!("Warning: dropping columns that weren't present in the first record");
+                    warned = true;
+                }
+            }
+            printer.render_record(&arrange(columns, fields))
+        })
+    }
+}
+
+impl Command for ConvertCommand {
+    /// Execute the convert action
+    fn execute(&mut self, context: &mut CommandContext) -> ChiselResult<()> {
+        if self.stream {
+            return match self.to {
+                DocumentFormat::Csv | DocumentFormat::Tsv => self.stream_delimited(context),
+                format => Err(ChiselError::FormatNotSupported(format!(
+                    "streaming {} output",
+                    format
+                ))),
+            };
+        }
+
+        let mut buffer: Vec<u8> = vec![];
+        if let Some(path) = &self.file {
+            source_from_file(path, &mut buffer)?;
+        } else {
+            source_from_stdin(&mut buffer)?;
+        }
+
+        let documents = self
+            .parse_documents(&buffer)?
+            .into_iter()
+            .map(|document| select_pointer(document, self.selection()))
+            .collect::<ChiselResult<Vec<_>>>()?;
+        match self.to {
+            DocumentFormat::Json => self.render_json(context, documents),
+            DocumentFormat::Yaml => self.render_yaml(context, documents),
+            DocumentFormat::Toml => self.render_toml(context, documents),
+            DocumentFormat::Csv | DocumentFormat::Tsv => self.render_delimited(context, documents),
+            DocumentFormat::Xml => self.render_xml(context, documents),
+            DocumentFormat::Msgpack | DocumentFormat::Cbor | DocumentFormat::Bson => {
+                self.render_binary(documents)
+            }
+        }
+    }
+}
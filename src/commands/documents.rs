@@ -0,0 +1,117 @@
+//! Helpers shared by commands that load a complete document, and then render a (possibly modified) version of it
+use super::sax::LineIndex;
+use super::CommandContext;
+use crate::dom::into_static;
+use crate::errors::{ChiselError, ChiselResult};
+use crate::formats::{self, DocumentFormat, FormatError, Node, ReadOptions};
+use crate::render::buffered_renderer::render_to_string;
+use crate::render::pretty_printer::{FormatOptions, PrettyPrinter};
+use crate::render::themes::Theme;
+use crate::sinks::write_file_atomically;
+use chisel_json::dom::Parser as DomParser;
+use chisel_json::errors::ParserError;
+use chisel_json::JsonValue;
+use std::path::Path;
+
+/// Report the details of a parse failure on stderr
+pub(crate) fn report_parse_error(err: &ParserError) {
+    eprintln!("Parse failed!");
+    eprintln!("\tFailed at stage: {}", err.source);
+    eprintln!("\tError reported: {}", err.details);
+    eprintln!("\tInput coords: {}", err.coords.unwrap_or_default());
+}
+
+/// Report the details of a failure to read a document in some format other than JSON on stderr
+pub(crate) fn report_format_error(buffer: &[u8], err: &FormatError) {
+    let (line, column) = LineIndex::new(buffer).coords(err.offset);
+    eprintln!("Parse failed!");
+    eprintln!("\tError reported: {}", err.message);
+    eprintln!("\tInput coords: [line: {}, column: {}]", line, column);
+}
+
+/// Parse a complete document, in whichever format it happens to be in
+pub(crate) fn parse_document(
+    format: DocumentFormat,
+    buffer: &[u8],
+) -> ChiselResult<JsonValue<'static>> {
+    match format {
+        DocumentFormat::Json => match DomParser::default().parse_bytes(buffer) {
+            Ok(json) => Ok(into_static(json)),
+            Err(err) => {
+                report_parse_error(&err);
+                Err(ChiselError::InvalidInput)
+            }
+        },
+        format => match formats::parse_document(format, buffer) {
+            Ok(node) => Ok(node.into_json()),
+            Err(err) => {
+                report_format_error(buffer, &err);
+                Err(ChiselError::InvalidInput)
+            }
+        },
+    }
+}
+
+/// Parse every document within a stream. Only YAML streams (and binary formats) can hold more than one document
+pub(crate) fn parse_documents(
+    format: DocumentFormat,
+    buffer: &[u8],
+) -> ChiselResult<Vec<JsonValue<'static>>> {
+    parse_documents_with(format, buffer, &ReadOptions::default())
+}
+
+/// Parse every document within a stream, using a specific set of read options
+pub(crate) fn parse_documents_with(
+    format: DocumentFormat,
+    buffer: &[u8],
+    options: &ReadOptions,
+) -> ChiselResult<Vec<JsonValue<'static>>> {
+    match format {
+        DocumentFormat::Json => Ok(vec![parse_document(format, buffer)?]),
+        format => match formats::parse_documents_with(format, buffer, options) {
+            Ok(nodes) => Ok(nodes.into_iter().map(Node::into_json).collect()),
+            Err(err) => {
+                report_format_error(buffer, &err);
+                Err(ChiselError::InvalidInput)
+            }
+        },
+    }
+}
+
+/// Pretty print a document.  If a target path is supplied then the output replaces the contents of that file,
+/// otherwise it's sent down the rendering pipeline as per usual
+pub(crate) fn write_document(
+    context: &CommandContext,
+    value: JsonValue,
+    options: FormatOptions,
+    target: Option<&Path>,
+) -> ChiselResult<()> {
+    match target {
+        Some(path) => {
+            let output = render_to_string(Theme::default(), |pipeline| {
+                PrettyPrinter::new(pipeline, options).render_json(value)
+            })?;
+            write_file_atomically(path, output.as_bytes())
+        }
+        None => {
+            let printer = PrettyPrinter::new(context.clone_render_pipeline(), options);
+            printer.render_json(value)
+        }
+    }
+}
+
+/// Work out where in-place output should be written, which is only possible when the input came from a JSON file
+pub(crate) fn in_place_target(
+    in_place: bool,
+    file: &Option<impl AsRef<Path>>,
+    format: DocumentFormat,
+) -> ChiselResult<Option<&Path>> {
+    match (in_place, file) {
+        (false, _) => Ok(None),
+        (true, Some(_)) if format != DocumentFormat::Json => Err(ChiselError::FormatNotSupported(
+            format!("in-place edits of {} files", format),
+        )),
+        (true, Some(path)) => Ok(Some(path.as_ref())),
+        (true, None) => Err(ChiselError::FileRequired),
+    }
+}
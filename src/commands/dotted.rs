@@ -0,0 +1,213 @@
+use std::path::PathBuf;
+
+use super::documents::parse_document;
+use super::{Command, CommandContext};
+use crate::dom::{insert_at_path, PathToken};
+use crate::errors::ChiselResult;
+use crate::escapes::{quote_json_string, unquote_json_string};
+use crate::formats::DocumentFormat;
+use crate::render::pretty_printer::{FormatOptions, PrettyPrinter};
+use crate::sources::{source_from_file, source_from_stdin};
+use chisel_json::JsonValue;
+use clap::{Args, ValueEnum};
+
+/// The different ways in which array indices may be written within a dotted key
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum IndexStyle {
+    /// subscripts, e.g. tags[0]
+    Brackets,
+    /// just another segment, e.g. tags.0
+    Separator,
+}
+
+/// A [Command] responsible for flattening documents (or arrays of records) into single-level objects with dotted keys
+#[derive(Debug, Args)]
+pub struct DottedCommand {
+    /// Source JSON file.
+    ///
+    /// If not specified, input is assumed to come from stdin.
+    #[arg(last = true, value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Input format
+    ///
+    /// The format of the input document. If not specified, then it's worked out from the extension of the source
+    /// file, falling back to JSON
+    #[arg(value_enum, long, value_name = "FORMAT")]
+    pub from: Option<DocumentFormat>,
+
+    /// Key separator
+    ///
+    /// The separator placed between the segments of each key. Keys which already contain the separator won't
+    /// survive a round trip
+    #[arg(short, long, default_value = ".")]
+    pub separator: String,
+
+    /// Index style
+    ///
+    /// How array indices are written within keys. When rebuilding with the separator style, any purely numeric
+    /// segment is taken to be an index
+    #[arg(value_enum, short = 'x', long, default_value = "brackets")]
+    pub index_style: IndexStyle,
+
+    /// Unflatten
+    ///
+    /// Rather than flattening, rebuild the nesting from objects with dotted keys
+    #[arg(short, long)]
+    pub unflatten: bool,
+
+    /// Indent space count
+    ///
+    /// Object keys and array values are idented by this amount plus the parent identation amount
+    #[arg(short, long, value_name = "n", default_value = "2")]
+    pub indent: u16,
+
+    /// KV padding count
+    ///
+    /// The number of spaces added to each side of the ":" character in a <key> : <value> pair
+    #[arg(short, long, value_name = "n", default_value = "1")]
+    pub kvpadding: u16,
+}
+
+/// Flatten a single value into a list of dotted key/value pairs. Empty containers are retained as values, so
+/// that they survive a round trip
+pub(crate) fn flatten_into(
+    prefix: String,
+    value: JsonValue<'static>,
+    separator: &str,
+    index_style: IndexStyle,
+    pairs: &mut Vec<(String, JsonValue<'static>)>,
+) {
+    match value {
+        JsonValue::Object(members) if !members.is_empty() => {
+            for (key, member) in members {
+                let name = unquote_json_string(&key);
+                let key = if prefix.is_empty() {
+                    name
+                } else {
+                    format!("{}{}{}", prefix, separator, name)
+                };
+                flatten_into(key, member, separator, index_style, pairs);
+            }
+        }
+        JsonValue::Array(elements) if !elements.is_empty() => {
+            for (index, element) in elements.into_iter().enumerate() {
+                let key = match index_style {
+                    IndexStyle::Brackets => format!("{}[{}]", prefix, index),
+                    IndexStyle::Separator if prefix.is_empty() => index.to_string(),
+                    IndexStyle::Separator => format!("{}{}{}", prefix, separator, index),
+                };
+                flatten_into(key, element, separator, index_style, pairs);
+            }
+        }
+        value => pairs.push((quote_json_string(&prefix), value)),
+    }
+}
+
+/// Split a dotted key back into a path
+pub(crate) fn key_to_path(key: &str, separator: &str, index_style: IndexStyle) -> Vec<PathToken> {
+    let mut path = vec![];
+    for segment in key.split(separator) {
+        match index_style {
+            IndexStyle::Brackets => {
+                // peel any trailing subscripts off the end of the segment
+                let mut name = segment;
+                let mut indices = vec![];
+                while let Some(open) = name.strip_suffix(']').and_then(|s| s.rfind('[')) {
+                    match name[open + 1..name.len() - 1].parse::<usize>() {
+                        Ok(index) => indices.push(index),
+                        Err(_) => break,
+                    }
+                    name = &name[..open];
+                }
+                if !name.is_empty() || indices.is_empty() {
+                    path.push(PathToken::Name(quote_json_string(name)));
+                }
+                path.extend(indices.into_iter().rev().map(PathToken::Index));
+            }
+            IndexStyle::Separator => match segment.parse::<usize>() {
+                Ok(index) if segment.bytes().all(|b| b.is_ascii_digit()) => {
+                    path.push(PathToken::Index(index))
+                }
+                _ => path.push(PathToken::Name(quote_json_string(segment))),
+            },
+        }
+    }
+    path
+}
+
+impl DottedCommand {
+    /// Flatten a single record. Anything that isn't a container is left as is
+    fn flatten(&self, value: JsonValue<'static>) -> JsonValue<'static> {
+        match value {
+            JsonValue::Object(_) | JsonValue::Array(_) => {
+                let mut pairs = vec![];
+                flatten_into(
+                    String::new(),
+                    value,
+                    &self.separator,
+                    self.index_style,
+                    &mut pairs,
+                );
+                JsonValue::Object(pairs)
+            }
+            value => value,
+        }
+    }
+
+    /// Rebuild a single record. Anything that isn't an object is left as is
+    fn unflatten(&self, value: JsonValue<'static>) -> JsonValue<'static> {
+        match value {
+            JsonValue::Object(pairs) => {
+                let mut root = JsonValue::Object(vec![]);
+                for (key, value) in pairs {
+                    insert_at_path(
+                        &mut root,
+                        &key_to_path(
+                            &unquote_json_string(&key),
+                            &self.separator,
+                            self.index_style,
+                        ),
+                        value,
+                    );
+                }
+                root
+            }
+            value => value,
+        }
+    }
+}
+
+impl Command for DottedCommand {
+    /// Execute the dotted action
+    fn execute(&mut self, context: &mut CommandContext) -> ChiselResult<()> {
+        let mut buffer: Vec<u8> = vec![];
+        if let Some(path) = &self.file {
+            source_from_file(path, &mut buffer)?;
+        } else {
+            source_from_stdin(&mut buffer)?;
+        }
+
+        let format = DocumentFormat::detect(self.from, self.file.as_deref());
+        let json = parse_document(format, &buffer)?;
+
+        // an array at the root is treated as a list of records, each of which is processed separately
+        let convert = |value| match self.unflatten {
+            true => self.unflatten(value),
+            false => self.flatten(value),
+        };
+        let result = match json {
+            JsonValue::Array(records) => {
+                JsonValue::Array(records.into_iter().map(convert).collect())
+            }
+            json => convert(json),
+        };
+
+        let options = FormatOptions {
+            indent: self.indent,
+            kvpadding: self.kvpadding,
+            ..FormatOptions::default()
+        };
+        PrettyPrinter::new(context.clone_render_pipeline(), options).render_json(result)
+    }
+}
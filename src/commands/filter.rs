@@ -3,7 +3,7 @@ use std::path::PathBuf;
 use super::Command;
 use clap::Args;
 
-///
+/// A [Command] responsible for filtering the input
 #[derive(Debug, Args)]
 pub struct FilterCommand {
     /// Source JSON file. If not specified, input is assumed to come from stdin.
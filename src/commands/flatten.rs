@@ -0,0 +1,192 @@
+use super::sax::{matched_to_json, parse_events};
+use super::{Command, CommandContext};
+use crate::cl_immediate;
+use crate::dom::PathToken;
+use crate::errors::ChiselResult;
+use crate::escapes::unquote_json_string;
+use crate::formats::DocumentFormat;
+use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode, Draw};
+use crate::sources::{source_from_file, source_from_stdin};
+use chisel_json::errors::ParserResult;
+use chisel_json::events::{Event, Match};
+use clap::{Args, ValueEnum};
+use std::path::PathBuf;
+
+/// The root identifier used for gron-style paths
+pub(crate) const GRON_ROOT: &str = "json";
+
+/// The different styles of assignment supported by the flatten command
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum FlattenStyle {
+    /// gron-style assignments, e.g. json.statuses[0].user.name = "foo";
+    Gron,
+    /// JSON pointer assignments, e.g. /statuses/0/user/name = "foo"
+    Pointer,
+}
+
+/// A [Command] responsible for flattening a document into a series of assignments, one per line
+#[derive(Debug, Args)]
+pub struct FlattenCommand {
+    /// Source JSON file.
+    ///
+    /// If not specified, input is assumed to come from stdin.
+    #[arg(last = true, value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Input format
+    ///
+    /// The format of the input document. If not specified, then it's worked out from the extension of the source
+    /// file, falling back to JSON
+    #[arg(value_enum, long, value_name = "FORMAT")]
+    pub from: Option<DocumentFormat>,
+
+    /// Assignment style
+    ///
+    /// The style of path used on the left hand side of each assignment. Pointers can't represent keys containing
+    /// line breaks, so the gron style should be preferred where the output needs to be rebuilt
+    #[arg(value_enum, short, long, default_value = "gron")]
+    pub style: FlattenStyle,
+}
+
+/// An open container, as tracked whilst flattening
+struct Frame {
+    /// Whether the container is an array
+    array: bool,
+    /// The index of the next array element
+    next: usize,
+}
+
+/// Format a path as a gron-style assignment target. Names that are valid identifiers use dotted notation, and
+/// anything else is written out as a (quoted) subscript
+pub(crate) fn gron_path(path: &[PathToken]) -> String {
+    let mut result = String::from(GRON_ROOT);
+    for token in path {
+        match token {
+            PathToken::Name(raw) => {
+                let name = raw
+                    .strip_prefix('"')
+                    .and_then(|s| s.strip_suffix('"'))
+                    .unwrap_or(raw);
+                if is_identifier(name) {
+                    result.push('.');
+                    result.push_str(name);
+                } else {
+                    result.push('[');
+                    result.push_str(raw);
+                    result.push(']');
+                }
+            }
+            PathToken::Index(index) => result.push_str(&format!("[{}]", index)),
+        }
+    }
+    result
+}
+
+/// Format a path as an RFC 6901 JSON pointer
+pub(crate) fn pointer_path(path: &[PathToken]) -> String {
+    let mut result = String::new();
+    for token in path {
+        result.push('/');
+        match token {
+            PathToken::Name(raw) => result.push_str(
+                &unquote_json_string(raw)
+                    .replace('~', "~0")
+                    .replace('/', "~1"),
+            ),
+            PathToken::Index(index) => result.push_str(&index.to_string()),
+        }
+    }
+    result
+}
+
+/// Checks whether a name may be written using dotted notation
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        }
+        _ => false,
+    }
+}
+
+impl FlattenCommand {
+    /// Generate the display list for a single assignment
+    fn render_assignment(&self, path: &[PathToken], value: &str) -> DisplayList {
+        let line = match self.style {
+            FlattenStyle::Gron => format!("{} = {};", gron_path(path), value),
+            FlattenStyle::Pointer => format!("{} = {}", pointer_path(path), value),
+        };
+        cl_immediate!(Draw::Text(line), Draw::NewLine)
+    }
+}
+
+impl Command for FlattenCommand {
+    /// Execute the flatten action
+    fn execute(&mut self, context: &mut CommandContext) -> ChiselResult<()> {
+        let mut buffer: Vec<u8> = vec![];
+        if let Some(path) = &self.file {
+            source_from_file(path, &mut buffer)?;
+        } else {
+            source_from_stdin(&mut buffer)?;
+        }
+
+        // paths are tracked directly from the event stream, so that keys are available in their raw form. Scalar
+        // values in JSON input are taken straight from the input, so that they're reproduced exactly
+        let format = DocumentFormat::detect(self.from, self.file.as_deref());
+        let mut frames: Vec<Frame> = vec![];
+        let mut path: Vec<PathToken> = vec![];
+        let mut key: Option<String> = None;
+        let mut handle_sax_event =
+            |evt: &Event, range: Option<(usize, usize)>| -> ParserResult<()> {
+                let Some((start, end)) = range else {
+                    return Ok(());
+                };
+                match &evt.matched {
+                    Match::ObjectKey(raw) => key = Some(raw.to_string()),
+                    Match::EndObject | Match::EndArray => {
+                        frames.pop();
+                        path.pop();
+                    }
+                    matched => {
+                        let token = match frames.last_mut() {
+                            Some(frame) if frame.array => {
+                                frame.next += 1;
+                                Some(PathToken::Index(frame.next - 1))
+                            }
+                            Some(_) => key.take().map(PathToken::Name),
+                            None => None,
+                        };
+                        let nested = token.is_some();
+                        if let Some(token) = token {
+                            path.push(token);
+                        }
+                        let value = match matched {
+                            Match::StartObject => String::from("{}"),
+                            Match::StartArray => String::from("[]"),
+                            _ if format == DocumentFormat::Json => {
+                                String::from_utf8_lossy(&buffer[start..end]).to_string()
+                            }
+                            _ => matched_to_json(matched).unwrap_or_default(),
+                        };
+                        let _ = context
+                            .render_pipeline
+                            .send(self.render_assignment(&path, &value));
+                        match matched {
+                            Match::StartObject | Match::StartArray => frames.push(Frame {
+                                array: matches!(matched, Match::StartArray),
+                                next: 0,
+                            }),
+                            _ if nested => {
+                                path.pop();
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                Ok(())
+            };
+
+        parse_events(format, &buffer, &mut handle_sax_event)
+    }
+}
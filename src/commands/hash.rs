@@ -0,0 +1,82 @@
+use super::documents::parse_documents;
+use super::{Command, CommandContext};
+use crate::cl_immediate;
+use crate::dom::canonical_json;
+use crate::errors::{ChiselError, ChiselResult};
+use crate::formats::DocumentFormat;
+use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode, Draw};
+use crate::sources::{source_from_file, source_from_stdin};
+use clap::{Args, ValueEnum};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// The different digest algorithms that can be used
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum HashAlgorithm {
+    /// SHA-256
+    Sha256,
+    /// BLAKE3
+    Blake3,
+}
+
+/// A [Command] responsible for hashing the canonical form of documents
+#[derive(Debug, Args)]
+pub struct HashCommand {
+    /// Source JSON file.
+    ///
+    /// If not specified, input is assumed to come from stdin.
+    #[arg(last = true, value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Input format
+    ///
+    /// The format of the input document. If not specified, then it's worked out from the extension of the source
+    /// file, falling back to JSON
+    #[arg(value_enum, long, value_name = "FORMAT")]
+    pub from: Option<DocumentFormat>,
+
+    /// Digest algorithm
+    ///
+    /// The algorithm used to hash the canonical (RFC 8785) form of each document
+    #[arg(
+        value_enum,
+        short,
+        long,
+        value_name = "ALGORITHM",
+        default_value = "sha256"
+    )]
+    pub algorithm: HashAlgorithm,
+}
+
+impl HashCommand {
+    /// Compute the hex encoded digest of some canonical JSON
+    fn digest(&self, canonical: &str) -> String {
+        match self.algorithm {
+            HashAlgorithm::Sha256 => format!("{:x}", Sha256::digest(canonical.as_bytes())),
+            HashAlgorithm::Blake3 => blake3::hash(canonical.as_bytes()).to_hex().to_string(),
+        }
+    }
+}
+
+impl Command for HashCommand {
+    fn execute(&mut self, context: &mut CommandContext) -> ChiselResult<()> {
+        let mut buffer: Vec<u8> = vec![];
+        if let Some(path) = &self.file {
+            source_from_file(path, &mut buffer)?;
+        } else {
+            source_from_stdin(&mut buffer)?;
+        }
+
+        // semantically equal documents share the same canonical form, and therefore the same digest. Streams
+        // holding several documents get a digest per document, each on a line of its own
+        let format = DocumentFormat::detect(self.from, self.file.as_deref());
+        for document in parse_documents(format, &buffer)? {
+            let digest = self.digest(&canonical_json(&document)?);
+            context
+                .render_pipeline
+                .send(cl_immediate!(Draw::Text(digest), Draw::NewLine))
+                .or(Err(ChiselError::DisplayListFailed))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+//! Arguments controlling the layout of pretty printed JSON, shared between the commands that produce it
+use crate::render::pretty_printer::{BraceStyle, FormatOptions, IndentChar, LineEnding};
+use clap::Args;
+
+/// Layout arguments, flattened into the arguments of a command
+#[derive(Debug, Args)]
+pub struct LayoutArgs {
+    /// Indent character
+    ///
+    /// The character used for indentation. The indent count applies to whichever character is chosen, so tabs will
+    /// usually want an indent of 1
+    #[arg(value_enum, long, value_name = "CHAR", default_value = "space")]
+    pub indent_char: IndentChar,
+
+    /// Align colons
+    ///
+    /// Line up the ":" characters of the pairs within each object, by padding out the shorter keys
+    #[arg(long)]
+    pub align_colons: bool,
+
+    /// Brace style
+    ///
+    /// Either K&R style, where opening braces and brackets follow on from their keys, or Allman style, where they
+    /// go on a line of their own beneath them
+    #[arg(value_enum, long, value_name = "STYLE", default_value = "kr")]
+    pub braces: BraceStyle,
+
+    /// Final newline
+    ///
+    /// Whether output ends with a line ending. If not specified, files edited in place end with one and other
+    /// output doesn't
+    #[arg(long, value_name = "BOOL")]
+    pub final_newline: Option<bool>,
+
+    /// Line endings
+    ///
+    /// The line endings used within the output
+    #[arg(value_enum, long, value_name = "ENDING", default_value = "lf")]
+    pub line_ending: LineEnding,
+}
+
+impl LayoutArgs {
+    /// Fold the layout arguments into a set of [FormatOptions], given whether the output is destined for a file
+    pub fn apply(&self, options: FormatOptions, to_file: bool) -> FormatOptions {
+        FormatOptions {
+            indent_char: self.indent_char,
+            align_colons: self.align_colons,
+            braces: self.braces,
+            final_newline: self.final_newline.unwrap_or(to_file),
+            line_ending: self.line_ending,
+            ..options
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use super::sax::{matched_to_char, parse_events, LineIndex};
+use super::{Command, CommandContext};
+use crate::cl_immediate;
+use crate::errors::{ChiselError, ChiselResult};
+use crate::formats::DocumentFormat;
+use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode, Draw};
+use crate::sources::{source_from_file, source_from_stdin};
+use chisel_json::coords::Span;
+use chisel_json::errors::ParserResult;
+use chisel_json::events::{Event, Match};
+use clap::Args;
+use std::path::PathBuf;
+
+/// A [Command] responsible for finding the JSON pointer(s) at a given position within a document
+#[derive(Debug, Args)]
+pub struct LocateCommand {
+    /// Position
+    ///
+    /// A (one-based) line and column, separated by a ':', e.g. 120:17. Columns are counted in characters
+    #[arg(
+        value_name = "LINE:COL",
+        value_parser = parse_position,
+        required_unless_present = "offset",
+        conflicts_with = "offset"
+    )]
+    pub position: Option<(usize, usize)>,
+
+    /// Source JSON file.
+    ///
+    /// If not specified, input is assumed to come from stdin.
+    #[arg(last = true, value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Input format
+    ///
+    /// The format of the input document. If not specified, then it's worked out from the extension of the source
+    /// file, falling back to JSON. Positions always refer to the original input
+    #[arg(value_enum, long, value_name = "FORMAT")]
+    pub from: Option<DocumentFormat>,
+
+    /// Byte offset
+    ///
+    /// A (zero-based) byte offset within the input, as an alternative to a line and column
+    #[arg(short, long, value_name = "N")]
+    pub offset: Option<usize>,
+
+    /// Delimiter
+    ///
+    /// The delimiter to be used in order to separate coordinates, type codes and pointer values
+    #[arg(short, long, default_value = ":")]
+    pub delimiter: char,
+}
+
+/// An element which encloses the target position
+struct Enclosing {
+    /// The type code of the element
+    code: char,
+    /// The JSON pointer for the element
+    pointer: String,
+    /// The half-open byte range covered by the element
+    range: (usize, usize),
+    /// The span of the element, as reported alongside the SAX events
+    span: Span,
+}
+
+/// Parse a position given in the form LINE:COL
+fn parse_position(position: &str) -> Result<(usize, usize), String> {
+    let (line, column) = position
+        .split_once(':')
+        .ok_or_else(|| String::from("expected a position of the form LINE:COL"))?;
+    match (line.trim().parse::<usize>(), column.trim().parse::<usize>()) {
+        (Ok(line), Ok(column)) if line > 0 && column > 0 => Ok((line, column)),
+        _ => Err(String::from("lines and columns must be positive integers")),
+    }
+}
+
+impl LocateCommand {
+    /// Process a single SAX event, tracking open containers and noting anything that encloses the target
+    fn handle_sax_event(
+        target: usize,
+        range: Option<(usize, usize)>,
+        open: &mut Vec<Enclosing>,
+        found: &mut Vec<Enclosing>,
+        evt: &Event,
+    ) -> ParserResult<()> {
+        let Some((start, end)) = range else {
+            return Ok(());
+        };
+        let pointer = evt.pointer.map(|p| p.to_string()).unwrap_or_default();
+        match evt.matched {
+            Match::StartObject | Match::StartArray => open.push(Enclosing {
+                code: matched_to_char(&evt.matched),
+                pointer,
+                range: (start, end),
+                span: evt.span,
+            }),
+            Match::EndObject | Match::EndArray => {
+                if let Some(mut container) = open.pop() {
+                    container.range.1 = end;
+                    container.span.end = evt.span.end;
+                    if container.range.0 <= target && target < end {
+                        found.push(container);
+                    }
+                }
+            }
+            _ if start <= target && target < end => found.push(Enclosing {
+                code: matched_to_char(&evt.matched),
+                pointer,
+                range: (start, end),
+                span: evt.span,
+            }),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Generate the display list for a single enclosing element
+    fn render_enclosing(&self, enclosing: &Enclosing) -> DisplayList {
+        let Span { start, end } = enclosing.span;
+        let fields = [
+            start.line.to_string(),
+            start.column.to_string(),
+            end.line.to_string(),
+            end.column.to_string(),
+            enclosing.code.to_string(),
+            enclosing.pointer.clone(),
+        ];
+        cl_immediate!(
+            Draw::Text(fields.join(&self.delimiter.to_string())),
+            Draw::NewLine
+        )
+    }
+}
+
+impl Command for LocateCommand {
+    /// Execute the locate action
+    fn execute(&mut self, context: &mut CommandContext) -> ChiselResult<()> {
+        let mut buffer: Vec<u8> = vec![];
+        if let Some(path) = &self.file {
+            source_from_file(path, &mut buffer)?;
+        } else {
+            source_from_stdin(&mut buffer)?;
+        }
+
+        // work out the target byte offset, making sure that it actually falls within the input
+        let lines = LineIndex::new(&buffer);
+        let (target, description) = match (self.position, self.offset) {
+            (Some((line, column)), _) => {
+                (lines.offset(line, column), format!("{}:{}", line, column))
+            }
+            (None, Some(offset)) => (
+                Some(offset).filter(|offset| *offset < buffer.len()),
+                format!("offset {}", offset),
+            ),
+            (None, None) => (None, String::new()),
+        };
+        let target = target.ok_or_else(|| ChiselError::PositionNotFound(description.clone()))?;
+
+        // the SAX span data is used to build up the extent of every element, so that anything which encloses the
+        // target can be collected.  Containers are only complete once they're closed, so the innermost elements
+        // are found first
+        let format = DocumentFormat::detect(self.from, self.file.as_deref());
+        let mut open: Vec<Enclosing> = vec![];
+        let mut found: Vec<Enclosing> = vec![];
+        parse_events(format, &buffer, &mut |evt, range| {
+            Self::handle_sax_event(target, range, &mut open, &mut found, evt)
+        })?;
+
+        if found.is_empty() {
+            return Err(ChiselError::PositionNotFound(description));
+        }
+        for enclosing in &found {
+            context
+                .render_pipeline
+                .send(self.render_enclosing(enclosing))
+                .or(Err(ChiselError::DisplayListFailed))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::pointers::PointersCommand;
+    use crate::render::display_lists::DisplayListCommand;
+    use clap::Parser;
+    use std::sync::mpsc::channel;
+
+    #[derive(Parser)]
+    struct Pointers {
+        #[command(flatten)]
+        command: PointersCommand,
+    }
+
+    #[derive(Parser)]
+    struct Locate {
+        #[command(flatten)]
+        command: LocateCommand,
+    }
+
+    /// Run a command, collecting whatever it outputs as a set of lines
+    fn output(command: &mut dyn Command) -> Vec<String> {
+        let (sender, receiver) = channel();
+        let mut context = CommandContext::new(sender);
+        command.execute(&mut context).unwrap();
+        drop(context);
+        let mut text = String::new();
+        for list in receiver {
+            for cmd in list.cmds {
+                match cmd {
+                    DisplayListCommand::Draw(Draw::Text(value)) => text.push_str(&value),
+                    DisplayListCommand::Draw(Draw::NewLine) => text.push('\n'),
+                    _ => (),
+                }
+            }
+        }
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn positions_reported_by_pointers_locate_the_same_elements() {
+        let path = std::env::temp_dir().join(format!("chiselj-locate-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            "{\"a/b\": {\"c\": [1, \"é\", 3]},\n  \"ü\": [true, null],\n  \"t~\": {\"日本\": \"語\", \"z\": [[], {}, 1.5]}}\n",
+        )
+        .unwrap();
+        let file = path.to_str().unwrap();
+
+        // scalars and keys are reported as they stand, whilst the extents of containers are trimmed down to the
+        // same fields that locate reports
+        let mut records = output(
+            &mut Pointers::parse_from([
+                "pointers",
+                "-t",
+                "keys,strings,integers,floats,booleans,nulls",
+                "--",
+                file,
+            ])
+            .command,
+        );
+        for record in output(&mut Pointers::parse_from(["pointers", "-x", "--", file]).command) {
+            let fields: Vec<&str> = record.split(':').collect();
+            let code = fields[4].to_lowercase();
+            records.push(
+                [&fields[..4], &[code.as_str(), fields[7]]]
+                    .concat()
+                    .join(":"),
+            );
+        }
+        assert_eq!(records.len(), 21);
+
+        // both the first and last characters of every element should locate that element before anything else
+        for record in &records {
+            let fields: Vec<&str> = record.split(':').collect();
+            for position in [&fields[0..2], &fields[2..4]] {
+                let position = position.join(":");
+                let located =
+                    output(&mut Locate::parse_from(["locate", &position, "--", file]).command);
+                assert_eq!(located.first(), Some(record), "at {}", position);
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+use super::documents::report_parse_error;
+use super::sax::parse_events;
+use super::{Command, CommandContext};
+use crate::errors::{ChiselError, ChiselResult};
+use crate::formats::DocumentFormat;
+use crate::render::compact_printer::CompactPrinter;
+use crate::sources::{reader_from_file, reader_from_stdin, source_from_file, source_from_stdin};
+use chisel_json::sax::Parser as SaxParser;
+use clap::Args;
+use std::path::PathBuf;
+
+/// A [Command] responsible for rendering documents as compactly as possible
+#[derive(Debug, Args)]
+pub struct MinifyCommand {
+    /// Source JSON file.
+    ///
+    /// If not specified, input is assumed to come from stdin.
+    #[arg(last = true, value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Input format
+    ///
+    /// The format of the input document. If not specified, then it's worked out from the extension of the source
+    /// file, falling back to JSON. Strings and numbers within JSON input are copied across exactly as they appear,
+    /// whereas other formats are re-serialised
+    #[arg(value_enum, long, value_name = "FORMAT")]
+    pub from: Option<DocumentFormat>,
+
+    /// Streaming
+    ///
+    /// Minify JSON input in constant memory, by writing output as the input is parsed rather than loading it all up
+    /// front. Output is written before the input has been fully validated, so invalid input may leave partial output
+    /// behind, and numbers are re-serialised rather than copied
+    #[arg(long)]
+    pub stream: bool,
+}
+
+impl MinifyCommand {
+    /// Minify the input a token at a time, as it's read
+    fn stream(&self, context: &CommandContext, format: DocumentFormat) -> ChiselResult<()> {
+        if format != DocumentFormat::Json {
+            return Err(ChiselError::FormatNotSupported(format!(
+                "streaming {} input",
+                format
+            )));
+        }
+        let mut reader = match &self.file {
+            Some(path) => reader_from_file(path)?,
+            None => reader_from_stdin()?,
+        };
+
+        let mut printer = CompactPrinter::new(context.clone_render_pipeline(), true);
+        let mut failure = None;
+        SaxParser::default()
+            .parse_buffer(&mut reader, &mut |evt| {
+                if failure.is_none() {
+                    failure = printer.render_event(&evt.matched, None).err();
+                }
+                Ok(())
+            })
+            .map_err(|err| {
+                report_parse_error(&err);
+                ChiselError::InvalidInput
+            })?;
+        match failure {
+            Some(err) => Err(err),
+            None => printer.finish(),
+        }
+    }
+}
+
+impl Command for MinifyCommand {
+    fn execute(&mut self, context: &mut CommandContext) -> ChiselResult<()> {
+        let format = DocumentFormat::detect(self.from, self.file.as_deref());
+        if self.stream {
+            return self.stream(context, format);
+        }
+
+        let mut buffer: Vec<u8> = vec![];
+        if let Some(path) = &self.file {
+            source_from_file(path, &mut buffer)?;
+        } else {
+            source_from_stdin(&mut buffer)?;
+        }
+
+        // the spans of JSON tokens cover their exact source text, which can be copied straight across
+        let mut printer = CompactPrinter::new(context.clone_render_pipeline(), false);
+        let mut failure = None;
+        parse_events(format, &buffer, &mut |evt, range| {
+            let raw = range
+                .filter(|_| format == DocumentFormat::Json)
+                .and_then(|(start, end)| std::str::from_utf8(&buffer[start..end]).ok());
+            if failure.is_none() {
+                failure = printer.render_event(&evt.matched, raw).err();
+            }
+            Ok(())
+        })?;
+        match failure {
+            Some(err) => Err(err),
+            None => printer.finish(),
+        }
+    }
+}
@@ -1,10 +1,23 @@
 use crate::{errors::ChiselResult, render::display_lists::DisplayList};
 use std::sync::mpsc::Sender;
 
+pub(crate) mod convert;
+pub(crate) mod delete;
+mod documents;
+pub(crate) mod dotted;
 pub(crate) mod filter;
+pub(crate) mod flatten;
+pub(crate) mod hash;
+mod layout;
+pub(crate) mod locate;
+pub(crate) mod minify;
 pub(crate) mod pointers;
 pub(crate) mod print;
+pub(crate) mod query;
 pub(crate) mod sax;
+pub(crate) mod set;
+pub(crate) mod transform;
+pub(crate) mod unflatten;
 
 /// An action context provides all the information and configuration needed to process an action
 #[derive(Debug)]
@@ -1,15 +1,37 @@
-use super::sax::{bit_filter, matched_to_bit, matched_to_char, PointerType};
+use super::sax::{
+    bit_filter, bits_to_chars, matched_to_bit, matched_to_char, matched_to_json, parse_events,
+    PointerType,
+};
 use super::{Command, CommandContext};
 use crate::cl_immediate;
-use crate::errors::ChiselResult;
+use crate::dom::{pointer_tokens, token_to_index};
+use crate::errors::{ChiselError, ChiselResult};
+use crate::escapes::{escape_tsv_field, quote_csv_field, quote_json_string};
+use crate::formats::DocumentFormat;
 use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode, Draw};
 use crate::sources::{source_from_file, source_from_stdin};
 use chisel_json::errors::ParserResult;
-use chisel_json::events::Event;
-use chisel_json::sax::Parser as SaxParser;
-use clap::Args;
+use chisel_json::events::{Event, Match};
+use clap::{Args, ValueEnum};
+use regex::Regex;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
+/// The different output formats supported by the pointers command
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum PointersFormat {
+    /// delimiter-separated fields, one pointer per line
+    Text,
+    /// a single JSON array of records
+    Json,
+    /// one JSON record per line
+    Ndjson,
+    /// RFC 4180 CSV, with a header row
+    Csv,
+    /// tab-separated values, with a header row
+    Tsv,
+}
+
 /// An [Command] responsible for filtering the input
 #[derive(Debug, Args)]
 pub struct PointersCommand {
@@ -19,6 +41,13 @@ pub struct PointersCommand {
     #[arg(last = true, value_name = "FILE")]
     pub file: Option<PathBuf>,
 
+    /// Input format
+    ///
+    /// The format of the input document. If not specified, then it's worked out from the extension of the source
+    /// file, falling back to JSON. Positions always refer to the original input
+    #[arg(value_enum, long, value_name = "FORMAT")]
+    pub from: Option<DocumentFormat>,
+
     /// Pointer types.
     ///
     /// If specified, only the JSON pointers corresponding to these specific element
@@ -26,50 +55,694 @@ pub struct PointersCommand {
     #[arg(value_enum, short, long, value_name = "TYPES", value_delimiter = ',')]
     pub types: Vec<PointerType>,
 
+    /// Pointer prefix
+    ///
+    /// If specified, only pointers at or beneath this pointer will be emitted, e.g. /statuses/0
+    #[arg(short, long, value_name = "POINTER")]
+    pub prefix: Option<String>,
+
+    /// Minimum depth
+    ///
+    /// If specified, only pointers with at least this many segments will be emitted. The root has a depth of zero
+    #[arg(long, value_name = "N")]
+    pub min_depth: Option<usize>,
+
+    /// Maximum depth
+    ///
+    /// If specified, only pointers with at most this many segments will be emitted
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// Pointer pattern
+    ///
+    /// If specified, only pointers matching this regular expression will be emitted. Any of the other filters are
+    /// applied first, so that the (relatively expensive) match is only performed where necessary
+    #[arg(short, long = "match", value_name = "REGEX")]
+    pub matches: Option<String>,
+
     /// Delimiter
     ///
     /// The delimiter to be used in order to separate type codes from pointer values
     #[arg(short, long, default_value = ":")]
     pub delimiter: char,
 
+    /// Values
+    ///
+    /// If specified, the matched value (serialised as JSON) is appended to each line. Only keys and scalar
+    /// values carry a value, containers don't
+    #[arg(short, long)]
+    pub values: bool,
+
+    /// Byte offsets
+    ///
+    /// If specified, the (zero-based) start and end byte offsets of each element, along with its length in bytes, are
+    /// included in the text output. The end offset is exclusive. The structured formats always include these
+    #[arg(short, long)]
+    pub bytes: bool,
+
+    /// Shape summary
+    ///
+    /// Rather than emitting every pointer, collapse array indices into '*' and emit each unique path once, along with
+    /// the number of times it occurs, the type codes observed for it and whether it's present within every parent.
+    /// Any prefix, depth or pattern filters are applied to the collapsed paths, where an array index within the prefix
+    /// matches any collapsed index
+    #[arg(short, long, conflicts_with_all = ["values", "bytes"])]
+    pub shape: bool,
+
+    /// Container extents
+    ///
+    /// Rather than emitting every pointer, emit each object (O) and array (A) once it has been closed, with an extent
+    /// running from its opening through to its closing bracket, along with its number of children and its depth
+    #[arg(short = 'x', long, conflicts_with_all = ["values", "shape"])]
+    pub extents: bool,
+
+    /// Output format
+    ///
+    /// The structured formats carry the same information as the text format, but as named fields which are quoted
+    /// and escaped appropriately
+    #[arg(value_enum, short, long, default_value = "text")]
+    pub format: PointersFormat,
+
     /// The currently operating filter, will default to [ALL]
     #[clap(skip)]
     pub filter: u8,
+
+    /// The compiled pointer pattern, if there is one
+    #[clap(skip)]
+    regex: Option<Regex>,
+
+    /// Tracks the position within the input against the prefix, if there is one
+    #[clap(skip)]
+    prefix_path: Option<PrefixPath>,
+
+    /// The number of records emitted so far
+    #[clap(skip)]
+    pub count: usize,
+
+    /// The shape summary, built up whilst parsing when in shape mode
+    #[clap(skip)]
+    shapes: Box<ShapeSummary>,
+
+    /// The currently open containers, when in extents mode
+    #[clap(skip)]
+    open: Vec<OpenContainer>,
+}
+
+/// A container which has been opened, but not yet closed
+#[derive(Debug)]
+struct OpenContainer {
+    /// The line on which the container was opened
+    line: usize,
+    /// The column at which the container was opened
+    column: usize,
+    /// The byte offset at which the container was opened
+    start_byte: usize,
+    /// The number of children seen so far
+    children: usize,
+    /// The depth of the container
+    depth: usize,
+    /// The pointer for the container, only present if it might be emitted
+    pointer: Option<String>,
+}
+
+/// A container which is currently open, as seen by a [PrefixPath]
+#[derive(Debug)]
+struct PrefixFrame {
+    /// Whether the container is an array (rather than an object)
+    array: bool,
+    /// The index of the next element, for arrays
+    next_index: usize,
+    /// Whether the container's own pointer agrees with the prefix, for as many components as it has
+    on_path: bool,
+    /// Whether the current member's pointer agrees with the prefix, for objects
+    member_on_path: bool,
+}
+
+/// Follows the structure of the input alongside the SAX parser, comparing each component of the current position
+/// against the reference tokens of the prefix. This means that pointers outside of the prefix can be rejected
+/// without ever having to be serialised
+#[derive(Debug)]
+struct PrefixPath {
+    /// The (unescaped) reference tokens of the prefix
+    tokens: Vec<String>,
+    /// Each of the tokens interpreted as an array index, where possible
+    indices: Vec<Option<usize>>,
+    /// The currently open containers
+    stack: Vec<PrefixFrame>,
+}
+
+impl PrefixPath {
+    /// Create a new path for a given prefix, failing if the prefix isn't a valid pointer
+    fn new(prefix: &str) -> ChiselResult<Self> {
+        let tokens = pointer_tokens(prefix)?;
+        let indices = tokens.iter().map(|t| token_to_index(t)).collect();
+        Ok(PrefixPath {
+            tokens,
+            indices,
+            stack: vec![],
+        })
+    }
+
+    /// Fold a single SAX event into the path, returning whether the event's pointer lies at or beneath the prefix
+    fn observe(&mut self, evt: &Event) -> bool {
+        // the depth of the event's pointer, i.e. the number of components it has beyond the root
+        let depth = self.stack.len();
+        let on_path = match &evt.matched {
+            Match::StartOfInput | Match::EndOfInput => return false,
+            Match::EndObject | Match::EndArray => {
+                return match self.stack.pop() {
+                    Some(frame) => frame.on_path && depth > self.tokens.len(),
+                    None => false,
+                }
+            }
+            Match::ObjectKey(key) => {
+                let Some(parent) = self.stack.last_mut() else {
+                    return false;
+                };
+                // keys appear within pointers with any quotes removed, just as the parser does
+                parent.member_on_path = parent.on_path
+                    && self.tokens.get(depth - 1).map_or(true, |token| {
+                        key.chars().filter(|c| *c != '"').eq(token.chars())
+                    });
+                return parent.member_on_path && depth >= self.tokens.len();
+            }
+            _ => match self.stack.last_mut() {
+                Some(parent) if parent.array => {
+                    let index = parent.next_index;
+                    parent.next_index += 1;
+                    parent.on_path
+                        && self
+                            .indices
+                            .get(depth - 1)
+                            .map_or(true, |token| *token == Some(index))
+                }
+                Some(parent) => parent.member_on_path,
+                None => true,
+            },
+        };
+        match evt.matched {
+            Match::StartObject | Match::StartArray => self.stack.push(PrefixFrame {
+                array: matches!(evt.matched, Match::StartArray),
+                next_index: 0,
+                on_path,
+                member_on_path: false,
+            }),
+            _ => (),
+        }
+        on_path && depth >= self.tokens.len()
+    }
+
+    /// Checks whether a shape's path lies at or beneath the prefix. Array indices are collapsed into '*' within shape
+    /// paths, so any index within the prefix is taken to match one
+    fn covers_shape(&self, pointer: &str) -> bool {
+        let Ok(components) = pointer_tokens(pointer) else {
+            return false;
+        };
+        components.len() >= self.tokens.len()
+            && self.tokens.iter().zip(&self.indices).zip(&components).all(
+                |((token, index), component)| {
+                    component == token || (component == "*" && index.is_some())
+                },
+            )
+    }
+}
+
+/// A single field within an output record
+enum Field {
+    /// A numeric field
+    Number(usize),
+    /// A boolean field
+    Boolean(bool),
+    /// A plain text field, which requires quoting in order to be valid JSON
+    Text(String),
+    /// A field which is already valid JSON, if present
+    Json(Option<String>),
+}
+
+impl Field {
+    /// The field as plain text, as used by the delimited formats
+    fn to_text(&self) -> String {
+        match self {
+            Field::Number(n) => n.to_string(),
+            Field::Boolean(b) => b.to_string(),
+            Field::Text(s) => s.clone(),
+            Field::Json(json) => json.clone().unwrap_or_default(),
+        }
+    }
+
+    /// The field as a JSON value
+    fn to_json(&self) -> String {
+        match self {
+            Field::Text(s) => quote_json_string(s),
+            Field::Json(json) => json.clone().unwrap_or_else(|| String::from("null")),
+            field => field.to_text(),
+        }
+    }
+}
+
+/// A list of named fields, making up a single output record
+type Record = Vec<(&'static str, Field)>;
+
+/// The information emitted for each matched pointer
+struct PointerRecord {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+    start_byte: usize,
+    end_byte: usize,
+    code: char,
+    pointer: String,
+    value: Option<String>,
+    /// The number of children and depth, for container extents
+    extent: Option<(usize, usize)>,
+}
+
+impl PointerRecord {
+    /// The names of the fields within a record, as used for headers and JSON member names
+    fn field_names(bytes: bool, values: bool, extents: bool) -> Vec<&'static str> {
+        let mut names = vec!["start_line", "start_column", "end_line", "end_column"];
+        if bytes {
+            names.extend(["start_byte", "end_byte", "length"]);
+        }
+        names.push("type");
+        if extents {
+            names.extend(["children", "depth"]);
+        }
+        names.push("pointer");
+        if values {
+            names.push("value");
+        }
+        names
+    }
+
+    /// Convert into a list of fields, optionally including byte offsets and values
+    fn into_record(self, bytes: bool, values: bool) -> Record {
+        let mut record = vec![
+            ("start_line", Field::Number(self.start_line)),
+            ("start_column", Field::Number(self.start_column)),
+            ("end_line", Field::Number(self.end_line)),
+            ("end_column", Field::Number(self.end_column)),
+        ];
+        if bytes {
+            record.extend([
+                ("start_byte", Field::Number(self.start_byte)),
+                ("end_byte", Field::Number(self.end_byte)),
+                ("length", Field::Number(self.end_byte - self.start_byte)),
+            ]);
+        }
+        record.push(("type", Field::Text(self.code.to_string())));
+        if let Some((children, depth)) = self.extent {
+            record.extend([
+                ("children", Field::Number(children)),
+                ("depth", Field::Number(depth)),
+            ]);
+        }
+        record.push(("pointer", Field::Text(self.pointer)));
+        if values {
+            record.push(("value", Field::Json(self.value)));
+        }
+        record
+    }
+}
+
+/// Everything observed for a single (normalised) path whilst building a shape summary
+#[derive(Debug)]
+struct Shape {
+    /// The path, with array indices replaced by '*'
+    pointer: String,
+    /// The index of the parent [Shape], if there is one
+    parent: Option<usize>,
+    /// Whether this path is an array element (rather than an object member)
+    element: bool,
+    /// Total number of occurrences
+    count: usize,
+    /// The bits for all the observed types
+    types: u8,
+    /// The number of distinct parent containers this path has been seen within
+    parents: usize,
+    /// The id of the last parent container this path was seen within
+    last_parent: Option<usize>,
+    /// The number of times this path has been an object
+    objects: usize,
+    /// The number of times this path has been an array
+    arrays: usize,
+}
+
+/// Collects [Shape] information from a stream of SAX events
+#[derive(Debug, Default)]
+struct ShapeSummary {
+    /// The currently open containers, as (is array, container id, shape index) tuples
+    stack: Vec<(bool, usize, usize)>,
+    /// The next container id to be allocated
+    next_id: usize,
+    /// Lookup from normalised path to shape index
+    index: HashMap<String, usize>,
+    /// All the shapes, in the order they were first seen
+    shapes: Vec<Shape>,
+}
+
+impl ShapeSummary {
+    /// Fold a single SAX event into the summary
+    fn observe(&mut self, evt: &Event) {
+        match evt.matched {
+            Match::EndObject | Match::EndArray => {
+                self.stack.pop();
+                return;
+            }
+            Match::StartOfInput | Match::EndOfInput | Match::ObjectKey(_) => return,
+            _ => (),
+        }
+        let Some(p) = evt.pointer else {
+            return;
+        };
+
+        // derive the normalised path from that of the parent container
+        let parent = self.stack.last().copied();
+        let pointer = match parent {
+            Some((true, _, shape)) => format!("{}/*", self.shapes[shape].pointer),
+            Some((false, _, shape)) => {
+                let pointer = p.to_string();
+                let name = pointer.rsplit('/').next().unwrap_or_default();
+                format!("{}/{}", self.shapes[shape].pointer, name)
+            }
+            None => String::new(),
+        };
+
+        let index = match self.index.get(&pointer) {
+            Some(index) => *index,
+            None => {
+                self.shapes.push(Shape {
+                    pointer: pointer.clone(),
+                    parent: parent.map(|(_, _, shape)| shape),
+                    element: matches!(parent, Some((true, _, _))),
+                    count: 0,
+                    types: 0,
+                    parents: 0,
+                    last_parent: None,
+                    objects: 0,
+                    arrays: 0,
+                });
+                self.index.insert(pointer, self.shapes.len() - 1);
+                self.shapes.len() - 1
+            }
+        };
+
+        let shape = &mut self.shapes[index];
+        shape.count += 1;
+        shape.types |= matched_to_bit(&evt.matched);
+        let parent_id = parent.map(|(_, id, _)| id);
+        if shape.last_parent != parent_id {
+            shape.parents += 1;
+            shape.last_parent = parent_id;
+        }
+        match evt.matched {
+            Match::StartObject => {
+                shape.objects += 1;
+                self.stack.push((false, self.next_id, index));
+                self.next_id += 1;
+            }
+            Match::StartArray => {
+                shape.arrays += 1;
+                self.stack.push((true, self.next_id, index));
+                self.next_id += 1;
+            }
+            _ => (),
+        }
+    }
+
+    /// Checks whether a given shape is present within every one of its parent containers
+    fn is_required(&self, shape: &Shape) -> bool {
+        match shape.parent {
+            Some(parent) if shape.element => shape.parents == self.shapes[parent].arrays,
+            Some(parent) => shape.parents == self.shapes[parent].objects,
+            None => true,
+        }
+    }
+
+    /// The names of the fields within a shape record
+    fn field_names() -> Vec<&'static str> {
+        vec!["count", "types", "required", "pointer"]
+    }
+
+    /// Generate records for all the shapes with types matching a given filter
+    fn records(&self, filter: u8, selected: impl Fn(usize, &str) -> bool) -> Vec<Record> {
+        self.shapes
+            .iter()
+            .filter(|shape| shape.types & filter > 0)
+            .filter(|shape| selected(shape.pointer.matches('/').count(), &shape.pointer))
+            .map(|shape| {
+                vec![
+                    ("count", Field::Number(shape.count)),
+                    ("types", Field::Text(bits_to_chars(shape.types))),
+                    ("required", Field::Boolean(self.is_required(shape))),
+                    ("pointer", Field::Text(shape.pointer.clone())),
+                ]
+            })
+            .collect()
+    }
 }
 
 impl PointersCommand {
     /// All the SAX event processing passes through here
-    fn handle_sax_event(&self, context: &CommandContext, evt: &Event) -> ParserResult<()> {
-        if (matched_to_bit(&evt.matched) & self.filter) > 0 {
-            match evt.pointer {
-                Some(p) => {
-                    let _ = context.render_pipeline.send(cl_immediate!(
-                        Draw::Text(format!("{}", evt.span.start.line)),
-                        Draw::Char(self.delimiter),
-                        Draw::Text(format!("{}", evt.span.start.column)),
-                        Draw::Char(self.delimiter),
-                        Draw::Text(format!("{}", evt.span.end.line)),
-                        Draw::Char(self.delimiter),
-                        Draw::Text(format!("{}", evt.span.end.column)),
-                        Draw::Char(self.delimiter),
-                        Draw::Char(matched_to_char(&evt.matched)),
-                        Draw::Char(self.delimiter),
-                        Draw::Text(p.to_string()),
-                        Draw::NewLine
-                    ));
+    fn handle_sax_event(
+        &mut self,
+        context: &CommandContext,
+        range: Option<(usize, usize)>,
+        evt: &Event,
+    ) -> ParserResult<()> {
+        if self.shape {
+            self.shapes.observe(evt);
+            return Ok(());
+        }
+
+        // the prefix has to follow every event in order to keep track of the position within the input
+        let prefixed = self
+            .prefix_path
+            .as_mut()
+            .map_or(true, |path| path.observe(evt));
+        let (start_byte, end_byte) = range.unwrap_or_default();
+        if self.extents {
+            self.handle_extent(context, evt, start_byte, end_byte, prefixed);
+            return Ok(());
+        }
+        if prefixed && (matched_to_bit(&evt.matched) & self.filter) > 0 {
+            // the depth can be checked without having to serialise the pointer, and a pointer's length includes the
+            // root component, if it has any
+            let Some(p) = evt.pointer else {
+                return Ok(());
+            };
+            if !self.within_depth(p.len().saturating_sub(1)) {
+                return Ok(());
+            }
+            let pointer = p.to_string();
+            if self.matches_pattern(&pointer) {
+                let record = PointerRecord {
+                    start_line: evt.span.start.line,
+                    start_column: evt.span.start.column,
+                    end_line: evt.span.end.line,
+                    end_column: evt.span.end.column,
+                    start_byte,
+                    end_byte,
+                    code: matched_to_char(&evt.matched),
+                    pointer,
+                    value: matched_to_json(&evt.matched),
+                    extent: None,
+                };
+                self.emit(context, record);
+            }
+        }
+        Ok(())
+    }
+
+    /// Track containers as they're opened and closed, emitting each one along with its full extent as it's closed
+    fn handle_extent(
+        &mut self,
+        context: &CommandContext,
+        evt: &Event,
+        start_byte: usize,
+        end_byte: usize,
+        prefixed: bool,
+    ) {
+        match evt.matched {
+            Match::StartOfInput | Match::EndOfInput | Match::ObjectKey(_) => (),
+            Match::EndObject | Match::EndArray => {
+                let Some(open) = self.open.pop() else {
+                    return;
+                };
+                if let Some(pointer) = open.pointer.filter(|p| self.matches_pattern(p)) {
+                    let record = PointerRecord {
+                        start_line: open.line,
+                        start_column: open.column,
+                        end_line: evt.span.end.line,
+                        end_column: evt.span.end.column,
+                        start_byte: open.start_byte,
+                        end_byte,
+                        code: matched_to_char(&evt.matched),
+                        pointer,
+                        value: None,
+                        extent: Some((open.children, open.depth)),
+                    };
+                    self.emit(context, record);
+                }
+            }
+            _ => {
+                if let Some(parent) = self.open.last_mut() {
+                    parent.children += 1;
                 }
-                None => (),
+                if matches!(evt.matched, Match::StartObject | Match::StartArray) {
+                    // only bother serialising the pointer if the container could actually be emitted
+                    let bit = matched_to_bit(&evt.matched);
+                    let depth = self.open.len();
+                    let pointer = evt
+                        .pointer
+                        .filter(|_| prefixed && bit & self.filter > 0 && self.within_depth(depth))
+                        .map(|p| p.to_string());
+                    self.open.push(OpenContainer {
+                        line: evt.span.start.line,
+                        column: evt.span.start.column,
+                        start_byte,
+                        children: 0,
+                        depth,
+                        pointer,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Send a single record down the rendering pipeline
+    fn emit(&mut self, context: &CommandContext, record: PointerRecord) {
+        let record = match self.format {
+            PointersFormat::Text => record.into_record(self.bytes, self.values),
+            _ => record.into_record(true, self.values),
+        };
+        let _ = context.render_pipeline.send(self.render_record(&record));
+        self.count += 1;
+    }
+
+    /// Checks whether a given depth lies within any specified depth bounds
+    fn within_depth(&self, depth: usize) -> bool {
+        self.min_depth.map_or(true, |min| depth >= min)
+            && self.max_depth.map_or(true, |max| depth <= max)
+    }
+
+    /// Checks whether a pointer satisfies any specified pattern filter
+    fn matches_pattern(&self, pointer: &str) -> bool {
+        self.regex.as_ref().map_or(true, |r| r.is_match(pointer))
+    }
+
+    /// Checks whether a shape's (normalised) path satisfies any specified prefix and pattern filters. Shape paths
+    /// have their indices collapsed, so can only be compared against the prefix once they've been built
+    fn selected(&self, pointer: &str) -> bool {
+        self.prefix_path
+            .as_ref()
+            .map_or(true, |path| path.covers_shape(pointer))
+            && self.matches_pattern(pointer)
+    }
+
+    /// Generate the display list for a single record
+    fn render_record(&self, record: &Record) -> DisplayList {
+        let line = match self.format {
+            PointersFormat::Text => record
+                .iter()
+                .filter(|(_, field)| !matches!(field, Field::Json(None)))
+                .map(|(_, field)| field.to_text())
+                .collect::<Vec<String>>()
+                .join(&self.delimiter.to_string()),
+            PointersFormat::Json if self.count == 0 => format!("  {}", record_to_json(record)),
+            PointersFormat::Json => format!(",\n  {}", record_to_json(record)),
+            PointersFormat::Ndjson => record_to_json(record),
+            PointersFormat::Csv => record
+                .iter()
+                .map(|(_, field)| quote_csv_field(&field.to_text()))
+                .collect::<Vec<String>>()
+                .join(","),
+            PointersFormat::Tsv => record
+                .iter()
+                .map(|(_, field)| escape_tsv_field(&field.to_text()))
+                .collect::<Vec<String>>()
+                .join("\t"),
+        };
+        match self.format {
+            PointersFormat::Json => cl_immediate!(Draw::Text(line)),
+            _ => cl_immediate!(Draw::Text(line), Draw::NewLine),
+        }
+    }
+
+    /// Anything that needs to be output ahead of the first record
+    fn render_prologue(&self, context: &CommandContext) -> ChiselResult<()> {
+        let names = if self.shape {
+            ShapeSummary::field_names()
+        } else {
+            PointerRecord::field_names(true, self.values, self.extents)
+        };
+        let prologue = match self.format {
+            PointersFormat::Json => cl_immediate!(Draw::Char('['), Draw::NewLine),
+            PointersFormat::Csv => cl_immediate!(Draw::Text(names.join(",")), Draw::NewLine),
+            PointersFormat::Tsv => cl_immediate!(Draw::Text(names.join("\t")), Draw::NewLine),
+            _ => return Ok(()),
+        };
+        context
+            .render_pipeline
+            .send(prologue)
+            .or(Err(ChiselError::DisplayListFailed))
+    }
+
+    /// Anything that needs to be output after the last record
+    fn render_epilogue(&self, context: &CommandContext) -> ChiselResult<()> {
+        let epilogue = match self.format {
+            PointersFormat::Json if self.count > 0 => {
+                cl_immediate!(Draw::NewLine, Draw::Char(']'), Draw::NewLine)
             }
+            PointersFormat::Json => cl_immediate!(Draw::Char(']'), Draw::NewLine),
+            _ => return Ok(()),
+        };
+        context
+            .render_pipeline
+            .send(epilogue)
+            .or(Err(ChiselError::DisplayListFailed))
+    }
+
+    /// Output the shape summary, once all the input has been consumed
+    fn render_shapes(&mut self, context: &CommandContext) -> ChiselResult<()> {
+        let records = self.shapes.records(self.filter, |depth, pointer| {
+            self.within_depth(depth) && self.selected(pointer)
+        });
+        for record in records {
+            context
+                .render_pipeline
+                .send(self.render_record(&record))
+                .or(Err(ChiselError::DisplayListFailed))?;
+            self.count += 1;
         }
         Ok(())
     }
 }
 
+/// Render a record as a single JSON object
+fn record_to_json(record: &Record) -> String {
+    let members = record
+        .iter()
+        .map(|(name, field)| format!("\"{}\":{}", name, field.to_json()))
+        .collect::<Vec<String>>();
+    format!("{{{}}}", members.join(","))
+}
+
 impl Command for PointersCommand {
     fn execute(&mut self, context: &mut CommandContext) -> ChiselResult<()> {
         // create the bit filter to be used as we filter SAX events
         self.filter = bit_filter(&self.types);
 
+        // validate the prefix and compile the pattern up front, prior to reading any input
+        if let Some(prefix) = &self.prefix {
+            self.prefix_path = Some(PrefixPath::new(prefix)?);
+        }
+        if let Some(pattern) = &self.matches {
+            self.regex =
+                Some(Regex::new(pattern).map_err(|e| ChiselError::InvalidPattern(e.to_string()))?);
+        }
+
         // sort out some argument related stuff and populate the buffer
         let mut buffer: Vec<u8> = vec![];
         if let Some(path) = &self.file {
@@ -78,11 +751,16 @@ impl Command for PointersCommand {
             source_from_stdin(&mut buffer)?;
         }
 
-        // instantiate a SAX parser instance and process the input, by delegating
+        // process the input as a stream of SAX events (whatever its format), by delegating
         // to the `handle_sax_event` associated function
-        let parser = SaxParser::default();
-        let _result = parser.parse_bytes(&buffer, &mut |evt| self.handle_sax_event(context, evt));
-
-        Ok(())
+        self.render_prologue(context)?;
+        let format = DocumentFormat::detect(self.from, self.file.as_deref());
+        parse_events(format, &buffer, &mut |evt, range| {
+            self.handle_sax_event(context, range, evt)
+        })?;
+        if self.shape {
+            self.render_shapes(context)?;
+        }
+        self.render_epilogue(context)
     }
 }
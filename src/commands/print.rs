@@ -1,11 +1,18 @@
 use std::path::PathBuf;
 
+use super::documents::parse_documents_with;
+use super::layout::LayoutArgs;
 use super::{Command, CommandContext};
-use crate::errors::ChiselResult;
+use crate::cl_immediate;
+use crate::dom::pointer_tokens;
+use crate::errors::{ChiselError, ChiselResult};
+use crate::formats::bson::ExtendedJson;
+use crate::formats::{DocumentFormat, ReadOptions};
+use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode};
+use crate::render::ordering::KeyOrder;
 use crate::render::pretty_printer::{FormatOptions, PrettyPrinter};
+use crate::sinks::terminal_width;
 use crate::sources::{source_from_file, source_from_stdin};
-use chisel_json::coords::Coords;
-use chisel_json::dom::Parser as DomParser;
 use clap::Args;
 
 /// An [Action] responsible for just printing (pretty or otherwise) the input
@@ -18,6 +25,20 @@ pub struct PrintCommand {
     #[arg(last = true, value_name = "FILE")]
     pub file: Option<PathBuf>,
 
+    /// Input format
+    ///
+    /// The format of the input document. If not specified, then it's worked out from the extension of the source
+    /// file, falling back to JSON
+    #[arg(value_enum, long, value_name = "FORMAT")]
+    pub from: Option<DocumentFormat>,
+
+    /// Extended JSON mode
+    ///
+    /// How BSON input is rendered. Canonical mode wraps every value so that its exact BSON type is preserved, whereas
+    /// relaxed mode shows numbers and dates as plain JSON wherever it can
+    #[arg(value_enum, long, value_name = "MODE", default_value = "relaxed")]
+    pub extended_json: ExtendedJson,
+
     /// Indent space count
     ///
     /// Object keys and array values are idented by this amount plus the parent identation amount
@@ -29,6 +50,45 @@ pub struct PrintCommand {
     /// The number of spaces added to each side of the ":" character in a <key> : <value> pair
     #[arg(short, long, value_name = "n", default_value = "1")]
     pub kvpadding: u16,
+
+    /// Sort object keys
+    ///
+    /// Sort the members of every object by key, either by code point (lexicographic), with runs of digits compared
+    /// by their numeric value (natural), or using the Unicode Collation Algorithm (unicode)
+    #[arg(value_enum, long, value_name = "ORDER", num_args = 0..=1, default_missing_value = "lexicographic")]
+    pub sort_keys: Option<KeyOrder>,
+
+    /// Sort arrays of scalars
+    ///
+    /// Sort the elements of any array holding only scalars. Values are ordered by type (null, false, true, numbers,
+    /// strings) and then by value, with strings following the key order if one is given
+    #[arg(long)]
+    pub sort_arrays: bool,
+
+    /// Sort arrays of objects
+    ///
+    /// Sort the elements of any array holding only objects, by the value that the given JSON pointer resolves to
+    /// within each object. Objects where the pointer doesn't resolve are placed last, in their original order
+    #[arg(long, value_name = "POINTER")]
+    pub sort_arrays_by: Option<String>,
+
+    /// Output width
+    ///
+    /// Draw any object or array on a single line if it fits within this many columns, expanding everything else. If
+    /// given without a value, the width of the terminal is used
+    #[arg(long, value_name = "n", num_args = 0..=1)]
+    pub width: Option<Option<usize>>,
+
+    /// Canonical output
+    ///
+    /// Render each document in the canonical form defined by RFC 8785 (the JSON Canonicalization Scheme), with keys
+    /// sorted, numbers formatted as per ECMAScript, minimal escaping and no whitespace. Formatting options are
+    /// ignored, other than the final newline and line ending
+    #[arg(long)]
+    pub canonical: bool,
+
+    #[command(flatten)]
+    pub layout: LayoutArgs,
 }
 
 impl Command for PrintCommand {
@@ -42,32 +102,50 @@ impl Command for PrintCommand {
             source_from_stdin(&mut buffer)?;
         }
 
-        // grab a DOM parser and build ourselves some JSON
-        let parser = DomParser::default();
-        let parse_result = parser.parse_bytes(&buffer);
-
-        match parse_result {
-            Ok(json) => {
-                // extract the formatting options from the context args
-                let options = FormatOptions {
+        // build ourselves some JSON, from whatever format the input happens to be in. Any parse failure will
+        // already have been reported by this point. Streams holding several documents (YAML, or concatenated binary
+        // documents such as mongodump output) are printed one after the other
+        let format = DocumentFormat::detect(self.from, self.file.as_deref());
+        let read_options = ReadOptions {
+            extended_json: self.extended_json,
+            ..ReadOptions::default()
+        };
+        if let Ok(documents) = parse_documents_with(format, &buffer, &read_options) {
+            // extract the formatting options from the context args
+            let options = self.layout.apply(
+                FormatOptions {
                     indent: self.indent,
                     kvpadding: self.kvpadding,
-                };
+                    sort_keys: self.sort_keys,
+                    sort_arrays: self.sort_arrays,
+                    sort_arrays_by: match &self.sort_arrays_by {
+                        Some(pointer) => Some(pointer_tokens(pointer)?),
+                        None => None,
+                    },
+                    width: self.width.map(|width| width.unwrap_or_else(terminal_width)),
+                    ..FormatOptions::default()
+                },
+                false,
+            );
 
-                // boof it out to the printer
-                let printer = PrettyPrinter::new(context.clone_render_pipeline(), options);
-                printer.render_json(json)?
-            }
-            Err(err) => {
-                eprintln!("Parse failed!");
-                eprintln!("\tFailed at stage: {}", err.source);
-                eprintln!("\tError reported: {}", err.details);
-                eprintln!(
-                    "\tInput coords: {}",
-                    err.coords.unwrap_or(Coords::default())
-                );
+            // boof it out to the printer
+            // documents are separated by a line ending, unless they already end with one
+            let separate = !options.final_newline;
+            let newline = options.line_ending.draw();
+            let printer = PrettyPrinter::new(context.clone_render_pipeline(), options);
+            for (index, json) in documents.into_iter().enumerate() {
+                if index > 0 && separate {
+                    context
+                        .render_pipeline
+                        .send(cl_immediate!(newline.clone()))
+                        .or(Err(ChiselError::DisplayListFailed))?;
+                }
+                match self.canonical {
+                    true => printer.render_json_canonical(&json)?,
+                    false => printer.render_json(json)?,
+                }
             }
-        };
+        }
         Ok(())
     }
 }
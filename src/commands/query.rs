@@ -0,0 +1,112 @@
+use std::path::PathBuf;
+
+use super::documents::parse_document;
+use super::{Command, CommandContext};
+use crate::cl_immediate;
+use crate::dom::clone_value;
+use crate::errors::{ChiselError, ChiselResult};
+use crate::formats::DocumentFormat;
+use crate::jsonpath::Query;
+use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode, Draw};
+use crate::render::pretty_printer::{FormatOptions, PrettyPrinter};
+use crate::sources::{source_from_file, source_from_stdin};
+use chisel_json::JsonValue;
+use clap::{Args, ValueEnum};
+
+/// The different ways in which query results may be output
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum QueryOutput {
+    /// output the matched values, as a JSON array
+    Values,
+    /// output the normalized path of each match, one per line
+    Paths,
+    /// output the JSON pointer for each match, one per line
+    Pointers,
+}
+
+/// A [Command] responsible for evaluating JSONPath (RFC 9535) queries
+#[derive(Debug, Args)]
+pub struct QueryCommand {
+    /// JSONPath query.
+    ///
+    /// The query to evaluate, e.g. '$.statuses[?@.retweet_count > 10].user.screen_name'
+    #[arg(value_name = "QUERY")]
+    pub query: String,
+
+    /// Source JSON file.
+    ///
+    /// If not specified, input is assumed to come from stdin.
+    #[arg(last = true, value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Input format
+    ///
+    /// The format of the input document. If not specified, then it's worked out from the extension of the source
+    /// file, falling back to JSON
+    #[arg(value_enum, long, value_name = "FORMAT")]
+    pub from: Option<DocumentFormat>,
+
+    /// Output
+    ///
+    /// Whether to output the matched values, or their locations
+    #[arg(value_enum, short, long, default_value = "values")]
+    pub output: QueryOutput,
+
+    /// Indent space count
+    ///
+    /// Object keys and array values are idented by this amount plus the parent identation amount
+    #[arg(short, long, value_name = "n", default_value = "2")]
+    pub indent: u16,
+
+    /// KV padding count
+    ///
+    /// The number of spaces added to each side of the ":" character in a <key> : <value> pair
+    #[arg(short, long, value_name = "n", default_value = "1")]
+    pub kvpadding: u16,
+}
+
+impl Command for QueryCommand {
+    /// Execute the query action
+    fn execute(&mut self, context: &mut CommandContext) -> ChiselResult<()> {
+        // parse the query up front, so that errors are reported before any input is read
+        let query = Query::parse(&self.query)?;
+
+        let mut buffer: Vec<u8> = vec![];
+        if let Some(path) = &self.file {
+            source_from_file(path, &mut buffer)?;
+        } else {
+            source_from_stdin(&mut buffer)?;
+        }
+
+        let format = DocumentFormat::detect(self.from, self.file.as_deref());
+        let json = parse_document(format, &buffer)?;
+
+        let nodes = query.select(&json);
+        match self.output {
+            QueryOutput::Values => {
+                let results =
+                    JsonValue::Array(nodes.iter().map(|n| clone_value(n.value)).collect());
+                let options = FormatOptions {
+                    indent: self.indent,
+                    kvpadding: self.kvpadding,
+                    ..FormatOptions::default()
+                };
+                let printer = PrettyPrinter::new(context.clone_render_pipeline(), options);
+                printer.render_json(results)
+            }
+            QueryOutput::Paths | QueryOutput::Pointers => {
+                for node in nodes {
+                    let location = match self.output {
+                        QueryOutput::Paths => node.normalized_path(),
+                        _ => node.pointer(),
+                    };
+                    context
+                        .render_pipeline
+                        .send(cl_immediate!(Draw::Text(location), Draw::NewLine))
+                        .or(Err(ChiselError::DisplayListFailed))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -1,7 +1,15 @@
 //! All utility and useful functions relating to SAX-based parsing should go in here
 
-use chisel_json::events::Match;
+use super::documents::{report_format_error, report_parse_error};
+use crate::errors::{ChiselError, ChiselResult};
+use crate::formats::{self, DocumentFormat, Node, NodeValue};
+use chisel_json::coords::{Coords, Span};
+use chisel_json::errors::ParserResult;
+use chisel_json::events::{Event, Match};
+use chisel_json::pointer::JsonPointer;
+use chisel_json::sax::Parser as SaxParser;
 use clap::ValueEnum;
+use std::borrow::Cow;
 
 // set of bits for building a filter
 
@@ -81,8 +89,23 @@ pub(crate) fn matched_to_char(m: &Match) -> char {
     }
 }
 
+/// Converts a specific [Match] emitted by the SAX parser into its JSON representation. Strings and keys are held by
+/// the parser in their raw (quoted and escaped) form, and so are already valid JSON. Returns [None] for anything that
+/// doesn't carry a value, such as the start of a container
+pub(crate) fn matched_to_json(m: &Match) -> Option<String> {
+    match m {
+        Match::ObjectKey(key) => Some(key.to_string()),
+        Match::String(value) => Some(value.to_string()),
+        Match::Integer(value) => Some(value.to_string()),
+        Match::Float(value) => Some(value.to_string()),
+        Match::Boolean(value) => Some(value.to_string()),
+        Match::Null => Some(String::from("null")),
+        _ => None,
+    }
+}
+
 /// Given a list of [PointerType]s, create a bit filter based on the `matched_to_bit` function
-pub(crate) fn bit_filter(types: &Vec<PointerType>) -> u8 {
+pub(crate) fn bit_filter(types: &[PointerType]) -> u8 {
     let mut filter = 0b0000_0000;
     if types.is_empty() {
         return ALL;
@@ -100,3 +123,352 @@ pub(crate) fn bit_filter(types: &Vec<PointerType>) -> u8 {
     }
     filter
 }
+
+/// The SAX parser reports positions as character based coordinates, which don't map cleanly onto byte offsets (and
+/// which drift slightly whenever the lexer has to push back a character).  A [ByteSpans] instance tracks the
+/// underlying UTF-8 buffer in lock-step with the parser instead, so that the exact byte range of each matched token
+/// can be recovered.  It *must* be fed every event in the order they're emitted
+pub(crate) struct ByteSpans<'a> {
+    /// The raw input
+    buffer: &'a [u8],
+    /// The current byte offset within the input
+    cursor: usize,
+    /// The coordinates of the most recently converted byte offset, from which the next conversion carries on
+    mark: Coords,
+}
+
+impl<'a> ByteSpans<'a> {
+    /// Create a new instance over a buffer, skipping any leading byte order mark
+    pub fn new(buffer: &'a [u8]) -> Self {
+        let cursor = if buffer.starts_with(&[0xef, 0xbb, 0xbf]) {
+            3
+        } else {
+            0
+        };
+        ByteSpans {
+            buffer,
+            cursor,
+            mark: Coords {
+                absolute: 0,
+                line: 1,
+                column: 1,
+            },
+        }
+    }
+
+    /// Advance over the token corresponding to the given [Match], returning its half-open byte range. Returns [None]
+    /// for the start and end of input, which don't correspond to any input
+    pub fn advance(&mut self, m: &Match) -> Option<(usize, usize)> {
+        if matches!(m, Match::StartOfInput | Match::EndOfInput) {
+            return None;
+        }
+        while self.cursor < self.buffer.len()
+            && matches!(
+                self.buffer[self.cursor],
+                b' ' | b'\t' | b'\r' | b'\n' | b',' | b':'
+            )
+        {
+            self.cursor += 1;
+        }
+        let start = self.cursor;
+        match self.buffer.get(start) {
+            Some(b'{' | b'}' | b'[' | b']') => self.cursor += 1,
+            Some(b'"') => {
+                self.cursor += 1;
+                while self.cursor < self.buffer.len() {
+                    match self.buffer[self.cursor] {
+                        b'\\' => self.cursor += 2,
+                        b'"' => {
+                            self.cursor += 1;
+                            break;
+                        }
+                        _ => self.cursor += 1,
+                    }
+                }
+            }
+            _ => {
+                while self.cursor < self.buffer.len()
+                    && !matches!(
+                        self.buffer[self.cursor],
+                        b' ' | b'\t' | b'\r' | b'\n' | b',' | b':' | b'}' | b']'
+                    )
+                {
+                    self.cursor += 1;
+                }
+            }
+        }
+        self.cursor = self.cursor.min(self.buffer.len());
+        Some((start, self.cursor))
+    }
+
+    /// Convert a half-open byte range into a [Span], in exactly the same terms as [LineIndex::span]. Ranges are
+    /// expected to arrive in input order, so that coordinates can be worked out incrementally rather than by
+    /// rescanning each line from its start
+    pub fn span(&mut self, range: (usize, usize)) -> Span {
+        let last = range.1.saturating_sub(1).max(range.0);
+        Span {
+            start: self.coords(range.0),
+            end: self.coords(last),
+        }
+    }
+
+    /// Work out the coordinates of a byte offset, moving forward from the last offset converted
+    fn coords(&mut self, offset: usize) -> Coords {
+        let offset = offset.min(self.buffer.len());
+        if offset < self.mark.absolute {
+            self.mark = Coords {
+                absolute: 0,
+                line: 1,
+                column: 1,
+            };
+        }
+        for b in &self.buffer[self.mark.absolute..offset] {
+            if *b == b'\n' {
+                self.mark.line += 1;
+                self.mark.column = 1;
+            } else if !is_continuation(Some(b)) {
+                self.mark.column += 1;
+            }
+        }
+        self.mark.absolute = offset;
+        self.mark
+    }
+}
+
+/// Maps between (one-based) line and column coordinates and byte offsets within a UTF-8 buffer. Columns are counted
+/// in characters, the same way as in the spans handed out by [parse_events]
+pub(crate) struct LineIndex<'a> {
+    /// The raw input
+    buffer: &'a [u8],
+    /// The byte offset at which each line starts
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    /// Create a new index over a buffer
+    pub fn new(buffer: &'a [u8]) -> Self {
+        let mut starts = vec![0];
+        starts.extend(
+            buffer
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| **b == b'\n')
+                .map(|(offset, _)| offset + 1),
+        );
+        LineIndex { buffer, starts }
+    }
+
+    /// Convert a line and column into a byte offset, provided that they actually lie within the input
+    pub fn offset(&self, line: usize, column: usize) -> Option<usize> {
+        let start = *self.starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .starts
+            .get(line)
+            .map_or(self.buffer.len(), |next| next - 1);
+        let mut remaining = column.checked_sub(1)?;
+        let mut offset = start;
+        while offset < end {
+            if remaining == 0 {
+                return Some(offset);
+            }
+            offset += 1;
+            if !is_continuation(self.buffer.get(offset)) {
+                remaining -= 1;
+            }
+        }
+        None
+    }
+
+    /// Convert a half-open byte range into a [Span], in the same terms as the SAX parser uses, where the end
+    /// coordinates are those of the last character within the range
+    pub fn span(&self, range: (usize, usize)) -> Span {
+        let last = range.1.saturating_sub(1).max(range.0);
+        let coords = |offset| {
+            let (line, column) = self.coords(offset);
+            Coords {
+                absolute: offset,
+                line,
+                column,
+            }
+        };
+        Span {
+            start: coords(range.0),
+            end: coords(last),
+        }
+    }
+
+    /// Convert a byte offset into a line and column
+    pub fn coords(&self, offset: usize) -> (usize, usize) {
+        let line = self.starts.partition_point(|start| *start <= offset);
+        let start = self.starts[line - 1];
+        let column = self.buffer[start..offset.min(self.buffer.len())]
+            .iter()
+            .filter(|b| !is_continuation(Some(b)))
+            .count();
+        (line, column + 1)
+    }
+}
+
+/// Checks whether a byte is a UTF-8 continuation byte
+#[inline]
+fn is_continuation(b: Option<&u8>) -> bool {
+    matches!(b, Some(b) if b & 0xc0 == 0x80)
+}
+
+/// Converts a set of bits (as produced by [matched_to_bit]) back into the corresponding `char` codes, as would be
+/// produced by [matched_to_char], in a fixed order
+pub(crate) fn bits_to_chars(bits: u8) -> String {
+    [
+        (OBJECT, 'o'),
+        (ARRAY, 'a'),
+        (KEY, 'k'),
+        (STRING, 's'),
+        (FLOAT, 'f'),
+        (INTEGER, 'i'),
+        (BOOLEAN, 'b'),
+        (NULL, 'n'),
+    ]
+    .iter()
+    .filter(|(bit, _)| bits & bit > 0)
+    .map(|(_, c)| *c)
+    .collect()
+}
+
+/// Parse a document in any of the supported formats, passing each SAX event on to a callback along with the byte
+/// range of the input that it was matched against (as per [ByteSpans]).  The span of each event is rebuilt from that
+/// byte range, so that every command reports the same (character based) coordinates no matter how it consumes them.
+/// Documents in formats other than JSON are replayed as the events that the equivalent JSON would have produced, but
+/// with spans that point back into the original input.  Any parse failure is reported before returning
+pub(crate) fn parse_events<Callback>(
+    format: DocumentFormat,
+    buffer: &[u8],
+    cb: &mut Callback,
+) -> ChiselResult<()>
+where
+    Callback: FnMut(&Event, Option<(usize, usize)>) -> ParserResult<()>,
+{
+    if format == DocumentFormat::Json {
+        let mut spans = ByteSpans::new(buffer);
+        let parser = SaxParser::default();
+        return parser
+            .parse_bytes(buffer, &mut |evt| match spans.advance(&evt.matched) {
+                Some(range) => {
+                    let evt = Event {
+                        matched: reborrow(&evt.matched),
+                        span: spans.span(range),
+                        pointer: evt.pointer,
+                    };
+                    cb(&evt, Some(range))
+                }
+                None => cb(evt, None),
+            })
+            .map_err(|err| {
+                report_parse_error(&err);
+                ChiselError::InvalidInput
+            });
+    }
+
+    let node = formats::parse_document(format, buffer).map_err(|err| {
+        report_format_error(buffer, &err);
+        ChiselError::InvalidInput
+    })?;
+    let lines = LineIndex::new(buffer);
+    let mut pointer = JsonPointer::default();
+    let start = Span {
+        start: Coords::default(),
+        end: Coords::default(),
+    };
+    cb(
+        &Event {
+            matched: Match::StartOfInput,
+            span: start,
+            pointer: None,
+        },
+        None,
+    )
+    .and_then(|_| replay_node(&node, &lines, &mut pointer, cb))
+    .or(Err(ChiselError::InvalidInput))
+}
+
+/// Borrow a [Match] afresh, so that an event can be re-issued with a different span
+fn reborrow<'a>(matched: &'a Match) -> Match<'a> {
+    match matched {
+        Match::StartOfInput => Match::StartOfInput,
+        Match::EndOfInput => Match::EndOfInput,
+        Match::StartObject => Match::StartObject,
+        Match::ObjectKey(key) => Match::ObjectKey(Cow::Borrowed(key)),
+        Match::EndObject => Match::EndObject,
+        Match::StartArray => Match::StartArray,
+        Match::EndArray => Match::EndArray,
+        Match::String(value) => Match::String(Cow::Borrowed(value)),
+        Match::Integer(value) => Match::Integer(*value),
+        Match::Float(value) => Match::Float(*value),
+        Match::Boolean(value) => Match::Boolean(*value),
+        Match::Null => Match::Null,
+    }
+}
+
+/// Pass a single replayed event on to a callback
+fn replay_event<Callback>(
+    cb: &mut Callback,
+    lines: &LineIndex,
+    matched: Match,
+    range: (usize, usize),
+    pointer: &JsonPointer,
+) -> ParserResult<()>
+where
+    Callback: FnMut(&Event, Option<(usize, usize)>) -> ParserResult<()>,
+{
+    let evt = Event {
+        matched,
+        span: lines.span(range),
+        pointer: Some(pointer),
+    };
+    cb(&evt, Some(range))
+}
+
+/// Replay a single node (and everything beneath it) as a series of SAX events, maintaining the pointer in exactly
+/// the same way as the SAX parser does
+fn replay_node<Callback>(
+    node: &Node,
+    lines: &LineIndex,
+    pointer: &mut JsonPointer,
+    cb: &mut Callback,
+) -> ParserResult<()>
+where
+    Callback: FnMut(&Event, Option<(usize, usize)>) -> ParserResult<()>,
+{
+    // most formats have no closing bracket to point at, so the last byte of a container is used in its place
+    let close = (
+        node.range.1.saturating_sub(1).max(node.range.0),
+        node.range.1,
+    );
+    let matched = match &node.value {
+        NodeValue::Object(members) => {
+            replay_event(cb, lines, Match::StartObject, node.range, pointer)?;
+            for member in members {
+                pointer.push_name(member.key.replace('"', ""));
+                let key = Match::ObjectKey(Cow::Borrowed(&member.key));
+                replay_event(cb, lines, key, member.range, pointer)?;
+                replay_node(&member.value, lines, pointer, cb)?;
+                pointer.pop();
+            }
+            return replay_event(cb, lines, Match::EndObject, close, pointer);
+        }
+        NodeValue::Array(values) => {
+            replay_event(cb, lines, Match::StartArray, node.range, pointer)?;
+            for (index, value) in values.iter().enumerate() {
+                pointer.push_index(index);
+                replay_node(value, lines, pointer, cb)?;
+                pointer.pop();
+            }
+            return replay_event(cb, lines, Match::EndArray, close, pointer);
+        }
+        NodeValue::String(raw) => Match::String(Cow::Borrowed(raw)),
+        NodeValue::Integer(value) => Match::Integer(*value),
+        NodeValue::Float(value) => Match::Float(*value),
+        NodeValue::Boolean(value) => Match::Boolean(*value),
+        NodeValue::Null => Match::Null,
+    };
+    replay_event(cb, lines, matched, node.range, pointer)
+}
@@ -0,0 +1,96 @@
+use std::path::PathBuf;
+
+use super::documents::{in_place_target, parse_document, write_document};
+use super::layout::LayoutArgs;
+use super::{Command, CommandContext};
+use crate::dom::{parse_literal, set_pointer};
+use crate::errors::ChiselResult;
+use crate::formats::DocumentFormat;
+use crate::render::pretty_printer::FormatOptions;
+use crate::sources::{source_from_file, source_from_stdin};
+use clap::Args;
+
+/// A [Command] responsible for setting the value at a given location within a document
+#[derive(Debug, Args)]
+pub struct SetCommand {
+    /// JSON pointer.
+    ///
+    /// The location of the value to be set, e.g. /config/timeout
+    #[arg(value_name = "POINTER")]
+    pub pointer: String,
+
+    /// Value.
+    ///
+    /// The new value, which must be a valid JSON literal. Strings must therefore be quoted, e.g. '"text"'
+    #[arg(value_name = "VALUE", allow_hyphen_values = true)]
+    pub value: String,
+
+    /// Source JSON file.
+    ///
+    /// If not specified, input is assumed to come from stdin.
+    #[arg(last = true, value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Input format
+    ///
+    /// The format of the input document. If not specified, then it's worked out from the extension of the source
+    /// file, falling back to JSON
+    #[arg(value_enum, long, value_name = "FORMAT")]
+    pub from: Option<DocumentFormat>,
+
+    /// Create intermediate objects
+    ///
+    /// If set, any objects missing along the path to the target location will be created
+    #[arg(short, long)]
+    pub create: bool,
+
+    /// Edit in place
+    ///
+    /// Rather than printing the result, atomically rewrite the source file
+    #[arg(long)]
+    pub in_place: bool,
+
+    /// Indent space count
+    ///
+    /// Object keys and array values are idented by this amount plus the parent identation amount
+    #[arg(short, long, value_name = "n", default_value = "2")]
+    pub indent: u16,
+
+    /// KV padding count
+    ///
+    /// The number of spaces added to each side of the ":" character in a <key> : <value> pair
+    #[arg(short, long, value_name = "n", default_value = "1")]
+    pub kvpadding: u16,
+
+    #[command(flatten)]
+    pub layout: LayoutArgs,
+}
+
+impl Command for SetCommand {
+    /// Execute the set action
+    fn execute(&mut self, context: &mut CommandContext) -> ChiselResult<()> {
+        // check the arguments before doing anything expensive
+        let format = DocumentFormat::detect(self.from, self.file.as_deref());
+        let target = in_place_target(self.in_place, &self.file, format)?;
+        let value = parse_literal(&self.value)?;
+
+        let mut buffer: Vec<u8> = vec![];
+        if let Some(path) = &self.file {
+            source_from_file(path, &mut buffer)?;
+        } else {
+            source_from_stdin(&mut buffer)?;
+        }
+
+        let mut json = parse_document(format, &buffer)?;
+        set_pointer(&mut json, &self.pointer, value, self.create)?;
+        let options = self.layout.apply(
+            FormatOptions {
+                indent: self.indent,
+                kvpadding: self.kvpadding,
+                ..FormatOptions::default()
+            },
+            target.is_some(),
+        );
+        write_document(context, json, options, target)
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use super::documents::parse_document;
+use super::{Command, CommandContext};
+use crate::cl_immediate;
+use crate::errors::{ChiselError, ChiselResult};
+use crate::escapes::unquote_json_string;
+use crate::formats::DocumentFormat;
+use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode, Draw};
+use crate::render::pretty_printer::{FormatOptions, PrettyPrinter};
+use crate::sources::{source_from_file, source_from_stdin};
+use crate::transform::Program;
+use chisel_json::JsonValue;
+use clap::Args;
+
+/// A [Command] responsible for running jq-style transformation programs
+#[derive(Debug, Args)]
+pub struct TransformCommand {
+    /// Transformation program.
+    ///
+    /// A jq-style program, e.g. '.statuses | map({id, text: .text | ascii_downcase})'
+    #[arg(value_name = "PROGRAM")]
+    pub program: String,
+
+    /// Source JSON file.
+    ///
+    /// If not specified, input is assumed to come from stdin.
+    #[arg(last = true, value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Input format
+    ///
+    /// The format of the input document. If not specified, then it's worked out from the extension of the source
+    /// file, falling back to JSON
+    #[arg(value_enum, long, value_name = "FORMAT")]
+    pub from: Option<DocumentFormat>,
+
+    /// Raw output
+    ///
+    /// Output string results without quotes or escapes, rather than as JSON strings
+    #[arg(short, long)]
+    pub raw_output: bool,
+
+    /// Null input
+    ///
+    /// Don't read any input, and run the program once with null as its input
+    #[arg(short, long)]
+    pub null_input: bool,
+
+    /// Indent space count
+    ///
+    /// Object keys and array values are idented by this amount plus the parent identation amount
+    #[arg(short, long, value_name = "n", default_value = "2")]
+    pub indent: u16,
+
+    /// KV padding count
+    ///
+    /// The number of spaces added to each side of the ":" character in a <key> : <value> pair
+    #[arg(short, long, value_name = "n", default_value = "1")]
+    pub kvpadding: u16,
+}
+
+impl Command for TransformCommand {
+    /// Execute the transform action
+    fn execute(&mut self, context: &mut CommandContext) -> ChiselResult<()> {
+        // parse the program up front, so that errors are reported before any input is read
+        let program = Program::parse(&self.program)?;
+
+        let results = if self.null_input {
+            program.run(JsonValue::Null)?
+        } else {
+            let mut buffer: Vec<u8> = vec![];
+            if let Some(path) = &self.file {
+                source_from_file(path, &mut buffer)?;
+            } else {
+                source_from_stdin(&mut buffer)?;
+            }
+
+            let format = DocumentFormat::detect(self.from, self.file.as_deref());
+            program.run(parse_document(format, &buffer)?)?
+        };
+
+        let options = FormatOptions {
+            indent: self.indent,
+            kvpadding: self.kvpadding,
+            ..FormatOptions::default()
+        };
+        let printer = PrettyPrinter::new(context.clone_render_pipeline(), options);
+        for result in results {
+            match result {
+                JsonValue::String(raw) if self.raw_output => context
+                    .render_pipeline
+                    .send(cl_immediate!(Draw::Text(unquote_json_string(&raw))))
+                    .or(Err(ChiselError::DisplayListFailed))?,
+                result => printer.render_json(result)?,
+            }
+            context
+                .render_pipeline
+                .send(cl_immediate!(Draw::NewLine))
+                .or(Err(ChiselError::DisplayListFailed))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use super::flatten::GRON_ROOT;
+use super::{Command, CommandContext};
+use crate::dom::{insert_at_path, keys_equal, parse_literal, pointer_tokens, PathToken};
+use crate::errors::{ChiselError, ChiselResult};
+use crate::escapes::quote_json_string;
+use crate::render::pretty_printer::{FormatOptions, PrettyPrinter};
+use crate::sources::{source_from_file, source_from_stdin};
+use chisel_json::JsonValue;
+use clap::Args;
+use std::path::PathBuf;
+
+/// A [Command] responsible for rebuilding a document from a series of assignments, as produced by flatten
+#[derive(Debug, Args)]
+pub struct UnflattenCommand {
+    /// Source file.
+    ///
+    /// A file containing gron-style or JSON pointer assignments, one per line. If not specified, input is assumed to
+    /// come from stdin.
+    #[arg(last = true, value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Indent space count
+    ///
+    /// Object keys and array values are idented by this amount plus the parent identation amount
+    #[arg(short, long, value_name = "n", default_value = "2")]
+    pub indent: u16,
+
+    /// KV padding count
+    ///
+    /// The number of spaces added to each side of the ":" character in a <key> : <value> pair
+    #[arg(short, long, value_name = "n", default_value = "1")]
+    pub kvpadding: u16,
+}
+
+/// Parse the path for a gron-style assignment, returning the path along with the remainder of the line
+fn parse_gron_path(line: &str) -> Option<(Vec<PathToken>, &str)> {
+    let mut rest = line.strip_prefix(GRON_ROOT)?;
+    let mut path = vec![];
+    loop {
+        if let Some(after) = rest.strip_prefix('.') {
+            let length = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+                .unwrap_or(after.len());
+            path.push(PathToken::Name(format!("\"{}\"", &after[..length])));
+            rest = &after[length..];
+        } else if let Some(after) = rest.strip_prefix("[\"") {
+            // scan through to the closing quote, skipping over any escapes
+            let mut escaped = false;
+            let length = after.find(|c: char| {
+                let close = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                close
+            })?;
+            path.push(PathToken::Name(format!("\"{}\"", &after[..length])));
+            rest = after[length + 1..].strip_prefix(']')?;
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let (index, after) = after.split_once(']')?;
+            path.push(PathToken::Index(index.trim().parse().ok()?));
+            rest = after;
+        } else {
+            return Some((path, rest));
+        }
+    }
+}
+
+/// Resolve the reference tokens of a pointer into a path. Tokens which look like indices are only treated as object
+/// member names if the document already has an object at that location
+fn resolve_pointer(root: &JsonValue, tokens: Vec<String>) -> Vec<PathToken> {
+    let mut current = Some(root);
+    let mut path = vec![];
+    for token in tokens {
+        let name = quote_json_string(&token);
+        let index = Some(&token)
+            .filter(|t| t.bytes().all(|b| b.is_ascii_digit()))
+            .filter(|t| t.len() == 1 || !t.starts_with('0'))
+            .and_then(|t| t.parse::<usize>().ok());
+        match (current, index) {
+            (Some(JsonValue::Object(pairs)), _) => {
+                current = pairs
+                    .iter()
+                    .find(|(k, _)| keys_equal(k, &name))
+                    .map(|(_, v)| v);
+                path.push(PathToken::Name(name));
+            }
+            (_, Some(index)) => {
+                current = match current {
+                    Some(JsonValue::Array(values)) => values.get(index),
+                    _ => None,
+                };
+                path.push(PathToken::Index(index));
+            }
+            _ => {
+                current = None;
+                path.push(PathToken::Name(name));
+            }
+        }
+    }
+    path
+}
+
+/// Parse a single assignment into a path and a value, resolving pointers against the document built up so far
+fn parse_assignment(root: &JsonValue, line: &str) -> Option<(Vec<PathToken>, JsonValue<'static>)> {
+    if line.starts_with(GRON_ROOT) {
+        let (path, rest) = parse_gron_path(line)?;
+        let value = rest.trim_start().strip_prefix('=')?.trim();
+        let value = value.strip_suffix(';').unwrap_or(value).trim_end();
+        return Some((path, parse_literal(value).ok()?));
+    }
+
+    // the root pointer is empty, so its assignment may have lost its leading space along the way
+    if let Some(value) = line.strip_prefix("= ") {
+        return Some((vec![], parse_literal(value.trim()).ok()?));
+    }
+
+    // pointers may legitimately contain " = ", so try each possible split in turn until a valid value is found
+    let mut from = 0;
+    while let Some(position) = line[from..].find(" = ") {
+        let (pointer, value) = line.split_at(from + position);
+        if let (Ok(tokens), Ok(value)) = (pointer_tokens(pointer), parse_literal(value[3..].trim()))
+        {
+            return Some((resolve_pointer(root, tokens), value));
+        }
+        from += position + 1;
+    }
+    None
+}
+
+impl Command for UnflattenCommand {
+    /// Execute the unflatten action
+    fn execute(&mut self, context: &mut CommandContext) -> ChiselResult<()> {
+        let mut buffer: Vec<u8> = vec![];
+        if let Some(path) = &self.file {
+            source_from_file(path, &mut buffer)?;
+        } else {
+            source_from_stdin(&mut buffer)?;
+        }
+
+        let mut root = JsonValue::Null;
+        for (number, line) in String::from_utf8_lossy(&buffer).lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_assignment(&root, line) {
+                Some((path, value)) => insert_at_path(&mut root, &path, value),
+                None => {
+                    return Err(ChiselError::InvalidAssignment(format!(
+                        "line {}: {}",
+                        number + 1,
+                        line
+                    )))
+                }
+            }
+        }
+
+        let options = FormatOptions {
+            indent: self.indent,
+            kvpadding: self.kvpadding,
+            ..FormatOptions::default()
+        };
+        PrettyPrinter::new(context.clone_render_pipeline(), options).render_json(root)
+    }
+}
@@ -0,0 +1,523 @@
+//! Utilities for inspecting and manipulating [JsonValue] DOM structures
+
+use crate::errors::{ChiselError, ChiselResult};
+use crate::escapes::{quote_json_string, unquote_json_string};
+use chisel_json::dom::Parser as DomParser;
+use chisel_json::JsonValue;
+use std::borrow::Cow;
+
+/// Convert a [JsonValue] into one that isn't tied to the lifetime of the parser that produced it
+pub fn into_static(value: JsonValue) -> JsonValue<'static> {
+    match value {
+        JsonValue::Object(pairs) => JsonValue::Object(
+            pairs
+                .into_iter()
+                .map(|(k, v)| (k, into_static(v)))
+                .collect(),
+        ),
+        JsonValue::Array(values) => JsonValue::Array(values.into_iter().map(into_static).collect()),
+        JsonValue::String(s) => JsonValue::String(Cow::Owned(s.into_owned())),
+        JsonValue::Float(f) => JsonValue::Float(f),
+        JsonValue::Integer(i) => JsonValue::Integer(i),
+        JsonValue::Boolean(b) => JsonValue::Boolean(b),
+        JsonValue::Null => JsonValue::Null,
+    }
+}
+
+/// Parse a single JSON literal (which may be a scalar as well as an object or array).  The DOM parser will only
+/// accept objects or arrays at the root, so the literal is wrapped within an array prior to parsing
+pub fn parse_literal(literal: &str) -> ChiselResult<JsonValue<'static>> {
+    let parser = DomParser::default();
+    match parser.parse_str(&format!("[{}]", literal)) {
+        Ok(JsonValue::Array(mut values)) if values.len() == 1 => Ok(into_static(values.remove(0))),
+        _ => Err(ChiselError::InvalidValue(literal.to_string())),
+    }
+}
+
+/// Split a JSON pointer into its (unescaped) reference tokens, as per RFC 6901. The empty pointer refers to the whole
+/// document, and so results in no tokens at all
+pub fn pointer_tokens(pointer: &str) -> ChiselResult<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+    match pointer.strip_prefix('/') {
+        Some(rest) => Ok(rest
+            .split('/')
+            .map(|t| t.replace("~1", "/").replace("~0", "~"))
+            .collect()),
+        None => Err(ChiselError::InvalidPointer(pointer.to_string())),
+    }
+}
+
+/// Interpret a pointer reference token as an array index. Leading zeros aren't permitted
+pub fn token_to_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    if !token.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// Find the position of a named member within a list of object pairs. Keys are held in their raw form within
+/// the DOM, so need to be unquoted prior to comparison
+fn member_position(pairs: &[(String, JsonValue)], name: &str) -> Option<usize> {
+    pairs
+        .iter()
+        .position(|(k, _)| unquote_json_string(k) == name)
+}
+
+/// Look up the value at the location given by a list of pointer reference tokens, without taking it out of the
+/// document. Anything that doesn't resolve results in [None]
+pub fn find_pointer<'v, 'a>(
+    root: &'v JsonValue<'a>,
+    tokens: &[String],
+) -> Option<&'v JsonValue<'a>> {
+    let mut current = root;
+    for token in tokens {
+        current = match current {
+            JsonValue::Object(pairs) => &pairs[member_position(pairs, token)?].1,
+            JsonValue::Array(values) => values.get(token_to_index(token)?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+/// Set the value at the location given by a JSON pointer, replacing anything that's already there.  Objects will
+/// gain new members as required, and arrays may be appended to by using either the `-` token or an index equal to
+/// the length of the array.  If `create` is set, any missing intermediate objects will be created along the way
+pub fn set_pointer<'a>(
+    root: &mut JsonValue<'a>,
+    pointer: &str,
+    value: JsonValue<'a>,
+    create: bool,
+) -> ChiselResult<()> {
+    let tokens = pointer_tokens(pointer)?;
+    let (last, parents) = match tokens.split_last() {
+        Some(split) => split,
+        None => {
+            *root = value;
+            return Ok(());
+        }
+    };
+
+    // walk down to the parent of the target location
+    let mut current = root;
+    for token in parents {
+        current = match current {
+            JsonValue::Object(pairs) => match member_position(pairs, token) {
+                Some(index) => &mut pairs[index].1,
+                None if create => {
+                    pairs.push((quote_json_string(token), JsonValue::Object(vec![])));
+                    &mut pairs.last_mut().unwrap().1
+                }
+                None => return Err(ChiselError::PointerNotFound(pointer.to_string())),
+            },
+            JsonValue::Array(values) => match token_to_index(token) {
+                Some(index) if index < values.len() => &mut values[index],
+                _ if create && (token == "-" || token_to_index(token) == Some(values.len())) => {
+                    values.push(JsonValue::Object(vec![]));
+                    values.last_mut().unwrap()
+                }
+                _ => return Err(ChiselError::PointerNotFound(pointer.to_string())),
+            },
+            _ => return Err(ChiselError::PointerNotFound(pointer.to_string())),
+        };
+    }
+
+    // and then update the parent
+    match current {
+        JsonValue::Object(pairs) => match member_position(pairs, last) {
+            Some(index) => pairs[index].1 = value,
+            None => pairs.push((quote_json_string(last), value)),
+        },
+        JsonValue::Array(values) => match token_to_index(last) {
+            Some(index) if index < values.len() => values[index] = value,
+            Some(index) if index == values.len() => values.push(value),
+            None if last == "-" => values.push(value),
+            _ => return Err(ChiselError::PointerNotFound(pointer.to_string())),
+        },
+        _ => return Err(ChiselError::PointerNotFound(pointer.to_string())),
+    }
+    Ok(())
+}
+
+/// Remove the value at the location given by a JSON pointer, handing it back to the caller.  The whole document
+/// can't be removed, so the empty pointer is rejected
+pub fn delete_pointer<'a>(root: &mut JsonValue<'a>, pointer: &str) -> ChiselResult<JsonValue<'a>> {
+    let tokens = pointer_tokens(pointer)?;
+    let (last, parents) = match tokens.split_last() {
+        Some(split) => split,
+        None => return Err(ChiselError::InvalidPointer(pointer.to_string())),
+    };
+
+    let mut current = root;
+    for token in parents {
+        current = match current {
+            JsonValue::Object(pairs) => match member_position(pairs, token) {
+                Some(index) => &mut pairs[index].1,
+                None => return Err(ChiselError::PointerNotFound(pointer.to_string())),
+            },
+            JsonValue::Array(values) => match token_to_index(token) {
+                Some(index) if index < values.len() => &mut values[index],
+                _ => return Err(ChiselError::PointerNotFound(pointer.to_string())),
+            },
+            _ => return Err(ChiselError::PointerNotFound(pointer.to_string())),
+        };
+    }
+
+    match current {
+        JsonValue::Object(pairs) => match member_position(pairs, last) {
+            Some(index) => Ok(pairs.remove(index).1),
+            None => Err(ChiselError::PointerNotFound(pointer.to_string())),
+        },
+        JsonValue::Array(values) => match token_to_index(last) {
+            Some(index) if index < values.len() => Ok(values.remove(index)),
+            _ => Err(ChiselError::PointerNotFound(pointer.to_string())),
+        },
+        _ => Err(ChiselError::PointerNotFound(pointer.to_string())),
+    }
+}
+
+/// Take a deep copy of a [JsonValue]
+pub fn clone_value(value: &JsonValue) -> JsonValue<'static> {
+    match value {
+        JsonValue::Object(pairs) => JsonValue::Object(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.clone(), clone_value(v)))
+                .collect(),
+        ),
+        JsonValue::Array(values) => JsonValue::Array(values.iter().map(clone_value).collect()),
+        JsonValue::String(s) => JsonValue::String(Cow::Owned(s.to_string())),
+        JsonValue::Float(f) => JsonValue::Float(*f),
+        JsonValue::Integer(i) => JsonValue::Integer(*i),
+        JsonValue::Boolean(b) => JsonValue::Boolean(*b),
+        JsonValue::Null => JsonValue::Null,
+    }
+}
+
+/// Check two [JsonValue]s for (deep) equality. Numbers are compared numerically regardless of whether they're
+/// integers or floats, strings are compared after decoding any escapes and the ordering of object members is ignored
+pub fn json_equal(lhs: &JsonValue, rhs: &JsonValue) -> bool {
+    match (lhs, rhs) {
+        (JsonValue::Object(l), JsonValue::Object(r)) => {
+            l.len() == r.len()
+                && l.iter().all(|(k, v)| {
+                    let key = unquote_json_string(k);
+                    r.iter()
+                        .any(|(rk, rv)| unquote_json_string(rk) == key && json_equal(v, rv))
+                })
+        }
+        (JsonValue::Array(l), JsonValue::Array(r)) => {
+            l.len() == r.len() && l.iter().zip(r.iter()).all(|(a, b)| json_equal(a, b))
+        }
+        (JsonValue::String(l), JsonValue::String(r)) => {
+            l == r || unquote_json_string(l) == unquote_json_string(r)
+        }
+        (JsonValue::Integer(l), JsonValue::Integer(r)) => l == r,
+        (JsonValue::Integer(l), JsonValue::Float(r)) => (*l as f64) == *r,
+        (JsonValue::Float(l), JsonValue::Integer(r)) => *l == (*r as f64),
+        (JsonValue::Float(l), JsonValue::Float(r)) => l == r,
+        (JsonValue::Boolean(l), JsonValue::Boolean(r)) => l == r,
+        (JsonValue::Null, JsonValue::Null) => true,
+        _ => false,
+    }
+}
+
+/// A single step along a path into a document. Names are held in their raw (quoted and escaped) form, in the same
+/// way as object keys within the DOM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathToken {
+    /// An object member
+    Name(String),
+    /// An array element
+    Index(usize),
+}
+
+/// Compare two raw object keys, only bothering to decode them if either contains an escape
+pub fn keys_equal(lhs: &str, rhs: &str) -> bool {
+    lhs == rhs
+        || ((lhs.contains('\\') || rhs.contains('\\'))
+            && unquote_json_string(lhs) == unquote_json_string(rhs))
+}
+
+/// Insert a value at the location given by a path, building up the document as required.  Anything along the path
+/// that isn't already a container of the right kind is replaced by one, and any gaps within arrays are filled with
+/// nulls.  Empty containers don't replace existing containers of the same kind, which allows a document to be built
+/// up from a series of assignments, in any order
+pub fn insert_at_path(
+    root: &mut JsonValue<'static>,
+    path: &[PathToken],
+    value: JsonValue<'static>,
+) {
+    let mut current = root;
+    for token in path {
+        current = match token {
+            PathToken::Name(name) => {
+                if !matches!(current, JsonValue::Object(_)) {
+                    *current = JsonValue::Object(vec![]);
+                }
+                let JsonValue::Object(pairs) = current else {
+                    unreachable!()
+                };
+                match pairs.iter().position(|(k, _)| keys_equal(k, name)) {
+                    Some(index) => &mut pairs[index].1,
+                    None => {
+                        pairs.push((name.clone(), JsonValue::Null));
+                        &mut pairs.last_mut().unwrap().1
+                    }
+                }
+            }
+            PathToken::Index(index) => {
+                if !matches!(current, JsonValue::Array(_)) {
+                    *current = JsonValue::Array(vec![]);
+                }
+                let JsonValue::Array(values) = current else {
+                    unreachable!()
+                };
+                while values.len() <= *index {
+                    values.push(JsonValue::Null);
+                }
+                &mut values[*index]
+            }
+        };
+    }
+
+    match (&*current, &value) {
+        (JsonValue::Object(_), JsonValue::Object(pairs)) if pairs.is_empty() => (),
+        (JsonValue::Array(_), JsonValue::Array(values)) if values.is_empty() => (),
+        _ => *current = value,
+    }
+}
+
+/// Take the value at the location given by a JSON pointer out of a document. The empty pointer refers to the whole
+/// document, which is handed back as is
+pub fn select_pointer<'a>(mut root: JsonValue<'a>, pointer: &str) -> ChiselResult<JsonValue<'a>> {
+    match pointer.is_empty() {
+        true => Ok(root),
+        false => delete_pointer(&mut root, pointer),
+    }
+}
+
+/// Write a [JsonValue] out as compact JSON text, with no whitespace between tokens
+pub fn compact_json(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Object(pairs) => {
+            let members: Vec<String> = pairs
+                .iter()
+                .map(|(key, value)| format!("{}:{}", key, compact_json(value)))
+                .collect();
+            format!("{{{}}}", members.join(","))
+        }
+        JsonValue::Array(values) => {
+            let values: Vec<String> = values.iter().map(compact_json).collect();
+            format!("[{}]", values.join(","))
+        }
+        JsonValue::String(raw) => raw.to_string(),
+        JsonValue::Float(f) => f.to_string(),
+        JsonValue::Integer(i) => i.to_string(),
+        JsonValue::Boolean(b) => b.to_string(),
+        JsonValue::Null => String::from("null"),
+    }
+}
+
+/// Write a [JsonValue] out in the canonical form defined by RFC 8785 (the JSON Canonicalization Scheme). Object
+/// members are sorted by the UTF-16 code units of their keys, strings use minimal escaping and every number is
+/// formatted as an IEEE 754 double would be by ECMAScript, so integers beyond 2^53 lose precision
+pub fn canonical_json(value: &JsonValue) -> ChiselResult<String> {
+    match value {
+        JsonValue::Object(pairs) => {
+            let mut members: Vec<(Vec<u16>, String, &JsonValue)> = pairs
+                .iter()
+                .map(|(key, value)| {
+                    let key = unquote_json_string(key);
+                    (key.encode_utf16().collect(), key, value)
+                })
+                .collect();
+            members.sort_by(|a, b| a.0.cmp(&b.0));
+            let members = members
+                .into_iter()
+                .map(|(_, key, value)| {
+                    Ok(format!(
+                        "{}:{}",
+                        quote_json_string(&key),
+                        canonical_json(value)?
+                    ))
+                })
+                .collect::<ChiselResult<Vec<String>>>()?;
+            Ok(format!("{{{}}}", members.join(",")))
+        }
+        JsonValue::Array(values) => {
+            let values = values
+                .iter()
+                .map(canonical_json)
+                .collect::<ChiselResult<Vec<String>>>()?;
+            Ok(format!("[{}]", values.join(",")))
+        }
+        JsonValue::String(raw) => Ok(quote_json_string(&unquote_json_string(raw))),
+        JsonValue::Float(f) => canonical_number(*f),
+        JsonValue::Integer(i) => canonical_number(*i as f64),
+        JsonValue::Boolean(b) => Ok(b.to_string()),
+        JsonValue::Null => Ok(String::from("null")),
+    }
+}
+
+/// Format a number in the same way as ECMAScript's `Number.prototype.toString`, which is what RFC 8785 requires
+pub fn canonical_number(value: f64) -> ChiselResult<String> {
+    if !value.is_finite() {
+        return Err(ChiselError::NotRepresentable(format!(
+            "non-finite number {}",
+            value
+        )));
+    }
+    if value == 0.0 {
+        return Ok(String::from("0"));
+    }
+
+    // the shortest digits that round trip, along with the position of the decimal point relative to them
+    let scientific = format!("{:e}", value.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let k = digits.len() as i32;
+    let n = exponent.parse::<i32>().unwrap_or_default() + 1;
+
+    let sign = if value < 0.0 { "-" } else { "" };
+    let formatted = if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat(-n as usize), digits)
+    } else {
+        let fraction = match k {
+            1 => String::new(),
+            _ => format!(".{}", &digits[1..]),
+        };
+        format!(
+            "{}{}e{}{}",
+            &digits[..1],
+            fraction,
+            if n > 0 { "+" } else { "-" },
+            (n - 1).abs()
+        )
+    };
+    Ok(format!("{}{}", sign, formatted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    /// A string value, given in its raw (quoted and escaped) form
+    fn string(raw: &str) -> JsonValue<'_> {
+        JsonValue::String(Cow::Borrowed(raw))
+    }
+
+    #[test]
+    fn canonical_numbers_match_the_rfc_8785_examples() {
+        let examples = [
+            (0.0, "0"),
+            (-0.0, "0"),
+            (5e-324, "5e-324"),
+            (-5e-324, "-5e-324"),
+            (1.7976931348623157e308, "1.7976931348623157e+308"),
+            (-1.7976931348623157e308, "-1.7976931348623157e+308"),
+            (9007199254740992.0, "9007199254740992"),
+            (-9007199254740992.0, "-9007199254740992"),
+            (295147905179352830000.0, "295147905179352830000"),
+            (9.999999999999997e22, "9.999999999999997e+22"),
+            (1e21, "1e+21"),
+            (999999999999999700000.0, "999999999999999700000"),
+            (1e23, "1e+23"),
+            (0.000001, "0.000001"),
+            (1e-7, "1e-7"),
+            (333333333.3333333, "333333333.3333333"),
+            (1e30, "1e+30"),
+            (4.50, "4.5"),
+            (2e-3, "0.002"),
+            (0.000000000000000000000000001, "1e-27"),
+        ];
+        for (value, expected) in examples {
+            assert_eq!(canonical_number(value).unwrap(), expected, "{:e}", value);
+        }
+    }
+
+    #[test]
+    fn canonical_numbers_reject_non_finite_values() {
+        assert!(canonical_number(f64::NAN).is_err());
+        assert!(canonical_number(f64::INFINITY).is_err());
+        assert!(canonical_number(f64::NEG_INFINITY).is_err());
+    }
+
+    #[test]
+    fn canonical_json_matches_the_rfc_8785_example() {
+        let value = JsonValue::Object(vec![
+            (
+                String::from("\"numbers\""),
+                JsonValue::Array(vec![
+                    // the extra digits round away, which is rather the point
+                    JsonValue::Float("333333333.33333329".parse().unwrap()),
+                    JsonValue::Float(1e30),
+                    JsonValue::Float(4.50),
+                    JsonValue::Float(2e-3),
+                    JsonValue::Float(0.000000000000000000000000001),
+                ]),
+            ),
+            (
+                String::from("\"string\""),
+                string(r#""\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/""#),
+            ),
+            (
+                String::from("\"literals\""),
+                JsonValue::Array(vec![
+                    JsonValue::Null,
+                    JsonValue::Boolean(true),
+                    JsonValue::Boolean(false),
+                ]),
+            ),
+        ]);
+        assert_eq!(
+            canonical_json(&value).unwrap(),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+    }
+
+    #[test]
+    fn canonical_json_sorts_keys_by_utf16_code_units() {
+        let keys = [
+            r#""\u20ac""#,
+            r#""\r""#,
+            r#""\ufb33""#,
+            r#""1""#,
+            r#""\ud83d\ude00""#,
+            r#""\u0080""#,
+            r#""\u00f6""#,
+        ];
+        let value = JsonValue::Object(
+            keys.iter()
+                .enumerate()
+                .map(|(i, key)| (key.to_string(), JsonValue::Integer(i as i64)))
+                .collect(),
+        );
+
+        // the emoji's leading surrogate sorts it ahead of U+FB33, even though its code point is greater
+        assert_eq!(
+            canonical_json(&value).unwrap(),
+            "{\"\\r\":1,\"1\":3,\"\u{80}\":5,\"\u{f6}\":6,\"\u{20ac}\":0,\"\u{1f600}\":4,\"\u{fb33}\":2}"
+        );
+    }
+
+    #[test]
+    fn canonical_json_formats_integers_as_doubles() {
+        let value = JsonValue::Array(vec![
+            JsonValue::Integer(0),
+            JsonValue::Integer(-12),
+            JsonValue::Integer(9007199254740993),
+        ]);
+        assert_eq!(canonical_json(&value).unwrap(), "[0,-12,9007199254740992]");
+    }
+}
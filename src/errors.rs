@@ -18,6 +18,34 @@ pub enum ChiselError {
     NoTty,
     /// Failed to send to the rendering pipeline
     DisplayListFailed,
+    /// A JSON pointer isn't well-formed
+    InvalidPointer(String),
+    /// A JSON pointer doesn't resolve to anything within the current document
+    PointerNotFound(String),
+    /// A value supplied on the command line isn't a valid JSON literal
+    InvalidValue(String),
+    /// An operation requires a file to be specified, rather than stdin
+    FileRequired,
+    /// Failed to write some output
+    OutputFailed,
+    /// A query expression couldn't be parsed
+    InvalidQuery(String),
+    /// A transformation program couldn't be parsed
+    InvalidTransform(String),
+    /// A transformation program raised an error whilst running
+    TransformFailed(String),
+    /// A position doesn't fall within any element of the current document
+    PositionNotFound(String),
+    /// A regular expression couldn't be compiled
+    InvalidPattern(String),
+    /// A flattened assignment couldn't be parsed
+    InvalidAssignment(String),
+    /// An operation isn't supported for a given document format
+    FormatNotSupported(String),
+    /// A value can't be represented within the output format
+    NotRepresentable(String),
+    /// Binary output would be written straight to a terminal
+    BinaryToTerminal,
 }
 
 impl Display for ChiselError {
@@ -31,6 +59,25 @@ impl Display for ChiselError {
             ),
             Self::NoTty => write!(f, "Not a tty!"),
             Self::DisplayListFailed => write!(f, "Failed to send display list to renderer"),
+            Self::InvalidPointer(p) => write!(f, "Invalid JSON pointer specified: \"{}\"", p),
+            Self::PointerNotFound(p) => write!(f, "JSON pointer doesn't resolve: \"{}\"", p),
+            Self::InvalidValue(v) => write!(f, "Not a valid JSON value: {}", v),
+            Self::FileRequired => write!(f, "A source file must be specified for this operation"),
+            Self::OutputFailed => write!(f, "Failed to write output"),
+            Self::InvalidQuery(reason) => write!(f, "Invalid query: {}", reason),
+            Self::InvalidTransform(reason) => write!(f, "Invalid transform: {}", reason),
+            Self::TransformFailed(reason) => write!(f, "Transform failed: {}", reason),
+            Self::PositionNotFound(p) => write!(f, "Position isn't within the document: {}", p),
+            Self::InvalidPattern(reason) => write!(f, "Invalid regular expression: {}", reason),
+            Self::InvalidAssignment(a) => write!(f, "Not a valid assignment: {}", a),
+            Self::FormatNotSupported(what) => write!(f, "Not supported: {}", what),
+            Self::NotRepresentable(what) => {
+                write!(f, "Can't be represented in the output format: {}", what)
+            }
+            Self::BinaryToTerminal => write!(
+                f,
+                "Refusing to write binary output to a terminal, try redirecting it to a file"
+            ),
         }
     }
 }
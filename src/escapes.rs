@@ -0,0 +1,117 @@
+//! Quoting and escaping helpers for the various textual representations produced and consumed by chisel
+//!
+//! Note that the DOM and SAX parsers both hand back strings (and object keys) in their *raw* form, which
+//! means that the surrounding quotes and any escape sequences are retained exactly as they appeared in the
+//! source input.  The functions in here convert between that raw form and plain old Rust strings
+
+/// Quote and escape a plain string so that it forms a valid JSON string literal
+pub fn quote_json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '\u{08}' => quoted.push_str("\\b"),
+            '\u{0c}' => quoted.push_str("\\f"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Take a raw JSON string literal (quotes and all) and convert it into a plain string, decoding any escape
+/// sequences along the way.  Anything that doesn't look like a quoted literal is just returned as is
+pub fn unquote_json_string(raw: &str) -> String {
+    let inner = match raw.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(inner) => inner,
+        None => return raw.to_string(),
+    };
+    if !inner.contains('\\') {
+        return inner.to_string();
+    }
+
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            unquoted.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unquoted.push('\n'),
+            Some('r') => unquoted.push('\r'),
+            Some('t') => unquoted.push('\t'),
+            Some('b') => unquoted.push('\u{08}'),
+            Some('f') => unquoted.push('\u{0c}'),
+            Some('u') => {
+                let high = hex_code_unit(&mut chars);
+                if (0xd800..0xdc00).contains(&high) {
+                    // attempt to pair up a surrogate, falling back to a replacement character
+                    let mut lookahead = chars.clone();
+                    if lookahead.next() == Some('\\') && lookahead.next() == Some('u') {
+                        let low = hex_code_unit(&mut lookahead);
+                        if (0xdc00..0xe000).contains(&low) {
+                            let code = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+                            unquoted.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                            chars = lookahead;
+                            continue;
+                        }
+                    }
+                    unquoted.push('\u{fffd}');
+                } else {
+                    unquoted.push(char::from_u32(high).unwrap_or('\u{fffd}'));
+                }
+            }
+            Some(c) => unquoted.push(c),
+            None => unquoted.push('\\'),
+        }
+    }
+    unquoted
+}
+
+/// Consume (up to) four hex digits from a char iterator, and return the resultant code unit
+fn hex_code_unit(chars: &mut std::str::Chars) -> u32 {
+    let mut code = 0;
+    for _ in 0..4 {
+        match chars.clone().next().and_then(|c| c.to_digit(16)) {
+            Some(digit) => {
+                chars.next();
+                code = (code << 4) | digit
+            }
+            None => break,
+        }
+    }
+    code
+}
+
+/// Quote a field for inclusion within a CSV record, as per RFC 4180. Fields are only quoted when they contain a
+/// delimiter, a quote or a line break, and embedded quotes are doubled up
+pub fn quote_csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Escape a field for inclusion within a TSV record. Tabs, line breaks and backslashes can't appear literally, and
+/// so are replaced with their usual backslash escapes
+pub fn escape_tsv_field(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,977 @@
+//! BSON reader and writer. Documents are read as MongoDB Extended JSON (v2), in either canonical or relaxed mode, and
+//! any number of documents may appear back to back (as in the files produced by `mongodump`). When writing, Extended
+//! JSON wrappers in either mode are turned back into the BSON types they represent, and plain JSON values are written
+//! as their nearest equivalent, with integers becoming 32-bit values wherever they fit
+use super::{ByteRange, FormatError, Member, Node, NodeValue};
+use crate::errors::{ChiselError, ChiselResult};
+use crate::escapes::{quote_json_string, unquote_json_string};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chisel_json::JsonValue;
+use clap::ValueEnum;
+
+/// The two flavours of MongoDB Extended JSON
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ExtendedJson {
+    /// every value is wrapped, so that its exact BSON type is preserved
+    Canonical,
+    /// numbers and (most) dates are written as plain JSON, at the cost of some type information
+    Relaxed,
+}
+
+/// Element type markers
+mod kind {
+    pub const DOUBLE: u8 = 0x01;
+    pub const STRING: u8 = 0x02;
+    pub const DOCUMENT: u8 = 0x03;
+    pub const ARRAY: u8 = 0x04;
+    pub const BINARY: u8 = 0x05;
+    pub const UNDEFINED: u8 = 0x06;
+    pub const OBJECT_ID: u8 = 0x07;
+    pub const BOOLEAN: u8 = 0x08;
+    pub const DATE: u8 = 0x09;
+    pub const NULL: u8 = 0x0a;
+    pub const REGEX: u8 = 0x0b;
+    pub const DB_POINTER: u8 = 0x0c;
+    pub const CODE: u8 = 0x0d;
+    pub const SYMBOL: u8 = 0x0e;
+    pub const CODE_WITH_SCOPE: u8 = 0x0f;
+    pub const INT32: u8 = 0x10;
+    pub const TIMESTAMP: u8 = 0x11;
+    pub const INT64: u8 = 0x12;
+    pub const DECIMAL128: u8 = 0x13;
+    pub const MIN_KEY: u8 = 0xff;
+    pub const MAX_KEY: u8 = 0x7f;
+}
+
+/// The exponent bias for Decimal128 values
+const DECIMAL_BIAS: i32 = 6176;
+
+/// The largest Decimal128 exponent
+const DECIMAL_MAX_EXPONENT: i32 = 6111;
+
+/// The largest number of significant digits within a Decimal128 value
+const DECIMAL_MAX_DIGITS: usize = 34;
+
+/// Parse a stream of BSON documents, each of which becomes a separate document
+pub fn parse(buffer: &[u8], mode: ExtendedJson) -> Result<Vec<Node>, FormatError> {
+    let mut reader = Reader {
+        buffer,
+        position: 0,
+        mode,
+    };
+    let mut documents = vec![];
+    while reader.position < buffer.len() {
+        documents.push(reader.document(false)?);
+    }
+    Ok(documents)
+}
+
+/// Build a string node
+fn string_node(text: &str, range: ByteRange) -> Node {
+    Node {
+        value: NodeValue::String(quote_json_string(text)),
+        range,
+    }
+}
+
+/// Build an object node from a series of keys and values, all of which share the same range
+fn wrapper(members: Vec<(&str, Node)>, range: ByteRange) -> Node {
+    Node {
+        value: NodeValue::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| Member {
+                    key: quote_json_string(key),
+                    range: value.range,
+                    value,
+                })
+                .collect(),
+        ),
+        range,
+    }
+}
+
+/// The canonical text of a double, which is used for the values that can't be written as JSON numbers
+fn double_text(value: f64) -> String {
+    match value {
+        v if v.is_nan() => String::from("NaN"),
+        v if v == f64::INFINITY => String::from("Infinity"),
+        v if v == f64::NEG_INFINITY => String::from("-Infinity"),
+        v => format!("{:?}", v),
+    }
+}
+
+/// Reads documents from a buffer, keeping track of where each value came from
+struct Reader<'a> {
+    /// The whole of the input
+    buffer: &'a [u8],
+    /// The current offset within the input
+    position: usize,
+    /// How values are mapped onto Extended JSON
+    mode: ExtendedJson,
+}
+
+impl<'a> Reader<'a> {
+    /// Build an error at a given offset
+    fn error(&self, message: &str, offset: usize) -> FormatError {
+        FormatError {
+            message: String::from(message),
+            offset,
+        }
+    }
+
+    /// Take a number of bytes from the input
+    fn take(&mut self, length: usize) -> Result<&'a [u8], FormatError> {
+        if self.buffer.len() - self.position < length {
+            return Err(self.error("unexpected end of input", self.buffer.len()));
+        }
+        let bytes = &self.buffer[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    /// Take a fixed number of bytes from the input
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], FormatError> {
+        let mut bytes = [0u8; N];
+        bytes.copy_from_slice(self.take(N)?);
+        Ok(bytes)
+    }
+
+    /// Read a little endian 32-bit integer
+    fn read_i32(&mut self) -> Result<i32, FormatError> {
+        Ok(i32::from_le_bytes(self.take_array()?))
+    }
+
+    /// Read a little endian 64-bit integer
+    fn read_i64(&mut self) -> Result<i64, FormatError> {
+        Ok(i64::from_le_bytes(self.take_array()?))
+    }
+
+    /// Read a UTF-8 string
+    fn utf8(&self, bytes: &'a [u8], offset: usize) -> Result<&'a str, FormatError> {
+        std::str::from_utf8(bytes).map_err(|_| self.error("string isn't valid UTF-8", offset))
+    }
+
+    /// Read a null terminated string, along with its range (excluding the terminator)
+    fn cstring(&mut self) -> Result<(&'a str, ByteRange), FormatError> {
+        let start = self.position;
+        let length = self.buffer[start..]
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| self.error("unterminated string", start))?;
+        let bytes = self.take(length)?;
+        self.position += 1;
+        Ok((self.utf8(bytes, start)?, (start, start + length)))
+    }
+
+    /// Read a length prefixed string, along with the range of its content
+    fn string(&mut self) -> Result<(&'a str, ByteRange), FormatError> {
+        let start = self.position;
+        let length = self.read_i32()?;
+        if length < 1 {
+            return Err(self.error("invalid string length", start));
+        }
+        let content = self.position;
+        let bytes = self.take(length as usize)?;
+        if bytes[bytes.len() - 1] != 0 {
+            return Err(self.error("string is missing its terminator", start));
+        }
+        let text = self.utf8(&bytes[..bytes.len() - 1], content)?;
+        Ok((text, (content, content + text.len())))
+    }
+
+    /// Read an ObjectId as hex, along with its range
+    fn object_id(&mut self) -> Result<Node, FormatError> {
+        let start = self.position;
+        let bytes = self.take(12)?;
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        Ok(string_node(&hex, (start, self.position)))
+    }
+
+    /// Read a document (or an array, in which case the keys are dropped)
+    fn document(&mut self, array: bool) -> Result<Node, FormatError> {
+        let start = self.position;
+        let length = self.read_i32()?;
+        if length < 5 || length as usize > self.buffer.len() - start {
+            return Err(self.error("invalid document length", start));
+        }
+        let end = start + length as usize;
+
+        let mut members = vec![];
+        let mut values = vec![];
+        loop {
+            let offset = self.position;
+            if offset >= end {
+                return Err(self.error("document is missing its terminator", start));
+            }
+            let kind = self.take(1)?[0];
+            if kind == 0 {
+                break;
+            }
+            let (name, range) = self.cstring()?;
+            let value = self.element(kind, offset)?;
+            match array {
+                true => values.push(value),
+                false => members.push(Member {
+                    key: quote_json_string(name),
+                    range,
+                    value,
+                }),
+            }
+        }
+        if self.position != end {
+            return Err(self.error("document length doesn't match its content", start));
+        }
+        let value = match array {
+            true => NodeValue::Array(values),
+            false => NodeValue::Object(members),
+        };
+        Ok(Node {
+            value,
+            range: (start, end),
+        })
+    }
+
+    /// Read the value of an element, given its type
+    fn element(&mut self, kind: u8, offset: usize) -> Result<Node, FormatError> {
+        let start = self.position;
+        let relaxed = self.mode == ExtendedJson::Relaxed;
+        let node = match kind {
+            kind::DOUBLE => {
+                let value = f64::from_le_bytes(self.take_array()?);
+                let range = (start, self.position);
+                match relaxed && value.is_finite() {
+                    true => Node {
+                        value: NodeValue::Float(value),
+                        range,
+                    },
+                    false => wrapper(
+                        vec![("$numberDouble", string_node(&double_text(value), range))],
+                        range,
+                    ),
+                }
+            }
+            kind::STRING => {
+                let (text, range) = self.string()?;
+                string_node(text, range)
+            }
+            kind::DOCUMENT => self.document(false)?,
+            kind::ARRAY => self.document(true)?,
+            kind::BINARY => {
+                let length = self.read_i32()?;
+                if length < 0 {
+                    return Err(self.error("invalid binary length", start));
+                }
+                let subtype = self.take(1)?[0];
+                let content = self.position;
+                let bytes = self.take(length as usize)?;
+                let range = (start, self.position);
+                let binary = wrapper(
+                    vec![
+                        (
+                            "base64",
+                            string_node(&BASE64.encode(bytes), (content, self.position)),
+                        ),
+                        (
+                            "subType",
+                            string_node(&format!("{:02x}", subtype), (content - 1, content)),
+                        ),
+                    ],
+                    range,
+                );
+                wrapper(vec![("$binary", binary)], range)
+            }
+            kind::UNDEFINED => {
+                let range = (offset, start);
+                let value = Node {
+                    value: NodeValue::Boolean(true),
+                    range,
+                };
+                wrapper(vec![("$undefined", value)], range)
+            }
+            kind::OBJECT_ID => {
+                let id = self.object_id()?;
+                let range = id.range;
+                wrapper(vec![("$oid", id)], range)
+            }
+            kind::BOOLEAN => match self.take(1)?[0] {
+                0 => Node {
+                    value: NodeValue::Boolean(false),
+                    range: (start, self.position),
+                },
+                1 => Node {
+                    value: NodeValue::Boolean(true),
+                    range: (start, self.position),
+                },
+                _ => return Err(self.error("invalid boolean", start)),
+            },
+            kind::DATE => {
+                let millis = self.read_i64()?;
+                let range = (start, self.position);
+                let value = match format_date(millis).filter(|_| relaxed) {
+                    Some(text) => string_node(&text, range),
+                    None => wrapper(
+                        vec![("$numberLong", string_node(&millis.to_string(), range))],
+                        range,
+                    ),
+                };
+                wrapper(vec![("$date", value)], range)
+            }
+            kind::NULL => Node {
+                value: NodeValue::Null,
+                range: (offset, start),
+            },
+            kind::REGEX => {
+                let (pattern, pattern_range) = self.cstring()?;
+                let (options, options_range) = self.cstring()?;
+                let range = (start, self.position);
+                let regex = wrapper(
+                    vec![
+                        ("pattern", string_node(pattern, pattern_range)),
+                        ("options", string_node(options, options_range)),
+                    ],
+                    range,
+                );
+                wrapper(vec![("$regularExpression", regex)], range)
+            }
+            kind::DB_POINTER => {
+                let (namespace, namespace_range) = self.string()?;
+                let id = self.object_id()?;
+                let range = (start, self.position);
+                let id_range = id.range;
+                let pointer = wrapper(
+                    vec![
+                        ("$ref", string_node(namespace, namespace_range)),
+                        ("$id", wrapper(vec![("$oid", id)], id_range)),
+                    ],
+                    range,
+                );
+                wrapper(vec![("$dbPointer", pointer)], range)
+            }
+            kind::CODE | kind::SYMBOL => {
+                let (text, range) = self.string()?;
+                let key = match kind {
+                    kind::CODE => "$code",
+                    _ => "$symbol",
+                };
+                wrapper(
+                    vec![(key, string_node(text, range))],
+                    (start, self.position),
+                )
+            }
+            kind::CODE_WITH_SCOPE => {
+                let length = self.read_i32()?;
+                let (code, code_range) = self.string()?;
+                let scope = self.document(false)?;
+                if length < 0 || start + length as usize != self.position {
+                    return Err(self.error("code length doesn't match its content", start));
+                }
+                wrapper(
+                    vec![("$code", string_node(code, code_range)), ("$scope", scope)],
+                    (start, self.position),
+                )
+            }
+            kind::INT32 => {
+                let value = i64::from(self.read_i32()?);
+                self.integer("$numberInt", value, start)
+            }
+            kind::INT64 => {
+                let value = self.read_i64()?;
+                self.integer("$numberLong", value, start)
+            }
+            kind::TIMESTAMP => {
+                let increment = u32::from_le_bytes(self.take_array()?);
+                let time = u32::from_le_bytes(self.take_array()?);
+                let range = (start, self.position);
+                let timestamp = wrapper(
+                    vec![
+                        (
+                            "t",
+                            Node {
+                                value: NodeValue::Integer(i64::from(time)),
+                                range: (start + 4, range.1),
+                            },
+                        ),
+                        (
+                            "i",
+                            Node {
+                                value: NodeValue::Integer(i64::from(increment)),
+                                range: (start, start + 4),
+                            },
+                        ),
+                    ],
+                    range,
+                );
+                wrapper(vec![("$timestamp", timestamp)], range)
+            }
+            kind::DECIMAL128 => {
+                let bits = u128::from_le_bytes(self.take_array()?);
+                let range = (start, self.position);
+                wrapper(
+                    vec![("$numberDecimal", string_node(&format_decimal(bits), range))],
+                    range,
+                )
+            }
+            kind::MIN_KEY | kind::MAX_KEY => {
+                let range = (offset, start);
+                let key = match kind {
+                    kind::MIN_KEY => "$minKey",
+                    _ => "$maxKey",
+                };
+                let value = Node {
+                    value: NodeValue::Integer(1),
+                    range,
+                };
+                wrapper(vec![(key, value)], range)
+            }
+            kind => {
+                return Err(FormatError {
+                    message: format!("unknown element type 0x{:02x}", kind),
+                    offset,
+                })
+            }
+        };
+        Ok(node)
+    }
+
+    /// Build the node for an integer, which is only wrapped in canonical mode
+    fn integer(&self, key: &str, value: i64, start: usize) -> Node {
+        let range = (start, self.position);
+        match self.mode {
+            ExtendedJson::Relaxed => Node {
+                value: NodeValue::Integer(value),
+                range,
+            },
+            ExtendedJson::Canonical => {
+                wrapper(vec![(key, string_node(&value.to_string(), range))], range)
+            }
+        }
+    }
+}
+
+/// Work out the civil date for a number of days since the Unix epoch
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Work out the number of days since the Unix epoch for a civil date
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = i64::from(month);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Format a date as an ISO-8601 string, as used by relaxed mode. Only dates between the years 1970 and 9999 are
+/// written this way
+fn format_date(millis: i64) -> Option<String> {
+    let days = millis.div_euclid(86_400_000);
+    let remainder = millis.rem_euclid(86_400_000);
+    let (year, month, day) = civil_from_days(days);
+    if !(1970..=9999).contains(&year) {
+        return None;
+    }
+    let (seconds, millis) = (remainder / 1000, remainder % 1000);
+    let time = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    match millis {
+        0 => Some(format!("{}Z", time)),
+        millis => Some(format!("{}.{:03}Z", time, millis)),
+    }
+}
+
+/// Parse an ISO-8601 date (with a time and either a `Z` or a numeric offset) into milliseconds since the epoch
+fn parse_date(text: &str) -> Option<i64> {
+    let number = |from: usize, to: usize| -> Option<i64> {
+        let digits = text.get(from..to)?;
+        match digits.bytes().all(|b| b.is_ascii_digit()) {
+            true => digits.parse().ok(),
+            false => None,
+        }
+    };
+    let bytes = text.as_bytes();
+    if bytes.len() < 20 || bytes[4] != b'-' || bytes[7] != b'-' || bytes[10] != b'T' {
+        return None;
+    }
+    if bytes[13] != b':' || bytes[16] != b':' {
+        return None;
+    }
+    let (year, month, day) = (number(0, 4)?, number(5, 7)?, number(8, 10)?);
+    let (hours, minutes, seconds) = (number(11, 13)?, number(14, 16)?, number(17, 19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hours > 23 || minutes > 59 {
+        return None;
+    }
+    if seconds > 60 {
+        return None;
+    }
+
+    // optional fractional seconds, of which only the milliseconds are kept
+    let mut rest = &text[19..];
+    let mut millis = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        let padded = format!("{:0<3}", &fraction[..digits.min(3)]);
+        millis = padded.parse::<i64>().ok()?;
+        rest = &fraction[digits..];
+    }
+    let offset = match rest {
+        "Z" => 0,
+        _ => {
+            let sign = match rest.as_bytes().first()? {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let digits = rest[1..].replace(':', "");
+            if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let (h, m): (i64, i64) = (digits[..2].parse().ok()?, digits[2..].parse().ok()?);
+            sign * (h * 60 + m)
+        }
+    };
+    let days = days_from_civil(year, month as u32, day as u32);
+    Some(((days * 24 + hours) * 60 + minutes - offset) * 60_000 + seconds * 1000 + millis)
+}
+
+/// Format a Decimal128 value as a string, following the IEEE 754 to-scientific-string conversion
+fn format_decimal(bits: u128) -> String {
+    let sign = match bits >> 127 {
+        1 => "-",
+        _ => "",
+    };
+    let combination = (bits >> 122) & 0x1f;
+    let (exponent, coefficient) = match combination {
+        0x1e => return format!("{}Infinity", sign),
+        0x1f => return String::from("NaN"),
+        // the large coefficient form can only hold values beyond the 34 digit limit, which are read as zero
+        c if c >> 3 == 0x3 => (((bits >> 111) & 0x3fff) as i32, 0),
+        _ => (((bits >> 113) & 0x3fff) as i32, bits & ((1u128 << 113) - 1)),
+    };
+    let exponent = exponent - DECIMAL_BIAS;
+    let coefficient = match coefficient.to_string() {
+        digits if digits.len() > DECIMAL_MAX_DIGITS => String::from("0"),
+        digits => digits,
+    };
+    let length = coefficient.len() as i32;
+    let adjusted = exponent + length - 1;
+
+    if exponent <= 0 && adjusted >= -6 {
+        if exponent == 0 {
+            return format!("{}{}", sign, coefficient);
+        }
+        let point = length + exponent;
+        return match point > 0 {
+            true => format!(
+                "{}{}.{}",
+                sign,
+                &coefficient[..point as usize],
+                &coefficient[point as usize..]
+            ),
+            false => format!("{}0.{}{}", sign, "0".repeat(-point as usize), coefficient),
+        };
+    }
+    let mantissa = match coefficient.len() {
+        1 => coefficient,
+        _ => format!("{}.{}", &coefficient[..1], &coefficient[1..]),
+    };
+    format!("{}{}E{:+}", sign, mantissa, adjusted)
+}
+
+/// Parse a decimal string into a Decimal128 value
+fn parse_decimal(text: &str) -> Option<u128> {
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let sign = u128::from(negative) << 127;
+    match unsigned.to_ascii_lowercase().as_str() {
+        "inf" | "infinity" => return Some(sign | (0x1e << 122)),
+        "nan" => return Some(0x1f << 122),
+        _ => (),
+    }
+
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(index) => (
+            &unsigned[..index],
+            unsigned[index + 1..].parse::<i32>().ok()?,
+        ),
+        None => (unsigned, 0),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    if !whole
+        .bytes()
+        .chain(fraction.bytes())
+        .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let digits = format!("{}{}", whole, fraction);
+    let digits = match digits.trim_start_matches('0') {
+        "" => "0",
+        digits => digits,
+    };
+    let mut exponent = exponent.checked_sub(fraction.len() as i32)?;
+    let mut digits = digits.to_string();
+
+    // pad out (or trim off trailing zeros) to bring the exponent within range, where that can be done exactly
+    while exponent > DECIMAL_MAX_EXPONENT && digits.len() < DECIMAL_MAX_DIGITS && digits != "0" {
+        digits.push('0');
+        exponent -= 1;
+    }
+    while exponent < -DECIMAL_BIAS && digits.ends_with('0') && digits.len() > 1 {
+        digits.pop();
+        exponent += 1;
+    }
+    if digits == "0" {
+        exponent = exponent.clamp(-DECIMAL_BIAS, DECIMAL_MAX_EXPONENT);
+    }
+    if digits.len() > DECIMAL_MAX_DIGITS
+        || !(-DECIMAL_BIAS..=DECIMAL_MAX_EXPONENT).contains(&exponent)
+    {
+        return None;
+    }
+    let coefficient: u128 = digits.parse().ok()?;
+    Some(sign | (((exponent + DECIMAL_BIAS) as u128) << 113) | coefficient)
+}
+
+/// Encode a document as BSON, appending it to a buffer. Only objects can be written as BSON documents
+pub fn encode(value: &JsonValue, buffer: &mut Vec<u8>) -> ChiselResult<()> {
+    match value {
+        JsonValue::Object(pairs) => {
+            let pairs = pairs
+                .iter()
+                .map(|(key, value)| (unquote_json_string(key), value));
+            encode_document(pairs, buffer)
+        }
+        _ => Err(ChiselError::NotRepresentable(String::from(
+            "BSON documents must be objects",
+        ))),
+    }
+}
+
+/// Encode a series of elements as a document, including the length prefix and terminator
+fn encode_document<'a, 'b: 'a>(
+    elements: impl Iterator<Item = (String, &'a JsonValue<'b>)>,
+    buffer: &mut Vec<u8>,
+) -> ChiselResult<()> {
+    let start = buffer.len();
+    buffer.extend_from_slice(&[0; 4]);
+    for (name, value) in elements {
+        let marker = buffer.len();
+        buffer.push(0);
+        push_cstring(&name, buffer)?;
+        buffer[marker] = encode_element(value, buffer)?;
+    }
+    buffer.push(0);
+    patch_length(start, buffer)
+}
+
+/// Fill in a length prefix, covering everything from the prefix onwards
+fn patch_length(start: usize, buffer: &mut [u8]) -> ChiselResult<()> {
+    let length = i32::try_from(buffer.len() - start).map_err(|_| {
+        ChiselError::NotRepresentable(String::from("document exceeds the BSON size limit"))
+    })?;
+    buffer[start..start + 4].copy_from_slice(&length.to_le_bytes());
+    Ok(())
+}
+
+/// Write a null terminated string
+fn push_cstring(text: &str, buffer: &mut Vec<u8>) -> ChiselResult<()> {
+    if text.contains('\0') {
+        return Err(ChiselError::NotRepresentable(format!(
+            "name containing a null character: {:?}",
+            text
+        )));
+    }
+    buffer.extend_from_slice(text.as_bytes());
+    buffer.push(0);
+    Ok(())
+}
+
+/// Write a length prefixed string. The length covers the content and terminator, but not the prefix itself
+fn push_string(text: &str, buffer: &mut Vec<u8>) -> ChiselResult<()> {
+    let length = i32::try_from(text.len() + 1).map_err(|_| {
+        ChiselError::NotRepresentable(String::from("string exceeds the BSON size limit"))
+    })?;
+    buffer.extend_from_slice(&length.to_le_bytes());
+    buffer.extend_from_slice(text.as_bytes());
+    buffer.push(0);
+    Ok(())
+}
+
+/// Write the value of an element, returning its type
+fn encode_element(value: &JsonValue, buffer: &mut Vec<u8>) -> ChiselResult<u8> {
+    match value {
+        JsonValue::Null => Ok(kind::NULL),
+        JsonValue::Boolean(value) => {
+            buffer.push(u8::from(*value));
+            Ok(kind::BOOLEAN)
+        }
+        JsonValue::Integer(value) => match i32::try_from(*value) {
+            Ok(value) => {
+                buffer.extend_from_slice(&value.to_le_bytes());
+                Ok(kind::INT32)
+            }
+            Err(_) => {
+                buffer.extend_from_slice(&value.to_le_bytes());
+                Ok(kind::INT64)
+            }
+        },
+        JsonValue::Float(value) => {
+            buffer.extend_from_slice(&value.to_le_bytes());
+            Ok(kind::DOUBLE)
+        }
+        JsonValue::String(raw) => {
+            push_string(&unquote_json_string(raw), buffer)?;
+            Ok(kind::STRING)
+        }
+        JsonValue::Array(values) => {
+            let elements = values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| (index.to_string(), value));
+            encode_document(elements, buffer)?;
+            Ok(kind::ARRAY)
+        }
+        JsonValue::Object(pairs) => match encode_extended(pairs, buffer)? {
+            Some(kind) => Ok(kind),
+            None => {
+                let pairs = pairs
+                    .iter()
+                    .map(|(key, value)| (unquote_json_string(key), value));
+                encode_document(pairs, buffer)?;
+                Ok(kind::DOCUMENT)
+            }
+        },
+    }
+}
+
+/// Look up a member of an object by its (decoded) key
+fn member<'a, 'b>(pairs: &'a [(String, JsonValue<'b>)], key: &str) -> Option<&'a JsonValue<'b>> {
+    pairs
+        .iter()
+        .find(|(k, _)| unquote_json_string(k) == key)
+        .map(|(_, value)| value)
+}
+
+/// Get the text of a string value
+fn text_of(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::String(raw) => Some(unquote_json_string(raw)),
+        _ => None,
+    }
+}
+
+/// Get the value of an integer that fits within a u32
+fn u32_of(value: &JsonValue) -> Option<u32> {
+    match value {
+        JsonValue::Integer(value) => u32::try_from(*value).ok(),
+        _ => None,
+    }
+}
+
+/// Decode an ObjectId from its hex representation
+fn object_id(value: &JsonValue) -> Option<Vec<u8>> {
+    let hex = text_of(value)?;
+    if hex.len() != 24 || !hex.is_ascii() {
+        return None;
+    }
+    (0..24)
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+        .collect()
+}
+
+/// Write an Extended JSON wrapper as the BSON value it represents, returning its type. Objects which aren't
+/// wrappers are left alone, and wrappers holding invalid values are rejected
+fn encode_extended(
+    pairs: &[(String, JsonValue)],
+    buffer: &mut Vec<u8>,
+) -> ChiselResult<Option<u8>> {
+    let keys: Vec<String> = pairs
+        .iter()
+        .map(|(key, _)| unquote_json_string(key))
+        .collect();
+    let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+    let first = match pairs.first() {
+        Some((_, value)) => value,
+        None => return Ok(None),
+    };
+    let invalid = |key: &str| ChiselError::NotRepresentable(format!("invalid {} value", key));
+
+    let kind = match keys.as_slice() {
+        ["$oid"] => {
+            buffer.extend(object_id(first).ok_or_else(|| invalid("$oid"))?);
+            kind::OBJECT_ID
+        }
+        ["$numberInt"] => {
+            let value: i32 = text_of(first)
+                .and_then(|text| text.parse().ok())
+                .ok_or_else(|| invalid("$numberInt"))?;
+            buffer.extend_from_slice(&value.to_le_bytes());
+            kind::INT32
+        }
+        ["$numberLong"] => {
+            let value: i64 = text_of(first)
+                .and_then(|text| text.parse().ok())
+                .ok_or_else(|| invalid("$numberLong"))?;
+            buffer.extend_from_slice(&value.to_le_bytes());
+            kind::INT64
+        }
+        ["$numberDouble"] => {
+            let value = match text_of(first).as_deref() {
+                Some("Infinity") => Some(f64::INFINITY),
+                Some("-Infinity") => Some(f64::NEG_INFINITY),
+                Some("NaN") => Some(f64::NAN),
+                Some(text) => text.parse::<f64>().ok().filter(|v| v.is_finite()),
+                None => None,
+            }
+            .ok_or_else(|| invalid("$numberDouble"))?;
+            buffer.extend_from_slice(&value.to_le_bytes());
+            kind::DOUBLE
+        }
+        ["$numberDecimal"] => {
+            let bits = text_of(first)
+                .and_then(|text| parse_decimal(&text))
+                .ok_or_else(|| invalid("$numberDecimal"))?;
+            buffer.extend_from_slice(&bits.to_le_bytes());
+            kind::DECIMAL128
+        }
+        ["$date"] => {
+            let millis = match first {
+                JsonValue::Object(inner) if inner.len() == 1 => member(inner, "$numberLong")
+                    .and_then(text_of)
+                    .and_then(|text| text.parse().ok()),
+                JsonValue::String(raw) => parse_date(&unquote_json_string(raw)),
+                JsonValue::Integer(value) => Some(*value),
+                _ => None,
+            }
+            .ok_or_else(|| invalid("$date"))?;
+            buffer.extend_from_slice(&millis.to_le_bytes());
+            kind::DATE
+        }
+        ["$binary"] => {
+            let JsonValue::Object(inner) = first else {
+                return Err(invalid("$binary"));
+            };
+            let bytes = member(inner, "base64")
+                .and_then(text_of)
+                .and_then(|text| BASE64.decode(text).ok())
+                .ok_or_else(|| invalid("$binary"))?;
+            let subtype = member(inner, "subType")
+                .and_then(text_of)
+                .and_then(|text| {
+                    u8::from_str_radix(&text, 16)
+                        .ok()
+                        .filter(|_| text.len() <= 2)
+                })
+                .ok_or_else(|| invalid("$binary"))?;
+            let length = i32::try_from(bytes.len()).map_err(|_| invalid("$binary"))?;
+            buffer.extend_from_slice(&length.to_le_bytes());
+            buffer.push(subtype);
+            buffer.extend(bytes);
+            kind::BINARY
+        }
+        ["$regularExpression"] => {
+            let JsonValue::Object(inner) = first else {
+                return Err(invalid("$regularExpression"));
+            };
+            let pattern = member(inner, "pattern").and_then(text_of);
+            let options = member(inner, "options").and_then(text_of);
+            let (Some(pattern), Some(options)) = (pattern, options) else {
+                return Err(invalid("$regularExpression"));
+            };
+
+            // options are always written in alphabetical order
+            let mut options: Vec<char> = options.chars().collect();
+            options.sort_unstable();
+            push_cstring(&pattern, buffer)?;
+            push_cstring(&options.into_iter().collect::<String>(), buffer)?;
+            kind::REGEX
+        }
+        ["$timestamp"] => {
+            let JsonValue::Object(inner) = first else {
+                return Err(invalid("$timestamp"));
+            };
+            let time = member(inner, "t").and_then(u32_of);
+            let increment = member(inner, "i").and_then(u32_of);
+            let (Some(time), Some(increment)) = (time, increment) else {
+                return Err(invalid("$timestamp"));
+            };
+            buffer.extend_from_slice(&increment.to_le_bytes());
+            buffer.extend_from_slice(&time.to_le_bytes());
+            kind::TIMESTAMP
+        }
+        ["$dbPointer"] => {
+            let JsonValue::Object(inner) = first else {
+                return Err(invalid("$dbPointer"));
+            };
+            let namespace = member(inner, "$ref").and_then(text_of);
+            let id = match member(inner, "$id") {
+                Some(JsonValue::Object(id)) => member(id, "$oid").and_then(object_id),
+                _ => None,
+            };
+            let (Some(namespace), Some(id)) = (namespace, id) else {
+                return Err(invalid("$dbPointer"));
+            };
+            push_string(&namespace, buffer)?;
+            buffer.extend(id);
+            kind::DB_POINTER
+        }
+        ["$code"] => {
+            push_string(&text_of(first).ok_or_else(|| invalid("$code"))?, buffer)?;
+            kind::CODE
+        }
+        ["$code", "$scope"] => {
+            let code = text_of(first).ok_or_else(|| invalid("$code"))?;
+            let Some(JsonValue::Object(scope)) = member(pairs, "$scope") else {
+                return Err(invalid("$scope"));
+            };
+            let start = buffer.len();
+            buffer.extend_from_slice(&[0; 4]);
+            push_string(&code, buffer)?;
+            let scope = scope
+                .iter()
+                .map(|(key, value)| (unquote_json_string(key), value));
+            encode_document(scope, buffer)?;
+            patch_length(start, buffer)?;
+            kind::CODE_WITH_SCOPE
+        }
+        ["$symbol"] => {
+            push_string(&text_of(first).ok_or_else(|| invalid("$symbol"))?, buffer)?;
+            kind::SYMBOL
+        }
+        ["$undefined"] => kind::UNDEFINED,
+        ["$minKey"] => kind::MIN_KEY,
+        ["$maxKey"] => kind::MAX_KEY,
+        _ => return Ok(None),
+    };
+    Ok(Some(kind))
+}
@@ -0,0 +1,272 @@
+//! CBOR reader and writer. Byte strings are read as objects with a single `@binary` member, holding their content
+//! according to a [BinaryStyle], e.g. `{"@binary": "AQID"}`. Tags become tagged values, e.g.
+//! `{"@tag": 2, "@value": {"@binary": "AQID"}}`, and map keys which aren't text strings are replaced by their JSON
+//! text. The simple values `false`, `true`, `null` and `undefined` map onto their JSON equivalents (with `undefined`
+//! becoming a null), and any other simple values are read as plain integers. Integers outside the range of an `i64`
+//! are read as floats. When writing, binary objects (holding either form of content) become byte strings
+use super::{
+    decode_binary, tagged_parts, BinaryStyle, ByteRange, FormatError, Member, Node, NodeValue,
+    MAX_DEPTH,
+};
+use crate::errors::{ChiselError, ChiselResult};
+use crate::escapes::{quote_json_string, unquote_json_string};
+use chisel_json::JsonValue;
+use ciborium_ll::{simple, Decoder, Encoder, Header};
+
+/// Parse a stream of CBOR data items, each of which becomes a separate document
+pub fn parse(buffer: &[u8], binary: BinaryStyle) -> Result<Vec<Node>, FormatError> {
+    let mut reader = Reader {
+        decoder: Decoder::from(buffer),
+        length: buffer.len(),
+        binary,
+        depth: 0,
+    };
+    let mut documents = vec![];
+    while reader.decoder.offset() < buffer.len() {
+        documents.push(reader.node()?);
+    }
+    Ok(documents)
+}
+
+/// Reads data items from a buffer, keeping track of where each one came from
+struct Reader<'a> {
+    /// The decoder, which tracks the current offset
+    decoder: Decoder<&'a [u8]>,
+    /// The length of the input
+    length: usize,
+    /// How byte strings are read
+    binary: BinaryStyle,
+    /// The nesting depth of the data item currently being read
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Build an error, given a decoder error and the offset at which things started going wrong
+    fn error<T>(&self, err: ciborium_ll::Error<T>) -> FormatError {
+        match err {
+            ciborium_ll::Error::Syntax(offset) => FormatError {
+                message: String::from("invalid data item"),
+                offset,
+            },
+            ciborium_ll::Error::Io(_) => FormatError {
+                message: String::from("unexpected end of input"),
+                offset: self.length,
+            },
+        }
+    }
+
+    /// Pull the next header
+    fn pull(&mut self) -> Result<Header, FormatError> {
+        self.decoder.pull().map_err(|err| self.error(err))
+    }
+
+    /// Check that a collection length is plausible, given that every entry needs at least one byte
+    fn check_length(&mut self, length: usize) -> Result<usize, FormatError> {
+        match length <= self.length - self.decoder.offset() {
+            true => Ok(length),
+            false => Err(FormatError {
+                message: String::from("unexpected end of input"),
+                offset: self.length,
+            }),
+        }
+    }
+
+    /// Read a single data item
+    fn node(&mut self) -> Result<Node, FormatError> {
+        let start = self.decoder.offset();
+        let header = self.pull()?;
+        self.node_from(header, start)
+    }
+
+    /// Read the rest of a data item, given its header and where it started, keeping track of how deeply nested it is
+    fn node_from(&mut self, header: Header, start: usize) -> Result<Node, FormatError> {
+        if self.depth == MAX_DEPTH {
+            return Err(FormatError {
+                message: format!("data items are nested more than {} deep", MAX_DEPTH),
+                offset: start,
+            });
+        }
+        self.depth += 1;
+        let node = self.item_from(header, start);
+        self.depth -= 1;
+        node
+    }
+
+    /// Read the rest of a data item, given its header and where it started
+    fn item_from(&mut self, header: Header, start: usize) -> Result<Node, FormatError> {
+        let value = match header {
+            Header::Positive(value) => match i64::try_from(value) {
+                Ok(value) => NodeValue::Integer(value),
+                Err(_) => NodeValue::Float(value as f64),
+            },
+            Header::Negative(value) => match i64::try_from(value) {
+                Ok(value) => NodeValue::Integer(-1 - value),
+                Err(_) => NodeValue::Float(-1.0 - value as f64),
+            },
+            Header::Float(value) => NodeValue::float(value),
+            Header::Simple(simple::FALSE) => NodeValue::Boolean(false),
+            Header::Simple(simple::TRUE) => NodeValue::Boolean(true),
+            Header::Simple(simple::NULL | simple::UNDEFINED) => NodeValue::Null,
+            Header::Simple(value) => NodeValue::Integer(i64::from(value)),
+            Header::Tag(tag) => {
+                let tag_range = (start, self.decoder.offset());
+                let content = self.node()?;
+                match i64::try_from(tag) {
+                    Ok(tag) => NodeValue::tagged(tag, tag_range, content),
+                    Err(_) => {
+                        return Err(FormatError {
+                            message: String::from("tag number is too large"),
+                            offset: start,
+                        })
+                    }
+                }
+            }
+            Header::Break => {
+                return Err(FormatError {
+                    message: String::from("unexpected break"),
+                    offset: start,
+                })
+            }
+            Header::Bytes(length) => {
+                let bytes = self.bytes(length).map_err(|err| self.error(err))?;
+                NodeValue::binary(&bytes, self.binary, (start, self.decoder.offset()))
+            }
+            Header::Text(length) => {
+                let text = self.text(length).map_err(|err| self.error(err))?;
+                NodeValue::String(quote_json_string(&text))
+            }
+            Header::Array(length) => {
+                let mut values = vec![];
+                match length {
+                    Some(length) => {
+                        values.reserve(self.check_length(length)?);
+                        for _ in 0..length {
+                            values.push(self.node()?);
+                        }
+                    }
+                    None => {
+                        while let Some((header, start)) = self.next_or_break()? {
+                            values.push(self.node_from(header, start)?);
+                        }
+                    }
+                }
+                NodeValue::Array(values)
+            }
+            Header::Map(length) => {
+                let mut members = vec![];
+                match length {
+                    Some(length) => {
+                        members.reserve(self.check_length(length)?);
+                        for _ in 0..length {
+                            let key = self.node()?;
+                            members.push(self.member(key)?);
+                        }
+                    }
+                    None => {
+                        while let Some((header, start)) = self.next_or_break()? {
+                            let key = self.node_from(header, start)?;
+                            members.push(self.member(key)?);
+                        }
+                    }
+                }
+                NodeValue::Object(members)
+            }
+        };
+        Ok(Node {
+            value,
+            range: (start, self.decoder.offset()),
+        })
+    }
+
+    /// Read the content of a byte string, which may be split into chunks
+    fn bytes(
+        &mut self,
+        length: Option<usize>,
+    ) -> Result<Vec<u8>, ciborium_ll::Error<std::io::Error>> {
+        let mut bytes = vec![];
+        let mut buffer = [0u8; 4096];
+        let mut segments = self.decoder.bytes(length);
+        while let Some(mut segment) = segments.pull()? {
+            while let Some(chunk) = segment.pull(&mut buffer)? {
+                bytes.extend_from_slice(chunk);
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Read the content of a text string, which may be split into chunks
+    fn text(
+        &mut self,
+        length: Option<usize>,
+    ) -> Result<String, ciborium_ll::Error<std::io::Error>> {
+        let mut text = String::new();
+        let mut buffer = [0u8; 4096];
+        let mut segments = self.decoder.text(length);
+        while let Some(mut segment) = segments.pull()? {
+            while let Some(chunk) = segment.pull(&mut buffer)? {
+                text.push_str(chunk);
+            }
+        }
+        Ok(text)
+    }
+
+    /// Pull the next header within an indefinite length collection, returning [None] at the break
+    fn next_or_break(&mut self) -> Result<Option<(Header, usize)>, FormatError> {
+        let start = self.decoder.offset();
+        match self.pull()? {
+            Header::Break => Ok(None),
+            header => Ok(Some((header, start))),
+        }
+    }
+
+    /// Read the value of a map entry, given its key
+    fn member(&mut self, key: Node) -> Result<Member, FormatError> {
+        let range: ByteRange = key.range;
+        Ok(Member {
+            key: key.into_key(),
+            range,
+            value: self.node()?,
+        })
+    }
+}
+
+/// Encode a document as CBOR, appending it to a buffer. Binary values are written as byte strings, tagged values are
+/// written as tags, and everything else is written as its nearest equivalent
+pub fn encode(value: &JsonValue, buffer: &mut Vec<u8>) -> ChiselResult<()> {
+    let mut encoder = Encoder::from(buffer);
+    encode_value(value, &mut encoder).or(Err(ChiselError::OutputFailed))
+}
+
+/// Encode a single value
+fn encode_value(value: &JsonValue, encoder: &mut Encoder<&mut Vec<u8>>) -> std::io::Result<()> {
+    match value {
+        JsonValue::Null => encoder.push(Header::Simple(simple::NULL)),
+        JsonValue::Boolean(false) => encoder.push(Header::Simple(simple::FALSE)),
+        JsonValue::Boolean(true) => encoder.push(Header::Simple(simple::TRUE)),
+        JsonValue::Integer(value) if *value >= 0 => encoder.push(Header::Positive(*value as u64)),
+        JsonValue::Integer(value) => encoder.push(Header::Negative(!*value as u64)),
+        JsonValue::Float(value) => encoder.push(Header::Float(*value)),
+        JsonValue::String(raw) => encoder.text(&unquote_json_string(raw), None),
+        JsonValue::Array(values) => {
+            encoder.push(Header::Array(Some(values.len())))?;
+            values
+                .iter()
+                .try_for_each(|value| encode_value(value, encoder))
+        }
+        JsonValue::Object(pairs) => {
+            if let Some(data) = decode_binary(value) {
+                return encoder.bytes(&data, None);
+            }
+            if let Some((tag, content)) = tagged_parts(pairs).filter(|(tag, _)| *tag >= 0) {
+                encoder.push(Header::Tag(tag as u64))?;
+                return encode_value(content, encoder);
+            }
+            encoder.push(Header::Map(Some(pairs.len())))?;
+            for (key, value) in pairs {
+                encoder.text(&unquote_json_string(key), None)?;
+                encode_value(value, encoder)?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! CSV and TSV readers. The first record is taken to be a header, and every following record becomes an object keyed
+//! by the names within the header. Every value is read as a string, with any type inference being left to the caller
+use super::{ByteRange, FormatError, Member, Node, NodeValue};
+use crate::escapes::quote_json_string;
+
+/// A single field, along with the range of the input it was read from
+struct Field {
+    /// The (unquoted or unescaped) text of the field
+    text: String,
+    /// Where the field was found
+    range: ByteRange,
+}
+
+/// Parse a delimited document, with fields separated by either commas or tabs
+pub fn parse(source: &str, tabs: bool) -> Result<Node, FormatError> {
+    let mut records = records(source, tabs)?.into_iter();
+    let Some(header) = records.next() else {
+        return Ok(Node {
+            value: NodeValue::Array(vec![]),
+            range: (0, 0),
+        });
+    };
+
+    let mut end = header.last().map_or(0, |field| field.range.1);
+    let mut rows = vec![];
+    for record in records {
+        let range = (record[0].range.0, record[record.len() - 1].range.1);
+        if record.len() != header.len() {
+            return Err(FormatError {
+                message: format!(
+                    "expected {} fields (as per the header), but found {}",
+                    header.len(),
+                    record.len()
+                ),
+                offset: range.0,
+            });
+        }
+        let members = header
+            .iter()
+            .zip(record)
+            .map(|(name, field)| Member {
+                key: quote_json_string(&name.text),
+                range: field.range,
+                value: Node {
+                    value: NodeValue::String(quote_json_string(&field.text)),
+                    range: field.range,
+                },
+            })
+            .collect();
+        rows.push(Node {
+            value: NodeValue::Object(members),
+            range,
+        });
+        end = range.1;
+    }
+    Ok(Node {
+        value: NodeValue::Array(rows),
+        range: (0, end),
+    })
+}
+
+/// Split the input up into records, skipping any blank lines. CSV fields may be quoted as per RFC 4180, whereas TSV
+/// fields can't be quoted, but may contain backslash escapes
+fn records(source: &str, tabs: bool) -> Result<Vec<Vec<Field>>, FormatError> {
+    let bytes = source.as_bytes();
+    let delimiter = if tabs { b'\t' } else { b',' };
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut position = 0;
+    loop {
+        let start = position;
+        let text = if !tabs && bytes.get(position) == Some(&b'"') {
+            let (text, end) = quoted_field(source, position)?;
+            position = end;
+            text
+        } else {
+            let end = bytes[position..]
+                .iter()
+                .position(|&b| b == delimiter || b == b'\n' || b == b'\r')
+                .map_or(bytes.len(), |length| position + length);
+            let text = &source[position..end];
+            position = end;
+            match tabs {
+                true => unescape_tsv_field(text),
+                false => text.to_string(),
+            }
+        };
+        record.push(Field {
+            text,
+            range: (start, position),
+        });
+
+        // work out whether the record carries on, and skip over any line break
+        match bytes.get(position) {
+            Some(&b) if b == delimiter => {
+                position += 1;
+                continue;
+            }
+            Some(b'\r') if bytes.get(position + 1) == Some(&b'\n') => position += 2,
+            Some(b'\n' | b'\r') => position += 1,
+            Some(_) => {
+                return Err(FormatError {
+                    message: String::from("unexpected character after a quoted field"),
+                    offset: position,
+                })
+            }
+            None => (),
+        }
+        let blank = record.len() == 1 && record[0].range.0 == record[0].range.1;
+        if !blank {
+            records.push(std::mem::take(&mut record));
+        }
+        record.clear();
+        if position >= bytes.len() {
+            break;
+        }
+    }
+    Ok(records)
+}
+
+/// Read a quoted CSV field starting at a given offset, returning its text and the offset just after it
+fn quoted_field(source: &str, start: usize) -> Result<(String, usize), FormatError> {
+    let bytes = source.as_bytes();
+    let mut text = String::new();
+    let mut chunk = start + 1;
+    let mut position = chunk;
+    loop {
+        match bytes.get(position) {
+            None => {
+                return Err(FormatError {
+                    message: String::from("unterminated quoted field"),
+                    offset: start,
+                })
+            }
+            Some(b'"') if bytes.get(position + 1) == Some(&b'"') => {
+                text.push_str(&source[chunk..=position]);
+                position += 2;
+                chunk = position;
+            }
+            Some(b'"') => {
+                text.push_str(&source[chunk..position]);
+                return Ok((text, position + 1));
+            }
+            Some(_) => position += 1,
+        }
+    }
+}
+
+/// Reverse the escaping applied to TSV fields. Unrecognised escapes are left as they are
+fn unescape_tsv_field(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            unescaped.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
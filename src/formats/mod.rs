@@ -0,0 +1,398 @@
+//! Readers for document formats other than JSON.  Each reader produces a tree of [Node]s, which records the range of
+//! the input that every value was read from.  The tree can then either be converted into a [JsonValue], or replayed
+//! as a stream of SAX events, so that commands can operate on other formats exactly as they would on JSON.  Binary
+//! formats can't be rendered as text, so their writers also live here
+use crate::dom::compact_json;
+use crate::escapes::quote_json_string;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bson::ExtendedJson;
+use chisel_json::JsonValue;
+use clap::ValueEnum;
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::path::Path;
+use xml::XmlOptions;
+
+pub mod bson;
+pub mod cbor;
+pub mod csv;
+pub mod msgpack;
+pub mod toml;
+pub mod xml;
+pub mod yaml;
+
+/// The different document formats that are understood
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum DocumentFormat {
+    /// JSON
+    Json,
+    /// YAML 1.2
+    Yaml,
+    /// TOML 1.0
+    Toml,
+    /// Comma separated values, as per RFC 4180
+    Csv,
+    /// Tab separated values
+    Tsv,
+    /// XML 1.0
+    Xml,
+    /// MessagePack
+    Msgpack,
+    /// CBOR, as per RFC 8949
+    Cbor,
+    /// BSON, read as MongoDB Extended JSON
+    Bson,
+}
+
+impl DocumentFormat {
+    /// Work out the format of an input. An explicit choice always wins, otherwise the extension of the source file
+    /// is used, falling back to JSON
+    pub fn detect(explicit: Option<DocumentFormat>, file: Option<&Path>) -> DocumentFormat {
+        if let Some(format) = explicit {
+            return format;
+        }
+        let extension = file
+            .and_then(|path| path.extension())
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("yaml" | "yml") => DocumentFormat::Yaml,
+            Some("toml") => DocumentFormat::Toml,
+            Some("csv") => DocumentFormat::Csv,
+            Some("tsv" | "tab") => DocumentFormat::Tsv,
+            Some("xml") => DocumentFormat::Xml,
+            Some("msgpack" | "mpk") => DocumentFormat::Msgpack,
+            Some("cbor") => DocumentFormat::Cbor,
+            Some("bson") => DocumentFormat::Bson,
+            _ => DocumentFormat::Json,
+        }
+    }
+}
+
+impl Display for DocumentFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Json => write!(f, "json"),
+            Self::Yaml => write!(f, "yaml"),
+            Self::Toml => write!(f, "toml"),
+            Self::Csv => write!(f, "csv"),
+            Self::Tsv => write!(f, "tsv"),
+            Self::Xml => write!(f, "xml"),
+            Self::Msgpack => write!(f, "msgpack"),
+            Self::Cbor => write!(f, "cbor"),
+            Self::Bson => write!(f, "bson"),
+        }
+    }
+}
+
+/// The different ways in which the content of binary values (from MessagePack and CBOR input) may be read
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum BinaryStyle {
+    /// base64 encoded strings, with padding
+    Base64,
+    /// arrays of byte values
+    Array,
+}
+
+/// The key of the single member object which wraps a binary value, so that it can be told apart from a string or
+/// an array and written back out as binary
+pub const BINARY_KEY: &str = "@binary";
+
+/// The maximum nesting depth of the values within binary formats, beyond which input is rejected rather than risking
+/// the stack
+pub const MAX_DEPTH: usize = 512;
+
+/// The key holding the tag number of a tagged value (a MessagePack extension, or a CBOR tag)
+pub const TAG_KEY: &str = "@tag";
+
+/// The key holding the content of a tagged value
+pub const VALUE_KEY: &str = "@value";
+
+/// Options which control how documents are read
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+    /// The mapping used for XML input
+    pub xml: XmlOptions,
+    /// How binary values are read
+    pub binary: BinaryStyle,
+    /// The flavour of Extended JSON that BSON is read as
+    pub extended_json: ExtendedJson,
+}
+
+/// Default implementation uses the most common conventions
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            xml: XmlOptions::default(),
+            binary: BinaryStyle::Base64,
+            extended_json: ExtendedJson::Relaxed,
+        }
+    }
+}
+
+/// A half-open range of byte offsets within the input
+pub type ByteRange = (usize, usize);
+
+/// A value read from a document, along with the range of the input it was read from
+#[derive(Debug, Clone)]
+pub struct Node {
+    /// The value itself
+    pub value: NodeValue,
+    /// Where the value was found
+    pub range: ByteRange,
+}
+
+/// The different types of value, which map directly onto JSON values
+#[derive(Debug, Clone)]
+pub enum NodeValue {
+    /// A mapping or table
+    Object(Vec<Member>),
+    /// A sequence or array
+    Array(Vec<Node>),
+    /// A string, held in its raw (quoted and escaped) JSON form
+    String(String),
+    /// An integer
+    Integer(i64),
+    /// A float
+    Float(f64),
+    /// A boolean
+    Boolean(bool),
+    /// A null
+    Null,
+}
+
+/// A single member of an object
+#[derive(Debug, Clone)]
+pub struct Member {
+    /// The key, held in its raw (quoted and escaped) JSON form
+    pub key: String,
+    /// Where the key was found
+    pub range: ByteRange,
+    /// The value
+    pub value: Node,
+}
+
+impl Node {
+    /// Convert the tree into the equivalent [JsonValue]
+    pub fn into_json(self) -> JsonValue<'static> {
+        match self.value {
+            NodeValue::Object(members) => JsonValue::Object(
+                members
+                    .into_iter()
+                    .map(|member| (member.key, member.value.into_json()))
+                    .collect(),
+            ),
+            NodeValue::Array(values) => {
+                JsonValue::Array(values.into_iter().map(Node::into_json).collect())
+            }
+            NodeValue::String(raw) => JsonValue::String(Cow::Owned(raw)),
+            NodeValue::Integer(value) => JsonValue::Integer(value),
+            NodeValue::Float(value) => JsonValue::Float(value),
+            NodeValue::Boolean(value) => JsonValue::Boolean(value),
+            NodeValue::Null => JsonValue::Null,
+        }
+    }
+
+    /// Shift all the ranges within the tree along by a fixed amount
+    fn shift(&mut self, by: usize) {
+        self.range = (self.range.0 + by, self.range.1 + by);
+        match &mut self.value {
+            NodeValue::Object(members) => members.iter_mut().for_each(|member| {
+                member.range = (member.range.0 + by, member.range.1 + by);
+                member.value.shift(by)
+            }),
+            NodeValue::Array(values) => values.iter_mut().for_each(|value| value.shift(by)),
+            _ => (),
+        }
+    }
+}
+
+impl NodeValue {
+    /// Build the value for a binary blob, which is wrapped up in an object with a single [BINARY_KEY] member
+    fn binary(bytes: &[u8], style: BinaryStyle, range: ByteRange) -> NodeValue {
+        let content = match style {
+            BinaryStyle::Base64 => NodeValue::String(quote_json_string(&BASE64.encode(bytes))),
+            BinaryStyle::Array => NodeValue::Array(
+                bytes
+                    .iter()
+                    .enumerate()
+                    .map(|(index, byte)| Node {
+                        value: NodeValue::Integer(i64::from(*byte)),
+                        range: (
+                            range.1 - bytes.len() + index,
+                            range.1 - bytes.len() + index + 1,
+                        ),
+                    })
+                    .collect(),
+            ),
+        };
+        NodeValue::Object(vec![Member {
+            key: quote_json_string(BINARY_KEY),
+            range,
+            value: Node {
+                value: content,
+                range,
+            },
+        }])
+    }
+
+    /// Build the value for a tagged value, given its tag number and the range covering the tag itself
+    fn tagged(tag: i64, tag_range: ByteRange, value: Node) -> NodeValue {
+        NodeValue::Object(vec![
+            Member {
+                key: quote_json_string(TAG_KEY),
+                range: tag_range,
+                value: Node {
+                    value: NodeValue::Integer(tag),
+                    range: tag_range,
+                },
+            },
+            Member {
+                key: quote_json_string(VALUE_KEY),
+                range: value.range,
+                value,
+            },
+        ])
+    }
+
+    /// Build the value for a float. Infinities and NaNs have no JSON equivalent, so are kept as strings
+    fn float(value: f64) -> NodeValue {
+        match value.is_finite() {
+            true => NodeValue::Float(value),
+            false => NodeValue::String(quote_json_string(&value.to_string())),
+        }
+    }
+}
+
+impl Node {
+    /// Turn a node into an object key. Keys which aren't strings are replaced by their JSON text
+    fn into_key(self) -> String {
+        match self.value {
+            NodeValue::String(raw) => raw,
+            _ => quote_json_string(&compact_json(&self.into_json())),
+        }
+    }
+}
+
+/// Decode a binary value from its JSON representation, as produced when reading binary formats. This is an object
+/// with a single [BINARY_KEY] member, holding either a base64 string or an array of bytes
+pub fn decode_binary(value: &JsonValue) -> Option<Vec<u8>> {
+    let content = match value {
+        JsonValue::Object(pairs) => match pairs.as_slice() {
+            [(key, content)] if key == &quote_json_string(BINARY_KEY) => content,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    match content {
+        JsonValue::String(raw) => BASE64.decode(crate::escapes::unquote_json_string(raw)).ok(),
+        JsonValue::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                JsonValue::Integer(byte) => u8::try_from(*byte).ok(),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+/// Split a tagged value back up into its tag and content, if that's what an object represents
+pub fn tagged_parts<'a, 'b>(
+    pairs: &'a [(String, JsonValue<'b>)],
+) -> Option<(i64, &'a JsonValue<'b>)> {
+    match pairs {
+        [(tag_key, JsonValue::Integer(tag)), (value_key, value)]
+            if tag_key == &quote_json_string(TAG_KEY)
+                && value_key == &quote_json_string(VALUE_KEY) =>
+        {
+            Some((*tag, value))
+        }
+        _ => None,
+    }
+}
+
+/// An error raised whilst reading a document
+#[derive(Debug, Clone)]
+pub struct FormatError {
+    /// A description of the problem
+    pub message: String,
+    /// The byte offset at which the problem was found
+    pub offset: usize,
+}
+
+/// Read every document within the input. Only YAML and the binary formats allow for more than one document, and an
+/// empty stream is read as a single null document (other than for CSV and TSV, where it's read as an empty array of
+/// records)
+pub fn parse_documents(format: DocumentFormat, buffer: &[u8]) -> Result<Vec<Node>, FormatError> {
+    parse_documents_with(format, buffer, &ReadOptions::default())
+}
+
+/// Read every document within the input, using a specific set of options. Binary formats may hold any number of
+/// values back to back, each of which is read as a separate document
+pub fn parse_documents_with(
+    format: DocumentFormat,
+    buffer: &[u8],
+    options: &ReadOptions,
+) -> Result<Vec<Node>, FormatError> {
+    let mut documents = match format {
+        DocumentFormat::Msgpack => msgpack::parse(buffer, options.binary)?,
+        DocumentFormat::Cbor => cbor::parse(buffer, options.binary)?,
+        DocumentFormat::Bson => bson::parse(buffer, options.extended_json)?,
+        format => parse_text_documents(format, buffer, options)?,
+    };
+    if documents.is_empty() {
+        documents.push(Node {
+            value: NodeValue::Null,
+            range: (0, 0),
+        });
+    }
+    Ok(documents)
+}
+
+/// Read every document within a textual input, which must be UTF-8 encoded
+fn parse_text_documents(
+    format: DocumentFormat,
+    buffer: &[u8],
+    options: &ReadOptions,
+) -> Result<Vec<Node>, FormatError> {
+    let source = std::str::from_utf8(buffer).map_err(|err| FormatError {
+        message: String::from("input isn't valid UTF-8"),
+        offset: err.valid_up_to(),
+    })?;
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let skipped = buffer.len() - source.len();
+    let parsed = match format {
+        DocumentFormat::Yaml => yaml::parse(source),
+        DocumentFormat::Toml => toml::parse(source).map(|document| vec![document]),
+        DocumentFormat::Csv => csv::parse(source, false).map(|document| vec![document]),
+        DocumentFormat::Tsv => csv::parse(source, true).map(|document| vec![document]),
+        DocumentFormat::Xml => xml::parse(source, &options.xml).map(|document| vec![document]),
+        format => Err(FormatError {
+            message: format!("{} isn't a text format that's handled here", format),
+            offset: 0,
+        }),
+    };
+    let mut documents = parsed.map_err(|err| FormatError {
+        offset: err.offset + skipped,
+        ..err
+    })?;
+
+    // any byte order mark is stripped before parsing, so ranges need shifting back into line with the buffer
+    if skipped > 0 {
+        documents.iter_mut().for_each(|node| node.shift(skipped));
+    }
+    Ok(documents)
+}
+
+/// Read a single document from the input
+pub fn parse_document(format: DocumentFormat, buffer: &[u8]) -> Result<Node, FormatError> {
+    let mut documents = parse_documents(format, buffer)?;
+    if documents.len() > 1 {
+        return Err(FormatError {
+            message: format!("expected a single document, but found {}", documents.len()),
+            offset: documents[1].range.0,
+        });
+    }
+    Ok(documents.remove(0))
+}
@@ -0,0 +1,278 @@
+//! MessagePack reader and writer. Binary values are read as objects with a single `@binary` member, holding their
+//! content according to a [BinaryStyle], e.g. `{"@binary": "AQID"}`. Extension types become tagged values, with the
+//! content read as binary, e.g. `{"@tag": 1, "@value": {"@binary": "AQID"}}`. Map keys which aren't strings are
+//! replaced by their JSON text, and unsigned integers too large for an `i64` are read as floats. When writing, binary
+//! objects (holding either form of content) become `bin` values, and tagged values holding binary become extension
+//! types
+use super::{
+    decode_binary, tagged_parts, BinaryStyle, FormatError, Member, Node, NodeValue, MAX_DEPTH,
+};
+use crate::errors::{ChiselError, ChiselResult};
+use crate::escapes::{quote_json_string, unquote_json_string};
+use chisel_json::JsonValue;
+use rmp::decode::{read_marker, Bytes, RmpRead};
+use rmp::encode;
+use rmp::Marker;
+
+/// Parse a stream of MessagePack values, each of which becomes a separate document
+pub fn parse(buffer: &[u8], binary: BinaryStyle) -> Result<Vec<Node>, FormatError> {
+    let mut reader = Reader {
+        buffer,
+        bytes: Bytes::new(buffer),
+        binary,
+        depth: 0,
+    };
+    let mut documents = vec![];
+    while !reader.bytes.remaining_slice().is_empty() {
+        documents.push(reader.node()?);
+    }
+    Ok(documents)
+}
+
+/// Reads values from a buffer, keeping track of where each one came from
+struct Reader<'a> {
+    /// The whole of the input
+    buffer: &'a [u8],
+    /// The input still to be read
+    bytes: Bytes<'a>,
+    /// How binary values are read
+    binary: BinaryStyle,
+    /// The nesting depth of the value currently being read
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// The current offset within the input
+    fn position(&self) -> usize {
+        self.buffer.len() - self.bytes.remaining_slice().len()
+    }
+
+    /// The error raised when the input runs out part way through a value
+    fn truncated(&self) -> FormatError {
+        FormatError {
+            message: String::from("unexpected end of input"),
+            offset: self.position(),
+        }
+    }
+
+    /// Read a fixed size piece of data, mapping any failure onto a truncation error
+    fn read<T, E>(
+        &mut self,
+        read: impl FnOnce(&mut Bytes<'a>) -> Result<T, E>,
+    ) -> Result<T, FormatError> {
+        read(&mut self.bytes).map_err(|_| self.truncated())
+    }
+
+    /// Take a number of bytes from the input
+    fn take(&mut self, length: usize) -> Result<&'a [u8], FormatError> {
+        let remaining = self.bytes.remaining_slice();
+        if remaining.len() < length {
+            return Err(self.truncated());
+        }
+        self.bytes = Bytes::new(&remaining[length..]);
+        Ok(&remaining[..length])
+    }
+
+    /// Check that a collection length is plausible, given that every entry needs at least one byte
+    fn check_length(&self, length: usize) -> Result<usize, FormatError> {
+        match length <= self.bytes.remaining_slice().len() {
+            true => Ok(length),
+            false => Err(self.truncated()),
+        }
+    }
+
+    /// Read a single value, keeping track of how deeply nested it is
+    fn node(&mut self) -> Result<Node, FormatError> {
+        if self.depth == MAX_DEPTH {
+            return Err(FormatError {
+                message: format!("values are nested more than {} deep", MAX_DEPTH),
+                offset: self.position(),
+            });
+        }
+        self.depth += 1;
+        let node = self.value();
+        self.depth -= 1;
+        node
+    }
+
+    /// Read the value that starts at the current position
+    fn value(&mut self) -> Result<Node, FormatError> {
+        let start = self.position();
+        let marker = self.read(read_marker)?;
+        let value = match marker {
+            Marker::Null => NodeValue::Null,
+            Marker::True => NodeValue::Boolean(true),
+            Marker::False => NodeValue::Boolean(false),
+            Marker::FixPos(value) => NodeValue::Integer(i64::from(value)),
+            Marker::FixNeg(value) => NodeValue::Integer(i64::from(value)),
+            Marker::U8 => NodeValue::Integer(i64::from(self.read(|b| b.read_data_u8())?)),
+            Marker::U16 => NodeValue::Integer(i64::from(self.read(|b| b.read_data_u16())?)),
+            Marker::U32 => NodeValue::Integer(i64::from(self.read(|b| b.read_data_u32())?)),
+            Marker::U64 => {
+                let value = self.read(|b| b.read_data_u64())?;
+                match i64::try_from(value) {
+                    Ok(value) => NodeValue::Integer(value),
+                    Err(_) => NodeValue::Float(value as f64),
+                }
+            }
+            Marker::I8 => NodeValue::Integer(i64::from(self.read(|b| b.read_data_i8())?)),
+            Marker::I16 => NodeValue::Integer(i64::from(self.read(|b| b.read_data_i16())?)),
+            Marker::I32 => NodeValue::Integer(i64::from(self.read(|b| b.read_data_i32())?)),
+            Marker::I64 => NodeValue::Integer(self.read(|b| b.read_data_i64())?),
+            Marker::F32 => NodeValue::float(f64::from(self.read(|b| b.read_data_f32())?)),
+            Marker::F64 => NodeValue::float(self.read(|b| b.read_data_f64())?),
+            Marker::FixStr(_) | Marker::Str8 | Marker::Str16 | Marker::Str32 => {
+                let length = self.length(marker)?;
+                let bytes = self.take(length)?;
+                match std::str::from_utf8(bytes) {
+                    Ok(text) => NodeValue::String(quote_json_string(text)),
+                    Err(_) => {
+                        return Err(FormatError {
+                            message: String::from("string isn't valid UTF-8"),
+                            offset: start,
+                        })
+                    }
+                }
+            }
+            Marker::Bin8 | Marker::Bin16 | Marker::Bin32 => {
+                let length = self.length(marker)?;
+                let bytes = self.take(length)?;
+                NodeValue::binary(bytes, self.binary, (start, self.position()))
+            }
+            Marker::FixArray(_) | Marker::Array16 | Marker::Array32 => {
+                let length = self.length(marker)?;
+                let mut values = Vec::with_capacity(self.check_length(length)?);
+                for _ in 0..length {
+                    values.push(self.node()?);
+                }
+                NodeValue::Array(values)
+            }
+            Marker::FixMap(_) | Marker::Map16 | Marker::Map32 => {
+                let length = self.length(marker)?;
+                let mut members = Vec::with_capacity(self.check_length(length)?);
+                for _ in 0..length {
+                    let key = self.node()?;
+                    let range = key.range;
+                    members.push(Member {
+                        key: key.into_key(),
+                        range,
+                        value: self.node()?,
+                    });
+                }
+                NodeValue::Object(members)
+            }
+            Marker::FixExt1
+            | Marker::FixExt2
+            | Marker::FixExt4
+            | Marker::FixExt8
+            | Marker::FixExt16
+            | Marker::Ext8
+            | Marker::Ext16
+            | Marker::Ext32 => {
+                let length = self.length(marker)?;
+                let tag_start = self.position();
+                let tag = self.read(|b| b.read_data_i8())?;
+                let tag_range = (tag_start, self.position());
+                let bytes = self.take(length)?;
+                let range = (tag_range.1, self.position());
+                let content = Node {
+                    value: NodeValue::binary(bytes, self.binary, range),
+                    range,
+                };
+                NodeValue::tagged(i64::from(tag), tag_range, content)
+            }
+            Marker::Reserved => {
+                return Err(FormatError {
+                    message: String::from("reserved marker"),
+                    offset: start,
+                })
+            }
+        };
+        Ok(Node {
+            value,
+            range: (start, self.position()),
+        })
+    }
+
+    /// Read the length that follows a marker (or is embedded within it)
+    fn length(&mut self, marker: Marker) -> Result<usize, FormatError> {
+        let length = match marker {
+            Marker::FixStr(length) | Marker::FixArray(length) | Marker::FixMap(length) => {
+                u32::from(length)
+            }
+            Marker::FixExt1 => 1,
+            Marker::FixExt2 => 2,
+            Marker::FixExt4 => 4,
+            Marker::FixExt8 => 8,
+            Marker::FixExt16 => 16,
+            Marker::Str8 | Marker::Bin8 | Marker::Ext8 => {
+                u32::from(self.read(|b| b.read_data_u8())?)
+            }
+            Marker::Str16 | Marker::Bin16 | Marker::Ext16 | Marker::Array16 | Marker::Map16 => {
+                u32::from(self.read(|b| b.read_data_u16())?)
+            }
+            _ => self.read(|b| b.read_data_u32())?,
+        };
+        Ok(length as usize)
+    }
+}
+
+/// Encode a document as MessagePack, appending it to a buffer. Binary values are written as `bin` values, tagged
+/// values holding binary content are written as extension types, and everything else is written as its nearest
+/// equivalent
+pub fn encode(value: &JsonValue, buffer: &mut Vec<u8>) -> ChiselResult<()> {
+    match value {
+        JsonValue::Null => encode::write_nil(buffer).or(Err(ChiselError::OutputFailed))?,
+        JsonValue::Boolean(value) => {
+            encode::write_bool(buffer, *value).or(Err(ChiselError::OutputFailed))?
+        }
+        JsonValue::Integer(value) => {
+            encode::write_sint(buffer, *value).or(Err(ChiselError::OutputFailed))?;
+        }
+        JsonValue::Float(value) => {
+            encode::write_f64(buffer, *value).or(Err(ChiselError::OutputFailed))?
+        }
+        JsonValue::String(raw) => encode::write_str(buffer, &unquote_json_string(raw))
+            .or(Err(ChiselError::OutputFailed))?,
+        JsonValue::Array(values) => {
+            encode::write_array_len(buffer, length(values.len())?)
+                .or(Err(ChiselError::OutputFailed))?;
+            for value in values {
+                encode(value, buffer)?;
+            }
+        }
+        JsonValue::Object(pairs) => {
+            if let Some(data) = decode_binary(value) {
+                encode::write_bin(buffer, &data).or(Err(ChiselError::OutputFailed))?;
+                return Ok(());
+            }
+            let extension = tagged_parts(pairs).and_then(|(tag, content)| {
+                Some((i8::try_from(tag).ok()?, decode_binary(content)?))
+            });
+            if let Some((tag, data)) = extension {
+                encode::write_ext_meta(buffer, length(data.len())?, tag)
+                    .or(Err(ChiselError::OutputFailed))?;
+                buffer.extend_from_slice(&data);
+                return Ok(());
+            }
+            encode::write_map_len(buffer, length(pairs.len())?)
+                .or(Err(ChiselError::OutputFailed))?;
+            for (key, value) in pairs {
+                encode::write_str(buffer, &unquote_json_string(key))
+                    .or(Err(ChiselError::OutputFailed))?;
+                encode(value, buffer)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that a length can be encoded
+fn length(length: usize) -> ChiselResult<u32> {
+    u32::try_from(length).map_err(|_| {
+        ChiselError::NotRepresentable(format!(
+            "length of {} exceeds the MessagePack limit",
+            length
+        ))
+    })
+}
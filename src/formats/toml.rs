@@ -0,0 +1,132 @@
+//! TOML reader. Tables (including inline tables and arrays of tables) become objects, and date-times become
+//! strings holding their RFC 3339 representation, as there's no native JSON equivalent
+use super::{ByteRange, FormatError, Member, Node, NodeValue};
+use crate::escapes::quote_json_string;
+use toml_edit::{ImDocument, InlineTable, Item, Key, Table, Value};
+
+/// Parse a TOML document
+pub fn parse(source: &str) -> Result<Node, FormatError> {
+    let document = ImDocument::parse(source).map_err(|err| FormatError {
+        message: err.message().trim().replace('\n', "; "),
+        offset: err.span().map_or(0, |span| span.start),
+    })?;
+
+    // the root table has no header, so it's taken to start at its first key
+    let mut root = table_node(document.as_table(), (0, 0));
+    if let NodeValue::Object(members) = &root.value {
+        root.range = members_range(members, (0, 0));
+    }
+    Ok(root)
+}
+
+/// The range covered by something that may (or may not) have come from the input
+fn range_of(span: Option<std::ops::Range<usize>>, fallback: ByteRange) -> ByteRange {
+    span.map_or(fallback, |span| (span.start, span.end))
+}
+
+/// The range covering a list of members, used for tables that were never written out explicitly
+fn members_range(members: &[Member], fallback: ByteRange) -> ByteRange {
+    let start = members
+        .iter()
+        .map(|member| member.range.0.min(member.value.range.0))
+        .min();
+    let end = members.iter().map(|member| member.value.range.1).max();
+    match (start, end) {
+        (Some(start), Some(end)) => (start, end),
+        _ => fallback,
+    }
+}
+
+/// Create a member, given a key and its value
+fn member(key: &Key, value: Node) -> Member {
+    let range = range_of(key.span(), value.range);
+    Member {
+        key: quote_json_string(key.get()),
+        range,
+        value,
+    }
+}
+
+/// Convert a standard table (including the root table) into an object. Sub-tables are positioned after the keys
+/// which lead to them, so the overall range is widened to cover everything within the table
+fn table_node(table: &Table, fallback: ByteRange) -> Node {
+    let mut members = vec![];
+    for (name, item) in table.iter() {
+        let Some((key, _)) = table.get_key_value(name) else {
+            continue;
+        };
+        let Some(value) = item_node(item, range_of(key.span(), fallback)) else {
+            continue;
+        };
+        members.push(member(key, value));
+    }
+    let range = match table.span() {
+        Some(span) if !(table.is_implicit() || span.is_empty()) => {
+            let end = members
+                .iter()
+                .map(|member| member.value.range.1)
+                .fold(span.end, usize::max);
+            (span.start, end)
+        }
+        _ => members_range(&members, fallback),
+    };
+    Node {
+        value: NodeValue::Object(members),
+        range,
+    }
+}
+
+/// Convert an inline table into an object
+fn inline_table_node(table: &InlineTable, range: ByteRange) -> Node {
+    let mut members = vec![];
+    for (name, value) in table.iter() {
+        if let Some((key, _)) = table.get_key_value(name) {
+            members.push(member(key, value_node(value, range)));
+        }
+    }
+    Node {
+        value: NodeValue::Object(members),
+        range: range_of(table.span(), range),
+    }
+}
+
+/// Convert an item into a node, returning [None] for empty items
+fn item_node(item: &Item, fallback: ByteRange) -> Option<Node> {
+    match item {
+        Item::None => None,
+        Item::Value(value) => Some(value_node(value, fallback)),
+        Item::Table(table) => Some(table_node(table, fallback)),
+        Item::ArrayOfTables(tables) => {
+            let values: Vec<Node> = tables
+                .iter()
+                .map(|table| table_node(table, fallback))
+                .collect();
+            let range = match (values.first(), values.last()) {
+                (Some(first), Some(last)) => (first.range.0, last.range.1),
+                _ => fallback,
+            };
+            Some(Node {
+                value: NodeValue::Array(values),
+                range,
+            })
+        }
+    }
+}
+
+/// Convert a value into a node
+fn value_node(value: &Value, fallback: ByteRange) -> Node {
+    let range = range_of(value.span(), fallback);
+    let value = match value {
+        Value::String(s) => NodeValue::String(quote_json_string(s.value())),
+        Value::Integer(i) => NodeValue::Integer(*i.value()),
+        Value::Float(f) if f.value().is_finite() => NodeValue::Float(*f.value()),
+        Value::Float(f) => NodeValue::String(quote_json_string(f.display_repr().trim())),
+        Value::Boolean(b) => NodeValue::Boolean(*b.value()),
+        Value::Datetime(d) => NodeValue::String(quote_json_string(&d.value().to_string())),
+        Value::Array(array) => {
+            NodeValue::Array(array.iter().map(|value| value_node(value, range)).collect())
+        }
+        Value::InlineTable(table) => return inline_table_node(table, range),
+    };
+    Node { value, range }
+}
@@ -0,0 +1,296 @@
+//! XML reader, along with the options shared with the XML printer. The mapping between XML and JSON is as follows:
+//!
+//! - The document becomes an object with a single member, named after the root element
+//! - Attributes become members whose keys are the attribute name plus a prefix (`@` by default)
+//! - Namespace declarations are treated as attributes, so `xmlns:x` becomes `@xmlns:x`, and element and attribute
+//!   names keep any namespace prefix exactly as written
+//! - Child elements become members named after the element, with repeated elements being gathered into an array
+//! - An element holding nothing but text becomes a string, and a self-closing element with no attributes becomes a null
+//! - Otherwise, any text within an element is placed under a text key (`#text` by default)
+//!
+//! Outside of strict mode, text is trimmed, and anything without an equivalent in JSON (comments, processing
+//! instructions and the relative order of differently named elements) is dropped. In strict mode these are rejected
+//! instead, so that anything which is read can be written back out without loss
+use super::{ByteRange, FormatError, Member, Node, NodeValue};
+use crate::escapes::quote_json_string;
+use roxmltree::{Document, NodeType};
+
+/// The XML namespace, which is implicitly declared by every document
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Options controlling the mapping between XML and JSON
+#[derive(Debug, Clone)]
+pub struct XmlOptions {
+    /// The prefix added to attribute names
+    pub attribute_prefix: String,
+    /// The key used to hold text content
+    pub text_key: String,
+    /// Elements which are always read as arrays, even when they only appear once
+    pub array_elements: Vec<String>,
+    /// Whether anything that can't survive a round trip is rejected
+    pub strict: bool,
+}
+
+/// Default implementation uses the most common conventions
+impl Default for XmlOptions {
+    fn default() -> Self {
+        Self {
+            attribute_prefix: String::from("@"),
+            text_key: String::from("#text"),
+            array_elements: vec![],
+            strict: false,
+        }
+    }
+}
+
+/// Parse an XML document
+pub fn parse(source: &str, options: &XmlOptions) -> Result<Node, FormatError> {
+    let document = Document::parse(source).map_err(|err| {
+        let message = err.to_string();
+        let suffix = format!(" at {}", err.pos());
+        FormatError {
+            message: message
+                .strip_suffix(&suffix)
+                .unwrap_or(&message)
+                .to_string(),
+            offset: text_offset(source, err.pos().row, err.pos().col),
+        }
+    })?;
+
+    let reader = Reader { source, options };
+    if options.strict {
+        for node in document.root().children() {
+            reader.check_dropped(node)?;
+        }
+    }
+    let root = document.root_element();
+    let range = range_of(root.range());
+    let name = reader.element_name(root);
+    Ok(Node {
+        value: NodeValue::Object(vec![Member {
+            key: quote_json_string(name),
+            range: (range.0 + 1, range.0 + 1 + name.len()),
+            value: reader.element(root)?,
+        }]),
+        range,
+    })
+}
+
+/// Work out the byte offset of a (1-based) row and column, given in characters
+fn text_offset(source: &str, row: u32, col: u32) -> usize {
+    let line_start: usize = source
+        .split_inclusive('\n')
+        .take(row.saturating_sub(1) as usize)
+        .map(str::len)
+        .sum();
+    let line = &source[line_start.min(source.len())..];
+    line_start
+        + line
+            .char_indices()
+            .nth(col.saturating_sub(1) as usize)
+            .map_or(line.len(), |(offset, _)| offset)
+}
+
+/// Convert a [std::ops::Range] into a [ByteRange]
+fn range_of(range: std::ops::Range<usize>) -> ByteRange {
+    (range.start, range.end)
+}
+
+/// Builds a tree of nodes from a parsed XML document
+struct Reader<'a> {
+    /// The source text
+    source: &'a str,
+    /// The mapping options
+    options: &'a XmlOptions,
+}
+
+impl<'a> Reader<'a> {
+    /// The qualified name of an element, exactly as it was written
+    fn element_name(&self, element: roxmltree::Node) -> &'a str {
+        let tag = &self.source[element.range().start + 1..];
+        let end = tag
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .unwrap_or(tag.len());
+        &tag[..end]
+    }
+
+    /// In strict mode, reject any nodes that have no JSON equivalent
+    fn check_dropped(&self, node: roxmltree::Node) -> Result<(), FormatError> {
+        let what = match node.node_type() {
+            NodeType::Comment => "comments",
+            NodeType::PI => "processing instructions",
+            _ => return Ok(()),
+        };
+        Err(FormatError {
+            message: format!("{} can't be represented in strict mode", what),
+            offset: node.range().start,
+        })
+    }
+
+    /// The namespace declarations made on an element, as attribute members
+    fn namespace_members(&self, element: roxmltree::Node) -> Vec<Member> {
+        let inherited: Vec<(Option<&str>, &str)> = element
+            .parent_element()
+            .map(|parent| {
+                parent
+                    .namespaces()
+                    .map(|ns| (ns.name(), ns.uri()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let start_tag = &self.source[element.range()];
+        let start_tag = &start_tag[..start_tag.find('>').unwrap_or(start_tag.len())];
+
+        let mut members = vec![];
+        for namespace in element.namespaces() {
+            let declared = (namespace.name(), namespace.uri());
+            if inherited.contains(&declared) || namespace.uri() == XML_NAMESPACE {
+                continue;
+            }
+            let name = match namespace.name() {
+                Some(prefix) => format!("xmlns:{}", prefix),
+                None => String::from("xmlns"),
+            };
+
+            // declarations aren't given ranges, so look for them within the start tag
+            let start = element.range().start;
+            let key = start_tag
+                .find(&format!("{}=", name))
+                .or_else(|| start_tag.find(&format!("{} ", name)))
+                .map_or((start, start), |offset| {
+                    (start + offset, start + offset + name.len())
+                });
+            let value = start_tag[key.1 - start..]
+                .find(['"', '\''])
+                .map_or(key, |offset| {
+                    let from = key.1 + offset + 1;
+                    (from, from + namespace.uri().len())
+                });
+            members.push(Member {
+                key: quote_json_string(&format!("{}{}", self.options.attribute_prefix, name)),
+                range: key,
+                value: Node {
+                    value: NodeValue::String(quote_json_string(namespace.uri())),
+                    range: value,
+                },
+            });
+        }
+        members
+    }
+
+    /// Build the node for an element
+    fn element(&self, element: roxmltree::Node) -> Result<Node, FormatError> {
+        let range = range_of(element.range());
+        let mut members = self.namespace_members(element);
+        for attribute in element.attributes() {
+            let name = &self.source[attribute.range_qname()];
+            members.push(Member {
+                key: quote_json_string(&format!("{}{}", self.options.attribute_prefix, name)),
+                range: range_of(attribute.range_qname()),
+                value: Node {
+                    value: NodeValue::String(quote_json_string(attribute.value())),
+                    range: range_of(attribute.range_value()),
+                },
+            });
+        }
+
+        // gather up child elements by name, along with any text
+        let mut groups: Vec<(&str, ByteRange, Vec<Node>)> = vec![];
+        let mut text = String::new();
+        let mut text_range: Option<ByteRange> = None;
+        let mut has_elements = false;
+        for child in element.children() {
+            match child.node_type() {
+                NodeType::Element => {
+                    has_elements = true;
+                    let name = self.element_name(child);
+                    let start = child.range().start;
+                    let node = self.element(child)?;
+                    match groups.iter().position(|(existing, ..)| *existing == name) {
+                        Some(index) if self.options.strict && index != groups.len() - 1 => {
+                            return Err(FormatError {
+                                message: format!(
+                                    "\"{}\" elements are interleaved with other elements, which can't be represented in strict mode",
+                                    name
+                                ),
+                                offset: start,
+                            })
+                        }
+                        Some(index) => groups[index].2.push(node),
+                        None => groups.push((name, (start + 1, start + 1 + name.len()), vec![node])),
+                    }
+                }
+                NodeType::Text => {
+                    let content = child.text().unwrap_or_default();
+                    let content = match self.options.strict {
+                        true => content,
+                        false => content.trim(),
+                    };
+                    if content.is_empty() {
+                        continue;
+                    }
+                    if !text.is_empty() && !self.options.strict {
+                        text.push(' ');
+                    }
+                    text.push_str(content);
+                    let child_range = range_of(child.range());
+                    text_range = Some(match text_range {
+                        Some((start, _)) => (start, child_range.1),
+                        None => child_range,
+                    });
+                }
+                _ if self.options.strict => self.check_dropped(child)?,
+                _ => (),
+            }
+        }
+
+        if self.options.strict && has_elements && !text.trim().is_empty() {
+            return Err(FormatError {
+                message: String::from("mixed content can't be represented in strict mode"),
+                offset: text_range.map_or(range.0, |range| range.0),
+            });
+        }
+        if has_elements && text.trim().is_empty() {
+            text.clear();
+        }
+
+        // elements with nothing but text are collapsed into strings (or nulls, if self-closing)
+        if members.is_empty() && groups.is_empty() {
+            let value = match self.source[..range.1].ends_with("/>") {
+                true => NodeValue::Null,
+                false => NodeValue::String(quote_json_string(&text)),
+            };
+            return Ok(Node { value, range });
+        }
+
+        for (name, key_range, mut nodes) in groups {
+            let array = nodes.len() > 1 || self.options.array_elements.iter().any(|n| n == name);
+            let value = match array {
+                true => Node {
+                    range: (nodes[0].range.0, nodes[nodes.len() - 1].range.1),
+                    value: NodeValue::Array(nodes),
+                },
+                false => nodes.remove(0),
+            };
+            members.push(Member {
+                key: quote_json_string(name),
+                range: key_range,
+                value,
+            });
+        }
+        if let Some(text_range) = text_range.filter(|_| !text.is_empty()) {
+            members.push(Member {
+                key: quote_json_string(&self.options.text_key),
+                range: text_range,
+                value: Node {
+                    value: NodeValue::String(quote_json_string(&text)),
+                    range: text_range,
+                },
+            });
+        }
+        Ok(Node {
+            value: NodeValue::Object(members),
+            range,
+        })
+    }
+}
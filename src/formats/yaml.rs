@@ -0,0 +1,353 @@
+//! YAML reader. Plain scalars are resolved according to the YAML 1.2 core schema, with infinities and NaNs being
+//! kept as strings as they have no JSON equivalent. Aliases are expanded in place (keeping the ranges of the anchored
+//! node), and merge keys are applied, with explicit keys taking precedence over merged ones
+use super::{FormatError, Member, Node, NodeValue};
+use crate::escapes::quote_json_string;
+use std::collections::{HashMap, HashSet};
+use yaml_rust2::parser::{Event, Parser, Tag};
+use yaml_rust2::scanner::{Marker, TScalarStyle};
+
+/// The handle used by the standard tags, such as `!!str`
+const CORE_HANDLE: &str = "tag:yaml.org,2002:";
+
+/// The key used to merge the contents of other mappings into a mapping
+const MERGE_KEY: &str = "<<";
+
+/// Parse a YAML stream, which may contain any number of documents
+pub fn parse(source: &str) -> Result<Vec<Node>, FormatError> {
+    // markers are in terms of characters, so precompute the byte offset of each one
+    let offsets: Vec<usize> = source
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(source.len()))
+        .collect();
+    let to_offset = |marker: &Marker| offsets.get(marker.index()).copied().unwrap_or(source.len());
+
+    let mut parser = Parser::new_from_str(source);
+    let mut events = vec![];
+    loop {
+        let (event, marker) = parser.next_token().map_err(|err| FormatError {
+            message: err.info().to_string(),
+            offset: to_offset(err.marker()),
+        })?;
+        if event == Event::StreamEnd {
+            break;
+        }
+        events.push((event, to_offset(&marker)));
+    }
+
+    let mut builder = Builder {
+        source,
+        events: events.into_iter(),
+        anchors: HashMap::new(),
+        last_end: 0,
+    };
+    builder.documents()
+}
+
+/// One of the entries within a mapping, prior to any merges being applied
+enum Entry {
+    /// An explicit key and value
+    Explicit(Member),
+    /// A set of members merged in from elsewhere
+    Merged(Vec<Member>),
+}
+
+/// Builds a tree of nodes from a stream of parser events
+struct Builder<'a> {
+    /// The source text
+    source: &'a str,
+    /// The events, each along with the byte offset they were reported at
+    events: std::vec::IntoIter<(Event, usize)>,
+    /// Anchored nodes, ready to be substituted for any aliases
+    anchors: HashMap<usize, Node>,
+    /// The end of the last scalar, where any following empty scalars are placed
+    last_end: usize,
+}
+
+impl<'a> Builder<'a> {
+    /// Build a node for each document within the stream
+    fn documents(&mut self) -> Result<Vec<Node>, FormatError> {
+        let mut documents = vec![];
+        while let Some((event, _)) = self.events.next() {
+            if event == Event::DocumentStart {
+                let (event, offset) = self.next_event()?;
+                documents.push(self.node(event, offset)?);
+            }
+        }
+        Ok(documents)
+    }
+
+    /// Pull the next event, which must exist
+    fn next_event(&mut self) -> Result<(Event, usize), FormatError> {
+        self.events.next().ok_or_else(|| FormatError {
+            message: String::from("unexpected end of input"),
+            offset: self.source.len(),
+        })
+    }
+
+    /// Build the node that starts with a given event
+    fn node(&mut self, event: Event, offset: usize) -> Result<Node, FormatError> {
+        let (node, anchor) = match event {
+            Event::Scalar(value, style, anchor, tag) => {
+                (self.scalar(&value, style, tag.as_ref(), offset), anchor)
+            }
+            Event::SequenceStart(anchor, _) => (self.sequence(offset)?, anchor),
+            Event::MappingStart(anchor, _) => (self.mapping(offset)?, anchor),
+            Event::Alias(id) => {
+                let node = self.anchors.get(&id).cloned().ok_or_else(|| FormatError {
+                    message: String::from("alias refers to an unknown anchor"),
+                    offset,
+                })?;
+                (node, 0)
+            }
+            _ => {
+                return Err(FormatError {
+                    message: String::from("unexpected event"),
+                    offset,
+                })
+            }
+        };
+        if anchor > 0 {
+            self.anchors.insert(anchor, node.clone());
+        }
+        Ok(node)
+    }
+
+    /// Build a scalar node, resolving its type
+    fn scalar(
+        &mut self,
+        value: &str,
+        style: TScalarStyle,
+        tag: Option<&Tag>,
+        offset: usize,
+    ) -> Node {
+        // empty scalars are reported at the start of the following token, so place them after the previous one
+        let range = if value.is_empty() && style == TScalarStyle::Plain {
+            (self.last_end, self.last_end)
+        } else {
+            (offset, self.scalar_end(value, style, offset))
+        };
+        self.last_end = range.1;
+        Node {
+            value: resolve(value, style, tag),
+            range,
+        }
+    }
+
+    /// Work out where a scalar ends within the source. Quoted scalars are scanned through to their closing quote,
+    /// and for everything else the words of the value are located in turn, as folding and indentation mean that the
+    /// value doesn't necessarily appear verbatim
+    fn scalar_end(&self, value: &str, style: TScalarStyle, offset: usize) -> usize {
+        let bytes = self.source.as_bytes();
+        let mut end = offset;
+        match style {
+            TScalarStyle::DoubleQuoted | TScalarStyle::SingleQuoted => {
+                let quote = bytes.get(offset).copied().unwrap_or(b'"');
+                end += 1;
+                while end < bytes.len() {
+                    match bytes[end] {
+                        b'\\' if quote == b'"' => end += 2,
+                        b'\'' if quote == b'\'' && bytes.get(end + 1) == Some(&b'\'') => end += 2,
+                        b if b == quote => return end + 1,
+                        _ => end += 1,
+                    }
+                }
+            }
+            _ => {
+                for word in value.split_whitespace() {
+                    match self.source[end..].find(word) {
+                        Some(position) => end += position + word.len(),
+                        None => break,
+                    }
+                }
+            }
+        }
+        end.min(bytes.len())
+    }
+
+    /// Work out where a collection ends, given where its end event was reported. Flow collections end at their
+    /// closing bracket, and block collections end with their last value
+    fn collection_end(&self, reported: usize, close: u8, last: usize) -> usize {
+        if self.source.as_bytes().get(reported) == Some(&close) {
+            reported + 1
+        } else {
+            last
+        }
+    }
+
+    /// Build a sequence node, following a sequence start event
+    fn sequence(&mut self, offset: usize) -> Result<Node, FormatError> {
+        let mut values = vec![];
+        let end = loop {
+            let (event, position) = self.next_event()?;
+            if event == Event::SequenceEnd {
+                break position;
+            }
+            values.push(self.node(event, position)?);
+        };
+        let last = values.iter().map(|value| value.range.1).max();
+        let end = self.collection_end(end, b']', last.unwrap_or(offset + 1));
+        self.last_end = end;
+        Ok(Node {
+            value: NodeValue::Array(values),
+            range: (offset, end),
+        })
+    }
+
+    /// Build a mapping node, following a mapping start event
+    fn mapping(&mut self, offset: usize) -> Result<Node, FormatError> {
+        let mut entries = vec![];
+        let end = loop {
+            let (event, position) = self.next_event()?;
+            let key = match &event {
+                Event::MappingEnd => break position,
+                Event::Scalar(value, ..) => Some(value.clone()),
+                Event::Alias(_) => None,
+                _ => {
+                    return Err(FormatError {
+                        message: String::from("complex mapping keys aren't supported"),
+                        offset: position,
+                    })
+                }
+            };
+            let merge = matches!(&event, Event::Scalar(value, TScalarStyle::Plain, _, None) if value == MERGE_KEY);
+            let key_node = self.node(event, position)?;
+            let (event, position) = self.next_event()?;
+            let value = self.node(event, position)?;
+            if merge {
+                entries.push(Entry::Merged(merged_members(value, position)?));
+                continue;
+            }
+
+            // scalar keys are always taken as written, so only aliased keys need to be checked
+            let key = match (key, key_node.value) {
+                (Some(key), _) => quote_json_string(&key),
+                (None, NodeValue::String(raw)) => raw,
+                (None, _) => {
+                    return Err(FormatError {
+                        message: String::from("aliased mapping keys must be strings"),
+                        offset: key_node.range.0,
+                    })
+                }
+            };
+            entries.push(Entry::Explicit(Member {
+                key,
+                range: key_node.range,
+                value,
+            }));
+        };
+
+        // explicit keys always win over merged ones, and earlier merges win over later ones
+        let explicit: HashSet<String> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Explicit(member) => Some(member.key.clone()),
+                Entry::Merged(_) => None,
+            })
+            .collect();
+        let mut seen = HashSet::new();
+        let mut members = vec![];
+        for entry in entries {
+            match entry {
+                Entry::Explicit(member) => members.push(member),
+                Entry::Merged(merged) => members.extend(merged.into_iter().filter(|member| {
+                    !explicit.contains(&member.key) && seen.insert(member.key.clone())
+                })),
+            }
+        }
+
+        // block mappings are reported from their first value indicator, so start them from the first key instead
+        let start = if self.source.as_bytes().get(offset) == Some(&b'{') {
+            offset
+        } else {
+            members
+                .iter()
+                .map(|member| member.range.0)
+                .min()
+                .unwrap_or(offset)
+                .min(offset)
+        };
+        let last = members.iter().map(|member| member.value.range.1).max();
+        let end = self.collection_end(end, b'}', last.unwrap_or(offset + 1));
+        self.last_end = end;
+        Ok(Node {
+            value: NodeValue::Object(members),
+            range: (start, end),
+        })
+    }
+}
+
+/// The members to be merged into a mapping, given the value of a merge key
+fn merged_members(value: Node, offset: usize) -> Result<Vec<Member>, FormatError> {
+    let invalid = || FormatError {
+        message: String::from("merge keys must refer to a mapping, or a sequence of mappings"),
+        offset,
+    };
+    match value.value {
+        NodeValue::Object(members) => Ok(members),
+        NodeValue::Array(values) => {
+            let mut members = vec![];
+            for value in values {
+                match value.value {
+                    NodeValue::Object(merged) => members.extend(merged),
+                    _ => return Err(invalid()),
+                }
+            }
+            Ok(members)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Resolve the type of a scalar, based on its style and any tag that it has
+fn resolve(value: &str, style: TScalarStyle, tag: Option<&Tag>) -> NodeValue {
+    let string = || NodeValue::String(quote_json_string(value));
+    let core = tag
+        .filter(|tag| tag.handle == CORE_HANDLE)
+        .map(|tag| tag.suffix.as_str());
+    match (core, tag) {
+        (Some("str"), _) => return string(),
+        (Some(_), _) => (),
+        (None, Some(tag)) if tag.handle == "!" && tag.suffix.is_empty() => return string(),
+        _ if style != TScalarStyle::Plain => return string(),
+        _ => (),
+    }
+
+    match value {
+        "" | "~" | "null" | "Null" | "NULL" => return NodeValue::Null,
+        "true" | "True" | "TRUE" => return NodeValue::Boolean(true),
+        "false" | "False" | "FALSE" => return NodeValue::Boolean(false),
+        _ => (),
+    }
+    let unsigned = value.trim_start_matches(['+', '-']);
+    if let Some(octal) = value.strip_prefix("0o") {
+        if let Ok(value) = i64::from_str_radix(octal, 8) {
+            return NodeValue::Integer(value);
+        }
+    }
+    if let Some(hex) = value.strip_prefix("0x") {
+        if let Ok(value) = i64::from_str_radix(hex, 16) {
+            return NodeValue::Integer(value);
+        }
+    }
+    if unsigned.len() + 1 >= value.len() && !unsigned.is_empty() {
+        if unsigned.bytes().all(|b| b.is_ascii_digit()) {
+            return match value.parse::<i64>() {
+                Ok(value) => NodeValue::Integer(value),
+                Err(_) => NodeValue::Float(value.parse().unwrap_or_default()),
+            };
+        }
+        let numeric = unsigned.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+            && unsigned.bytes().any(|b| b.is_ascii_digit())
+            && unsigned
+                .bytes()
+                .all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E' | b'+' | b'-'));
+        if numeric {
+            if let Ok(value) = value.parse::<f64>() {
+                return NodeValue::Float(value);
+            }
+        }
+    }
+    string()
+}
@@ -0,0 +1,671 @@
+//! Evaluation of parsed JSONPath queries against a [JsonValue] DOM
+use super::{
+    Comparable, ComparisonOp, Function, FunctionArg, FunctionExpr, Literal, LogicalExpr, Node,
+    PathElement, Query, Segment, Selector,
+};
+use crate::dom::json_equal;
+use crate::escapes::{quote_json_string, unquote_json_string};
+use chisel_json::JsonValue;
+use regex::Regex;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// The result of evaluating a comparable, or function argument of value type. `Nothing` represents the absence
+/// of a value, e.g. when a singular query selects no nodes at all
+enum Value<'a> {
+    Nothing,
+    Borrowed(&'a JsonValue<'a>),
+    Owned(JsonValue<'static>),
+}
+
+impl<'a> Value<'a> {
+    fn get(&self) -> Option<&JsonValue<'_>> {
+        match self {
+            Value::Nothing => None,
+            Value::Borrowed(value) => Some(value),
+            Value::Owned(value) => Some(value),
+        }
+    }
+}
+
+/// Evaluation state for a given document
+pub(super) struct Evaluator<'a> {
+    /// The document root, used by absolute queries within filters
+    root: &'a JsonValue<'a>,
+    /// Compiled regular expressions, keyed on the I-Regexp and whether or not they're anchored
+    regexes: RefCell<HashMap<(String, bool), Option<Regex>>>,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(root: &'a JsonValue<'a>) -> Self {
+        Evaluator {
+            root,
+            regexes: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Evaluate an absolute query against the document root
+    pub fn select(&self, query: &Query) -> Vec<Node<'a>> {
+        self.evaluate(query, self.root)
+    }
+
+    /// Evaluate a query, with relative queries starting from the current node
+    fn evaluate(&self, query: &Query, current: &'a JsonValue<'a>) -> Vec<Node<'a>> {
+        let start = if query.relative { current } else { self.root };
+        let mut nodes = vec![Node {
+            path: vec![],
+            value: start,
+        }];
+        for segment in &query.segments {
+            let mut selected = vec![];
+            for node in &nodes {
+                match segment {
+                    Segment::Child(selectors) => {
+                        self.apply_selectors(selectors, node, &mut selected)
+                    }
+                    Segment::Descendant(selectors) => {
+                        self.apply_descendant(selectors, node, &mut selected)
+                    }
+                }
+            }
+            nodes = selected;
+        }
+        nodes
+    }
+
+    /// Apply a list of selectors to a node and to all of its descendants, in document order
+    fn apply_descendant(
+        &self,
+        selectors: &[Selector],
+        node: &Node<'a>,
+        output: &mut Vec<Node<'a>>,
+    ) {
+        self.apply_selectors(selectors, node, output);
+        for child in children(node) {
+            self.apply_descendant(selectors, &child, output);
+        }
+    }
+
+    /// Apply a list of selectors to a single node, appending the results to the output
+    fn apply_selectors(&self, selectors: &[Selector], node: &Node<'a>, output: &mut Vec<Node<'a>>) {
+        for selector in selectors {
+            match (selector, node.value) {
+                (Selector::Name(name), JsonValue::Object(pairs)) => {
+                    if let Some((_, value)) =
+                        pairs.iter().find(|(k, _)| unquote_json_string(k) == *name)
+                    {
+                        output.push(child(node, PathElement::Name(name.clone()), value));
+                    }
+                }
+                (Selector::Wildcard, _) => output.extend(children(node)),
+                (Selector::Index(index), JsonValue::Array(values)) => {
+                    if let Some(i) = normalize_index(*index, values.len()) {
+                        output.push(child(node, PathElement::Index(i), &values[i]));
+                    }
+                }
+                (Selector::Slice { start, end, step }, JsonValue::Array(values)) => {
+                    for i in slice_indices(*start, *end, *step, values.len()) {
+                        output.push(child(node, PathElement::Index(i), &values[i]));
+                    }
+                }
+                (Selector::Filter(expr), JsonValue::Object(_) | JsonValue::Array(_)) => {
+                    for candidate in children(node) {
+                        if self.test(expr, candidate.value) {
+                            output.push(candidate);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Evaluate a logical expression with a given current node
+    fn test(&self, expr: &LogicalExpr, current: &'a JsonValue<'a>) -> bool {
+        match expr {
+            LogicalExpr::Or(terms) => terms.iter().any(|t| self.test(t, current)),
+            LogicalExpr::And(terms) => terms.iter().all(|t| self.test(t, current)),
+            LogicalExpr::Not(inner) => !self.test(inner, current),
+            LogicalExpr::Exists(query) => !self.evaluate(query, current).is_empty(),
+            LogicalExpr::Function(f) => self.call_logical(f, current),
+            LogicalExpr::Comparison(lhs, op, rhs) => {
+                let lhs = self.comparable(lhs, current);
+                let rhs = self.comparable(rhs, current);
+                compare(lhs.get(), *op, rhs.get())
+            }
+        }
+    }
+
+    /// Reduce a comparable down to a single value
+    fn comparable(&self, comparable: &Comparable, current: &'a JsonValue<'a>) -> Value<'a> {
+        match comparable {
+            Comparable::Literal(literal) => Value::Owned(literal_value(literal)),
+            Comparable::Query(query) => singular_value(self.evaluate(query, current)),
+            Comparable::Function(f) => self.call_value(f, current),
+        }
+    }
+
+    /// Evaluate a function argument which is declared as a value type
+    fn value_arg(&self, arg: &FunctionArg, current: &'a JsonValue<'a>) -> Value<'a> {
+        match arg {
+            FunctionArg::Literal(literal) => Value::Owned(literal_value(literal)),
+            FunctionArg::Query(query) => singular_value(self.evaluate(query, current)),
+            FunctionArg::Function(f) => self.call_value(f, current),
+            FunctionArg::Logical(_) => Value::Nothing,
+        }
+    }
+
+    /// Evaluate a function argument which is declared as a nodes type
+    fn nodes_arg(&self, arg: &FunctionArg, current: &'a JsonValue<'a>) -> Vec<Node<'a>> {
+        match arg {
+            FunctionArg::Query(query) => self.evaluate(query, current),
+            _ => vec![],
+        }
+    }
+
+    /// Invoke a function which returns a value
+    fn call_value(&self, f: &FunctionExpr, current: &'a JsonValue<'a>) -> Value<'a> {
+        match f.function {
+            Function::Length => {
+                let arg = self.value_arg(&f.args[0], current);
+                let length = match arg.get() {
+                    Some(JsonValue::String(s)) => unquote_json_string(s).chars().count(),
+                    Some(JsonValue::Array(values)) => values.len(),
+                    Some(JsonValue::Object(pairs)) => pairs.len(),
+                    _ => return Value::Nothing,
+                };
+                Value::Owned(JsonValue::Integer(length as i64))
+            }
+            Function::Count => {
+                let nodes = self.nodes_arg(&f.args[0], current);
+                Value::Owned(JsonValue::Integer(nodes.len() as i64))
+            }
+            Function::Value => singular_value(self.nodes_arg(&f.args[0], current)),
+            Function::Match | Function::Search => Value::Nothing,
+        }
+    }
+
+    /// Invoke a function which returns a logical result
+    fn call_logical(&self, f: &FunctionExpr, current: &'a JsonValue<'a>) -> bool {
+        match f.function {
+            Function::Match | Function::Search => {
+                let subject = self.value_arg(&f.args[0], current);
+                let pattern = self.value_arg(&f.args[1], current);
+                match (subject.get(), pattern.get()) {
+                    (Some(JsonValue::String(subject)), Some(JsonValue::String(pattern))) => self
+                        .regex_matches(
+                            &unquote_json_string(pattern),
+                            &unquote_json_string(subject),
+                            f.function == Function::Match,
+                        ),
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Test a string against an I-Regexp, compiling and caching the expression as needed. Invalid expressions
+    /// never match anything
+    fn regex_matches(&self, pattern: &str, subject: &str, anchored: bool) -> bool {
+        let mut cache = self.regexes.borrow_mut();
+        let regex = cache
+            .entry((pattern.to_string(), anchored))
+            .or_insert_with(|| {
+                let translated = translate_iregexp(pattern);
+                let source = if anchored {
+                    format!("^(?:{})$", translated)
+                } else {
+                    translated
+                };
+                Regex::new(&source).ok()
+            });
+        match regex {
+            Some(regex) => regex.is_match(subject),
+            None => false,
+        }
+    }
+}
+
+/// Create a child node
+fn child<'a>(parent: &Node<'a>, element: PathElement, value: &'a JsonValue<'a>) -> Node<'a> {
+    let mut path = parent.path.clone();
+    path.push(element);
+    Node { path, value }
+}
+
+/// Gather up all the immediate children of a node
+fn children<'a>(node: &Node<'a>) -> Vec<Node<'a>> {
+    match node.value {
+        JsonValue::Object(pairs) => pairs
+            .iter()
+            .map(|(k, v)| child(node, PathElement::Name(unquote_json_string(k)), v))
+            .collect(),
+        JsonValue::Array(values) => values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| child(node, PathElement::Index(i), v))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Convert the result of a singular query into a value
+fn singular_value(mut nodes: Vec<Node>) -> Value {
+    if nodes.len() == 1 {
+        Value::Borrowed(nodes.remove(0).value)
+    } else {
+        Value::Nothing
+    }
+}
+
+/// Convert a literal into a DOM value, with strings held in the same raw form as the DOM parser uses
+fn literal_value(literal: &Literal) -> JsonValue<'static> {
+    match literal {
+        Literal::String(s) => JsonValue::String(Cow::Owned(quote_json_string(s))),
+        Literal::Integer(i) => JsonValue::Integer(*i),
+        Literal::Float(f) => JsonValue::Float(*f),
+        Literal::Boolean(b) => JsonValue::Boolean(*b),
+        Literal::Null => JsonValue::Null,
+    }
+}
+
+/// Turn a (possibly negative) index into an offset within an array of a given length
+fn normalize_index(index: i64, len: usize) -> Option<usize> {
+    let len = len as i64;
+    let normalized = if index < 0 { len + index } else { index };
+    if normalized >= 0 && normalized < len {
+        Some(normalized as usize)
+    } else {
+        None
+    }
+}
+
+/// Calculate the indices selected by a slice, as per section 2.3.4.2.2 of the RFC
+fn slice_indices(
+    start: Option<i64>,
+    end: Option<i64>,
+    step: Option<i64>,
+    len: usize,
+) -> Vec<usize> {
+    let len = len as i64;
+    let step = step.unwrap_or(1);
+    if step == 0 {
+        return vec![];
+    }
+    let normalize = |i: i64| if i >= 0 { i } else { len + i };
+    let mut indices = vec![];
+    if step > 0 {
+        let lower = normalize(start.unwrap_or(0)).clamp(0, len);
+        let upper = normalize(end.unwrap_or(len)).clamp(0, len);
+        let mut i = lower;
+        while i < upper {
+            indices.push(i as usize);
+            i += step;
+        }
+    } else {
+        let upper = normalize(start.unwrap_or(len - 1)).clamp(-1, len - 1);
+        let lower = match end {
+            Some(end) => normalize(end).clamp(-1, len - 1),
+            None => -1,
+        };
+        let mut i = upper;
+        while lower < i {
+            indices.push(i as usize);
+            i += step;
+        }
+    }
+    indices
+}
+
+/// Compare two (possibly absent) values, as per section 2.3.5.2.2 of the RFC
+fn compare(lhs: Option<&JsonValue>, op: ComparisonOp, rhs: Option<&JsonValue>) -> bool {
+    match op {
+        ComparisonOp::Eq => values_equal(lhs, rhs),
+        ComparisonOp::Ne => !values_equal(lhs, rhs),
+        ComparisonOp::Lt => values_less(lhs, rhs),
+        ComparisonOp::Le => values_less(lhs, rhs) || values_equal(lhs, rhs),
+        ComparisonOp::Gt => values_less(rhs, lhs),
+        ComparisonOp::Ge => values_less(rhs, lhs) || values_equal(lhs, rhs),
+    }
+}
+
+fn values_equal(lhs: Option<&JsonValue>, rhs: Option<&JsonValue>) -> bool {
+    match (lhs, rhs) {
+        (None, None) => true,
+        (Some(l), Some(r)) => json_equal(l, r),
+        _ => false,
+    }
+}
+
+/// Only numbers and strings can be ordered, everything else compares false
+fn values_less(lhs: Option<&JsonValue>, rhs: Option<&JsonValue>) -> bool {
+    match (lhs, rhs) {
+        (Some(JsonValue::String(l)), Some(JsonValue::String(r))) => {
+            unquote_json_string(l).cmp(&unquote_json_string(r)) == Ordering::Less
+        }
+        (Some(l), Some(r)) => match (as_number(l), as_number(r)) {
+            (Some(l), Some(r)) => l < r,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn as_number(value: &JsonValue) -> Option<f64> {
+    match value {
+        JsonValue::Integer(i) => Some(*i as f64),
+        JsonValue::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+/// Translate an I-Regexp (RFC 9485) into the syntax used by the regex crate. The two are largely compatible, with
+/// the exception of `.` which mustn't match either carriage returns or line feeds
+fn translate_iregexp(pattern: &str) -> String {
+    let mut translated = String::with_capacity(pattern.len());
+    let mut in_class = false;
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                translated.push(ch);
+                if let Some(escaped) = chars.next() {
+                    translated.push(escaped);
+                }
+            }
+            '[' if !in_class => {
+                in_class = true;
+                translated.push(ch);
+            }
+            ']' if in_class => {
+                in_class = false;
+                translated.push(ch);
+            }
+            '.' if !in_class => translated.push_str("[^\\n\\r]"),
+            _ => translated.push(ch),
+        }
+    }
+    translated
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom::parse_literal;
+    use crate::jsonpath::Query;
+    use chisel_json::JsonValue;
+
+    /// The example document from RFC 9535, section 1.5
+    const BOOKSTORE: &str = r#"{ "store": {
+        "book": [
+          { "category": "reference", "author": "Nigel Rees", "title": "Sayings of the Century", "price": 8.95 },
+          { "category": "fiction", "author": "Evelyn Waugh", "title": "Sword of Honour", "price": 12.99 },
+          { "category": "fiction", "author": "Herman Melville", "title": "Moby Dick", "isbn": "0-553-21311-3",
+            "price": 8.99 },
+          { "category": "fiction", "author": "J. R. R. Tolkien", "title": "The Lord of the Rings",
+            "isbn": "0-395-19395-8", "price": 22.99 }
+        ],
+        "bicycle": { "color": "red", "price": 399 }
+      } }"#;
+
+    /// Select from a document, returning the normalized paths of the selected nodes
+    fn paths(document: &str, query: &str) -> Vec<String> {
+        let root = parse_literal(document).unwrap();
+        let query = Query::parse(query).unwrap();
+        query
+            .select(&root)
+            .iter()
+            .map(|node| node.normalized_path())
+            .collect()
+    }
+
+    /// Select scalars from a document, returning the selected values as JSON text
+    fn values(document: &str, query: &str) -> Vec<String> {
+        let root = parse_literal(document).unwrap();
+        let query = Query::parse(query).unwrap();
+        query
+            .select(&root)
+            .iter()
+            .map(|node| match node.value {
+                JsonValue::String(raw) => raw.to_string(),
+                JsonValue::Integer(i) => i.to_string(),
+                JsonValue::Float(f) => f.to_string(),
+                JsonValue::Boolean(b) => b.to_string(),
+                JsonValue::Null => String::from("null"),
+                _ => panic!("{} selected a container", node),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bookstore_examples() {
+        assert_eq!(
+            values(BOOKSTORE, "$.store.book[*].author"),
+            [
+                r#""Nigel Rees""#,
+                r#""Evelyn Waugh""#,
+                r#""Herman Melville""#,
+                r#""J. R. R. Tolkien""#
+            ]
+        );
+        assert_eq!(
+            values(BOOKSTORE, "$..author"),
+            values(BOOKSTORE, "$.store.book[*].author")
+        );
+        assert_eq!(
+            paths(BOOKSTORE, "$.store.*"),
+            ["$['store']['book']", "$['store']['bicycle']"]
+        );
+        let mut prices = values(BOOKSTORE, "$.store..price");
+        prices.sort();
+        assert_eq!(prices, ["12.99", "22.99", "399", "8.95", "8.99"]);
+        assert_eq!(paths(BOOKSTORE, "$..book[2]"), ["$['store']['book'][2]"]);
+        assert_eq!(
+            values(BOOKSTORE, "$..book[2].author"),
+            [r#""Herman Melville""#]
+        );
+        assert!(paths(BOOKSTORE, "$..book[2].publisher").is_empty());
+        assert_eq!(paths(BOOKSTORE, "$..book[-1]"), ["$['store']['book'][3]"]);
+        assert_eq!(
+            paths(BOOKSTORE, "$..book[0,1]"),
+            ["$['store']['book'][0]", "$['store']['book'][1]"]
+        );
+        assert_eq!(
+            paths(BOOKSTORE, "$..book[:2]"),
+            paths(BOOKSTORE, "$..book[0,1]")
+        );
+        assert_eq!(
+            paths(BOOKSTORE, "$..book[?@.isbn]"),
+            ["$['store']['book'][2]", "$['store']['book'][3]"]
+        );
+        assert_eq!(
+            paths(BOOKSTORE, "$..book[?@.price<10]"),
+            ["$['store']['book'][0]", "$['store']['book'][2]"]
+        );
+        assert_eq!(paths(BOOKSTORE, "$..*").len(), 27);
+    }
+
+    #[test]
+    fn root_identifier() {
+        assert_eq!(paths(r#"{"k": "v"}"#, "$"), ["$"]);
+    }
+
+    #[test]
+    fn name_selectors() {
+        let document = r#"{"o": {"j j": {"k.k": 3}}, "'": {"@": 2}}"#;
+        assert_eq!(paths(document, "$.o['j j']"), ["$['o']['j j']"]);
+        assert_eq!(
+            paths(document, "$.o['j j']['k.k']"),
+            ["$['o']['j j']['k.k']"]
+        );
+        assert_eq!(
+            paths(document, r#"$.o["j j"]["k.k"]"#),
+            ["$['o']['j j']['k.k']"]
+        );
+        assert_eq!(paths(document, r#"$["'"]["@"]"#), [r"$['\'']['@']"]);
+        assert_eq!(values(document, r#"$["'"]["@"]"#), ["2"]);
+    }
+
+    #[test]
+    fn wildcard_selectors() {
+        let document = r#"{"o": {"j": 1, "k": 2}, "a": [5, 3]}"#;
+        assert_eq!(paths(document, "$[*]"), ["$['o']", "$['a']"]);
+        assert_eq!(paths(document, "$.o[*]"), ["$['o']['j']", "$['o']['k']"]);
+        assert_eq!(
+            paths(document, "$.o[*, *]"),
+            ["$['o']['j']", "$['o']['k']", "$['o']['j']", "$['o']['k']"]
+        );
+        assert_eq!(paths(document, "$.a[*]"), ["$['a'][0]", "$['a'][1]"]);
+    }
+
+    #[test]
+    fn index_selectors() {
+        let document = r#"["a", "b"]"#;
+        assert_eq!(values(document, "$[1]"), [r#""b""#]);
+        assert_eq!(paths(document, "$[-2]"), ["$[0]"]);
+        assert!(paths(document, "$[2]").is_empty());
+        assert!(paths(document, "$[-3]").is_empty());
+    }
+
+    #[test]
+    fn slice_selectors() {
+        let document = r#"["a", "b", "c", "d", "e", "f", "g"]"#;
+        assert_eq!(paths(document, "$[1:3]"), ["$[1]", "$[2]"]);
+        assert_eq!(paths(document, "$[5:]"), ["$[5]", "$[6]"]);
+        assert_eq!(paths(document, "$[1:5:2]"), ["$[1]", "$[3]"]);
+        assert_eq!(paths(document, "$[5:1:-2]"), ["$[5]", "$[3]"]);
+        assert_eq!(
+            paths(document, "$[::-1]"),
+            ["$[6]", "$[5]", "$[4]", "$[3]", "$[2]", "$[1]", "$[0]"]
+        );
+        assert!(paths(document, "$[::0]").is_empty());
+    }
+
+    #[test]
+    fn filter_selectors() {
+        let document = r#"{
+            "a": [3, 5, 1, 2, 4, 6, {"b": "j"}, {"b": "k"}, {"b": {}}, {"b": "kilo"}],
+            "o": {"p": 1, "q": 2, "r": 3, "s": 5, "t": {"u": 6}},
+            "e": "f"
+        }"#;
+        assert_eq!(paths(document, "$.a[?@.b == 'kilo']"), ["$['a'][9]"]);
+        assert_eq!(paths(document, "$.a[?(@.b == 'kilo')]"), ["$['a'][9]"]);
+        assert_eq!(
+            paths(document, "$.a[?@>3.5]"),
+            ["$['a'][1]", "$['a'][4]", "$['a'][5]"]
+        );
+        assert_eq!(
+            paths(document, "$.a[?@.b]"),
+            ["$['a'][6]", "$['a'][7]", "$['a'][8]", "$['a'][9]"]
+        );
+        assert_eq!(paths(document, "$[?@.*]"), ["$['a']", "$['o']"]);
+        assert_eq!(paths(document, "$[?@[?@.b]]"), ["$['a']"]);
+        assert_eq!(
+            paths(document, "$.o[?@<3, ?@<3]"),
+            ["$['o']['p']", "$['o']['q']", "$['o']['p']", "$['o']['q']"]
+        );
+        assert_eq!(
+            paths(document, r#"$.a[?@<2 || @.b == "k"]"#),
+            ["$['a'][2]", "$['a'][7]"]
+        );
+        assert_eq!(
+            paths(document, r#"$.a[?match(@.b, "[jk]")]"#),
+            ["$['a'][6]", "$['a'][7]"]
+        );
+        assert_eq!(
+            paths(document, r#"$.a[?search(@.b, "[jk]")]"#),
+            ["$['a'][6]", "$['a'][7]", "$['a'][9]"]
+        );
+        assert_eq!(
+            paths(document, "$.o[?@>1 && @<4]"),
+            ["$['o']['q']", "$['o']['r']"]
+        );
+        assert_eq!(paths(document, "$.o[?@.u || @.x]"), ["$['o']['t']"]);
+        assert_eq!(
+            paths(document, "$.a[?@.b == $.x]"),
+            [
+                "$['a'][0]",
+                "$['a'][1]",
+                "$['a'][2]",
+                "$['a'][3]",
+                "$['a'][4]",
+                "$['a'][5]"
+            ]
+        );
+        assert_eq!(paths(document, "$.a[?@ == @]").len(), 10);
+    }
+
+    #[test]
+    fn function_extensions() {
+        let document =
+            r#"{"a": ["ab", "abc", [1, 2, 3], {"x": 1}], "o": {"p": {"q": [1, 2]}, "r": {}}}"#;
+        assert_eq!(
+            paths(document, "$.a[?length(@) == 3]"),
+            ["$['a'][1]", "$['a'][2]"]
+        );
+        assert_eq!(paths(document, "$.o[?count(@.*) == 1]"), ["$['o']['p']"]);
+        assert_eq!(paths(document, "$.o[?value(@.q[0]) == 1]"), ["$['o']['p']"]);
+        assert_eq!(
+            paths(document, "$.a[?match(@, 'ab.?')]"),
+            ["$['a'][0]", "$['a'][1]"]
+        );
+        assert_eq!(paths(document, "$.a[?match(@, 'a.')]"), ["$['a'][0]"]);
+    }
+
+    #[test]
+    fn descendant_segments() {
+        let document = r#"{"o": {"j": 1, "k": 2}, "a": [5, 3, [{"j": 4}, {"k": 6}]]}"#;
+        assert_eq!(
+            paths(document, "$..j"),
+            ["$['o']['j']", "$['a'][2][0]['j']"]
+        );
+        assert_eq!(paths(document, "$..[0]"), ["$['a'][0]", "$['a'][2][0]"]);
+        assert_eq!(paths(document, "$..*").len(), 11);
+        assert_eq!(paths(document, "$..[*]"), paths(document, "$..*"));
+        assert_eq!(paths(document, "$..o"), ["$['o']"]);
+        assert_eq!(paths(document, "$.o..[*, *]").len(), 4);
+        assert_eq!(
+            paths(document, "$.a..[0, 1]"),
+            ["$['a'][0]", "$['a'][1]", "$['a'][2][0]", "$['a'][2][1]"]
+        );
+    }
+
+    #[test]
+    fn null_semantics() {
+        let document = r#"{"a": null, "b": [null], "c": [{}], "null": 1}"#;
+        assert_eq!(values(document, "$.a"), ["null"]);
+        assert!(paths(document, "$.a[0]").is_empty());
+        assert!(paths(document, "$.a.d").is_empty());
+        assert_eq!(values(document, "$.b[0]"), ["null"]);
+        assert_eq!(values(document, "$.b[*]"), ["null"]);
+        assert_eq!(values(document, "$.b[?@]"), ["null"]);
+        assert_eq!(values(document, "$.b[?@==null]"), ["null"]);
+        assert!(paths(document, "$.c[?@.d==null]").is_empty());
+        assert_eq!(values(document, "$.null"), ["1"]);
+    }
+
+    #[test]
+    fn normalized_paths_and_pointers() {
+        let document = r#"{"a/b": {"c~d": ["x"]}, "\u000b\n'": 1}"#;
+        let root = parse_literal(document).unwrap();
+        let nodes = Query::parse("$..*").unwrap().select(&root);
+        let located: Vec<(String, String)> = nodes
+            .iter()
+            .map(|node| (node.normalized_path(), node.pointer()))
+            .collect();
+        assert_eq!(
+            located,
+            [
+                (String::from("$['a/b']"), String::from("/a~1b")),
+                (String::from(r"$['\u000b\n\'']"), String::from("/\u{b}\n'")),
+                (String::from("$['a/b']['c~d']"), String::from("/a~1b/c~0d")),
+                (
+                    String::from("$['a/b']['c~d'][0]"),
+                    String::from("/a~1b/c~0d/0")
+                ),
+            ]
+        );
+    }
+}
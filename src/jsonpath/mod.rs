@@ -0,0 +1,254 @@
+//! An implementation of JSONPath, as specified within RFC 9535.  Queries are parsed into a simple AST, which is
+//! then evaluated directly against a [JsonValue] DOM in order to produce a list of nodes, each of which carries
+//! both a reference to the selected value and its normalized path within the document
+use crate::errors::ChiselResult;
+use chisel_json::JsonValue;
+use std::fmt::{Display, Write};
+
+mod evaluator;
+mod parser;
+
+/// A parsed JSONPath query, either rooted at the document (`$`) or at the current filter node (`@`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    /// Is this query relative to the current node, rather than the root?
+    pub relative: bool,
+    /// The segments making up the query
+    pub segments: Vec<Segment>,
+}
+
+/// A single segment within a query
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    /// A child segment, selecting from the immediate children of each input node
+    Child(Vec<Selector>),
+    /// A descendant segment, selecting from each input node and all of its descendants
+    Descendant(Vec<Selector>),
+}
+
+/// An individual selector within a segment
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selector {
+    /// Select an object member by name
+    Name(String),
+    /// Select all children
+    Wildcard,
+    /// Select an array element, negative values index from the end of the array
+    Index(i64),
+    /// Select a slice of an array
+    Slice {
+        start: Option<i64>,
+        end: Option<i64>,
+        step: Option<i64>,
+    },
+    /// Select the children for which a logical expression holds
+    Filter(LogicalExpr),
+}
+
+/// A logical expression, as found within a filter selector
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogicalExpr {
+    /// Disjunction
+    Or(Vec<LogicalExpr>),
+    /// Conjunction
+    And(Vec<LogicalExpr>),
+    /// Negation
+    Not(Box<LogicalExpr>),
+    /// A comparison between two values
+    Comparison(Comparable, ComparisonOp, Comparable),
+    /// An existence test for a query, which holds if the query selects at least one node
+    Exists(Query),
+    /// A test based on the result of a function returning a logical or nodes result
+    Function(FunctionExpr),
+}
+
+/// The available comparison operators
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ComparisonOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Something which may appear on either side of a comparison
+#[derive(Debug, Clone, PartialEq)]
+pub enum Comparable {
+    /// A literal value, held in the same raw form as the DOM
+    Literal(Literal),
+    /// A singular query, selecting at most one node
+    Query(Query),
+    /// A function returning a value
+    Function(FunctionExpr),
+}
+
+/// Literal values
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Null,
+}
+
+/// The built-in functions
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Function {
+    Length,
+    Count,
+    Match,
+    Search,
+    Value,
+}
+
+/// The declared types for function parameters and results, as per section 2.4.1 of the RFC
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FunctionType {
+    Value,
+    Logical,
+    Nodes,
+}
+
+impl Function {
+    /// Look up a function by name
+    pub fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "length" => Some(Function::Length),
+            "count" => Some(Function::Count),
+            "match" => Some(Function::Match),
+            "search" => Some(Function::Search),
+            "value" => Some(Function::Value),
+            _ => None,
+        }
+    }
+
+    /// The declared parameter types for the function
+    pub fn parameters(&self) -> &'static [FunctionType] {
+        match self {
+            Function::Length => &[FunctionType::Value],
+            Function::Count | Function::Value => &[FunctionType::Nodes],
+            Function::Match | Function::Search => &[FunctionType::Value, FunctionType::Value],
+        }
+    }
+
+    /// The declared result type for the function
+    pub fn result(&self) -> FunctionType {
+        match self {
+            Function::Length | Function::Count | Function::Value => FunctionType::Value,
+            Function::Match | Function::Search => FunctionType::Logical,
+        }
+    }
+}
+
+/// A function invocation
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionExpr {
+    pub function: Function,
+    pub args: Vec<FunctionArg>,
+}
+
+/// An argument to a function
+#[derive(Debug, Clone, PartialEq)]
+pub enum FunctionArg {
+    Literal(Literal),
+    Query(Query),
+    Logical(LogicalExpr),
+    Function(FunctionExpr),
+}
+
+impl Query {
+    /// Parse a JSONPath query expression
+    pub fn parse(expression: &str) -> ChiselResult<Query> {
+        parser::parse_query(expression)
+    }
+
+    /// Evaluate the query against a document, returning the list of selected nodes
+    pub fn select<'a>(&self, root: &'a JsonValue<'a>) -> Vec<Node<'a>> {
+        evaluator::Evaluator::new(root).select(self)
+    }
+
+    /// A singular query can only ever select at most one node
+    pub fn is_singular(&self) -> bool {
+        self.segments.iter().all(|s| match s {
+            Segment::Child(selectors) => {
+                selectors.len() == 1
+                    && matches!(selectors[0], Selector::Name(_) | Selector::Index(_))
+            }
+            Segment::Descendant(_) => false,
+        })
+    }
+}
+
+/// An element within a normalized path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathElement {
+    Name(String),
+    Index(usize),
+}
+
+/// A node selected by a query, along with its location within the document
+#[derive(Debug)]
+pub struct Node<'a> {
+    /// The location of the node
+    pub path: Vec<PathElement>,
+    /// The node value
+    pub value: &'a JsonValue<'a>,
+}
+
+impl<'a> Node<'a> {
+    /// Format the location of the node as a normalized path, e.g. `$['statuses'][0]`
+    pub fn normalized_path(&self) -> String {
+        let mut path = String::from("$");
+        for element in &self.path {
+            match element {
+                PathElement::Name(name) => {
+                    path.push_str("['");
+                    for ch in name.chars() {
+                        match ch {
+                            '\'' => path.push_str("\\'"),
+                            '\\' => path.push_str("\\\\"),
+                            '\u{08}' => path.push_str("\\b"),
+                            '\u{0c}' => path.push_str("\\f"),
+                            '\n' => path.push_str("\\n"),
+                            '\r' => path.push_str("\\r"),
+                            '\t' => path.push_str("\\t"),
+                            c if (c as u32) < 0x20 => {
+                                let _ = write!(path, "\\u{:04x}", c as u32);
+                            }
+                            c => path.push(c),
+                        }
+                    }
+                    path.push_str("']");
+                }
+                PathElement::Index(index) => {
+                    let _ = write!(path, "[{}]", index);
+                }
+            }
+        }
+        path
+    }
+
+    /// Format the location of the node as a JSON pointer
+    pub fn pointer(&self) -> String {
+        let mut pointer = String::new();
+        for element in &self.path {
+            pointer.push('/');
+            match element {
+                PathElement::Name(name) => {
+                    pointer.push_str(&name.replace('~', "~0").replace('/', "~1"))
+                }
+                PathElement::Index(index) => pointer.push_str(&index.to_string()),
+            }
+        }
+        pointer
+    }
+}
+
+impl Display for Node<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.normalized_path())
+    }
+}
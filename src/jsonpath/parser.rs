@@ -0,0 +1,781 @@
+//! A recursive descent parser for JSONPath query expressions, following the ABNF grammar given in RFC 9535
+use super::{
+    Comparable, ComparisonOp, Function, FunctionArg, FunctionExpr, FunctionType, Literal,
+    LogicalExpr, Query, Segment, Selector,
+};
+use crate::errors::{ChiselError, ChiselResult};
+
+/// The range of integers that may be used for indexes and slices (the I-JSON safe range)
+const MAX_INT: i64 = (1 << 53) - 1;
+
+/// Parse a complete query expression, which must be rooted at `$`
+pub(super) fn parse_query(expression: &str) -> ChiselResult<Query> {
+    let mut parser = QueryParser {
+        chars: expression.chars().collect(),
+        position: 0,
+    };
+    if parser.peek() != Some('$') {
+        return parser.error("a query must start with '$'");
+    }
+    let query = parser.parse_rooted_query()?;
+    if parser.position < parser.chars.len() {
+        return parser.error("unexpected trailing input");
+    }
+    Ok(query)
+}
+
+/// Internal parser state
+struct QueryParser {
+    /// The expression being parsed
+    chars: Vec<char>,
+    /// The current position within the expression
+    position: usize,
+}
+
+/// The result of parsing something that may appear within a filter expression, prior to working out whether it forms
+/// part of a comparison or stands on its own as a test
+enum Operand {
+    Literal(Literal),
+    Query(Query),
+    Function(FunctionExpr),
+}
+
+impl QueryParser {
+    /// Generate an error, annotated with the current position
+    fn error<T>(&self, reason: &str) -> ChiselResult<T> {
+        Err(ChiselError::InvalidQuery(format!(
+            "{} (at offset {})",
+            reason, self.position
+        )))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let ch = self.peek();
+        if ch.is_some() {
+            self.position += 1;
+        }
+        ch
+    }
+
+    /// Check for, and consume a specific sequence of characters
+    fn consume(&mut self, expected: &str) -> bool {
+        let matched = expected
+            .chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_at(i) == Some(c));
+        if matched {
+            self.position += expected.chars().count();
+        }
+        matched
+    }
+
+    /// Check for, and consume a keyword, which mustn't be immediately followed by further name characters
+    fn consume_keyword(&mut self, keyword: &str) -> bool {
+        let checkpoint = self.position;
+        if self.consume(keyword) && !self.peek().map_or(false, is_name_char) {
+            return true;
+        }
+        self.position = checkpoint;
+        false
+    }
+
+    fn expect(&mut self, expected: char) -> ChiselResult<()> {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", expected))
+        }
+    }
+
+    /// Skip blank space, as defined within the RFC
+    fn skip_blanks(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.position += 1;
+        }
+    }
+
+    /// Parse a query starting at `$` or `@`, followed by any number of segments
+    fn parse_rooted_query(&mut self) -> ChiselResult<Query> {
+        let relative = match self.advance() {
+            Some('$') => false,
+            Some('@') => true,
+            _ => return self.error("expected '$' or '@'"),
+        };
+        let mut segments = vec![];
+        loop {
+            // blank space is permitted between segments, but mustn't be consumed if there's no segment following
+            let checkpoint = self.position;
+            self.skip_blanks();
+            match self.peek() {
+                Some('[') => segments.push(Segment::Child(self.parse_bracketed_selection()?)),
+                Some('.') if self.peek_at(1) == Some('.') => {
+                    self.position += 2;
+                    segments.push(Segment::Descendant(self.parse_descendant_selectors()?));
+                }
+                Some('.') => {
+                    self.position += 1;
+                    segments.push(Segment::Child(vec![self.parse_shorthand()?]));
+                }
+                _ => {
+                    self.position = checkpoint;
+                    break;
+                }
+            }
+        }
+        Ok(Query { relative, segments })
+    }
+
+    /// Parse whatever follows a `..`
+    fn parse_descendant_selectors(&mut self) -> ChiselResult<Vec<Selector>> {
+        match self.peek() {
+            Some('[') => self.parse_bracketed_selection(),
+            _ => Ok(vec![self.parse_shorthand()?]),
+        }
+    }
+
+    /// Parse either a wildcard, or a member name shorthand
+    fn parse_shorthand(&mut self) -> ChiselResult<Selector> {
+        match self.peek() {
+            Some('*') => {
+                self.position += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some(c) if is_name_first(c) => {
+                let mut name = String::new();
+                while let Some(c) = self.peek() {
+                    if !is_name_char(c) {
+                        break;
+                    }
+                    name.push(c);
+                    self.position += 1;
+                }
+                Ok(Selector::Name(name))
+            }
+            _ => self.error("expected a member name or '*'"),
+        }
+    }
+
+    /// Parse a comma-separated list of selectors between brackets
+    fn parse_bracketed_selection(&mut self) -> ChiselResult<Vec<Selector>> {
+        self.expect('[')?;
+        let mut selectors = vec![];
+        loop {
+            self.skip_blanks();
+            selectors.push(self.parse_selector()?);
+            self.skip_blanks();
+            match self.advance() {
+                Some(',') => continue,
+                Some(']') => break,
+                _ => {
+                    self.position = self.position.saturating_sub(1);
+                    return self.error("expected ',' or ']'");
+                }
+            }
+        }
+        Ok(selectors)
+    }
+
+    /// Parse an individual selector
+    fn parse_selector(&mut self) -> ChiselResult<Selector> {
+        match self.peek() {
+            Some('\'' | '"') => Ok(Selector::Name(self.parse_string_literal()?)),
+            Some('*') => {
+                self.position += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.position += 1;
+                self.skip_blanks();
+                Ok(Selector::Filter(self.parse_logical_or()?))
+            }
+            Some(':') => self.parse_slice(None),
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let index = self.parse_int()?;
+                let checkpoint = self.position;
+                self.skip_blanks();
+                if self.peek() == Some(':') {
+                    self.parse_slice(Some(index))
+                } else {
+                    self.position = checkpoint;
+                    Ok(Selector::Index(index))
+                }
+            }
+            _ => self.error("expected a selector"),
+        }
+    }
+
+    /// Parse the remainder of a slice selector, with the current position sitting on the first ':'
+    fn parse_slice(&mut self, start: Option<i64>) -> ChiselResult<Selector> {
+        self.expect(':')?;
+        self.skip_blanks();
+        let end = self.parse_optional_int()?;
+        self.skip_blanks();
+        let mut step = None;
+        if self.peek() == Some(':') {
+            self.position += 1;
+            self.skip_blanks();
+            step = self.parse_optional_int()?;
+        }
+        Ok(Selector::Slice { start, end, step })
+    }
+
+    fn parse_optional_int(&mut self) -> ChiselResult<Option<i64>> {
+        match self.peek() {
+            Some(c) if c == '-' || c.is_ascii_digit() => Ok(Some(self.parse_int()?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Parse an integer, which mustn't have leading zeros (or be "-0")
+    fn parse_int(&mut self) -> ChiselResult<i64> {
+        let start = self.position;
+        let negative = self.consume("-");
+        let digits_start = self.position;
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        let digits: String = self.chars[digits_start..self.position].iter().collect();
+        if digits.is_empty() {
+            return self.error("expected an integer");
+        }
+        if (digits.len() > 1 && digits.starts_with('0')) || (negative && digits == "0") {
+            self.position = start;
+            return self.error("invalid integer");
+        }
+        match digits.parse::<i64>() {
+            Ok(value) if value <= MAX_INT => Ok(if negative { -value } else { value }),
+            _ => {
+                self.position = start;
+                self.error("integer out of range")
+            }
+        }
+    }
+
+    /// Parse a single or double quoted string literal, decoding any escapes
+    fn parse_string_literal(&mut self) -> ChiselResult<String> {
+        let quote = match self.advance() {
+            Some(q @ ('\'' | '"')) => q,
+            _ => return self.error("expected a string literal"),
+        };
+        let mut value = String::new();
+        loop {
+            match self.advance() {
+                None => return self.error("unterminated string literal"),
+                Some(c) if c == quote => break,
+                Some('\\') => match self.advance() {
+                    Some('b') => value.push('\u{08}'),
+                    Some('f') => value.push('\u{0c}'),
+                    Some('n') => value.push('\n'),
+                    Some('r') => value.push('\r'),
+                    Some('t') => value.push('\t'),
+                    Some('/') => value.push('/'),
+                    Some('\\') => value.push('\\'),
+                    Some(c) if c == quote => value.push(c),
+                    Some('u') => value.push(self.parse_unicode_escape()?),
+                    _ => return self.error("invalid escape sequence"),
+                },
+                Some(c) if (c as u32) < 0x20 => {
+                    return self.error("control characters must be escaped")
+                }
+                Some(c) => value.push(c),
+            }
+        }
+        Ok(value)
+    }
+
+    /// Parse the hex digits following a `\u`, including a trailing low surrogate if required
+    fn parse_unicode_escape(&mut self) -> ChiselResult<char> {
+        let high = self.parse_hex4()?;
+        if (0xdc00..0xe000).contains(&high) {
+            return self.error("unexpected low surrogate");
+        }
+        if (0xd800..0xdc00).contains(&high) {
+            if !self.consume("\\u") {
+                return self.error("expected a low surrogate");
+            }
+            let low = self.parse_hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return self.error("expected a low surrogate");
+            }
+            let code = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+            return Ok(char::from_u32(code).unwrap_or('\u{fffd}'));
+        }
+        match char::from_u32(high) {
+            Some(c) => Ok(c),
+            None => self.error("invalid unicode escape"),
+        }
+    }
+
+    fn parse_hex4(&mut self) -> ChiselResult<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            match self.advance().and_then(|c| c.to_digit(16)) {
+                Some(digit) => code = (code << 4) | digit,
+                None => return self.error("invalid unicode escape"),
+            }
+        }
+        Ok(code)
+    }
+
+    /// logical-or-expr = logical-and-expr *(S "||" S logical-and-expr)
+    fn parse_logical_or(&mut self) -> ChiselResult<LogicalExpr> {
+        let mut terms = vec![self.parse_logical_and()?];
+        loop {
+            let checkpoint = self.position;
+            self.skip_blanks();
+            if self.consume("||") {
+                self.skip_blanks();
+                terms.push(self.parse_logical_and()?);
+            } else {
+                self.position = checkpoint;
+                break;
+            }
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            LogicalExpr::Or(terms)
+        })
+    }
+
+    /// logical-and-expr = basic-expr *(S "&&" S basic-expr)
+    fn parse_logical_and(&mut self) -> ChiselResult<LogicalExpr> {
+        let mut terms = vec![self.parse_basic()?];
+        loop {
+            let checkpoint = self.position;
+            self.skip_blanks();
+            if self.consume("&&") {
+                self.skip_blanks();
+                terms.push(self.parse_basic()?);
+            } else {
+                self.position = checkpoint;
+                break;
+            }
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            LogicalExpr::And(terms)
+        })
+    }
+
+    /// basic-expr = paren-expr / comparison-expr / test-expr
+    fn parse_basic(&mut self) -> ChiselResult<LogicalExpr> {
+        if self.peek() == Some('!') {
+            self.position += 1;
+            self.skip_blanks();
+            return match self.peek() {
+                Some('(') => Ok(LogicalExpr::Not(Box::new(self.parse_paren()?))),
+                _ => {
+                    let test = self.parse_test()?;
+                    Ok(LogicalExpr::Not(Box::new(test)))
+                }
+            };
+        }
+        if self.peek() == Some('(') {
+            return self.parse_paren();
+        }
+
+        let start = self.position;
+        let lhs = self.parse_operand()?;
+        let checkpoint = self.position;
+        self.skip_blanks();
+        match self.parse_comparison_op() {
+            Some(op) => {
+                self.skip_blanks();
+                let lhs = self.comparable(lhs, start)?;
+                let rhs_start = self.position;
+                let rhs = self.parse_operand()?;
+                let rhs = self.comparable(rhs, rhs_start)?;
+                Ok(LogicalExpr::Comparison(lhs, op, rhs))
+            }
+            None => {
+                self.position = checkpoint;
+                self.testable(lhs, start)
+            }
+        }
+    }
+
+    /// paren-expr = "(" S logical-expr S ")"
+    fn parse_paren(&mut self) -> ChiselResult<LogicalExpr> {
+        self.expect('(')?;
+        self.skip_blanks();
+        let expr = self.parse_logical_or()?;
+        self.skip_blanks();
+        self.expect(')')?;
+        Ok(expr)
+    }
+
+    /// Parse a test expression (following a '!')
+    fn parse_test(&mut self) -> ChiselResult<LogicalExpr> {
+        let start = self.position;
+        let operand = self.parse_operand()?;
+        self.testable(operand, start)
+    }
+
+    fn parse_comparison_op(&mut self) -> Option<ComparisonOp> {
+        for (text, op) in [
+            ("==", ComparisonOp::Eq),
+            ("!=", ComparisonOp::Ne),
+            ("<=", ComparisonOp::Le),
+            (">=", ComparisonOp::Ge),
+            ("<", ComparisonOp::Lt),
+            (">", ComparisonOp::Gt),
+        ] {
+            if self.consume(text) {
+                return Some(op);
+            }
+        }
+        None
+    }
+
+    /// Parse a literal, a query or a function invocation
+    fn parse_operand(&mut self) -> ChiselResult<Operand> {
+        match self.peek() {
+            Some('$' | '@') => Ok(Operand::Query(self.parse_rooted_query()?)),
+            Some('\'' | '"') => Ok(Operand::Literal(Literal::String(
+                self.parse_string_literal()?,
+            ))),
+            Some(c) if c == '-' || c.is_ascii_digit() => Ok(Operand::Literal(self.parse_number()?)),
+            Some(c) if c.is_ascii_lowercase() => {
+                if self.consume_keyword("true") {
+                    return Ok(Operand::Literal(Literal::Boolean(true)));
+                }
+                if self.consume_keyword("false") {
+                    return Ok(Operand::Literal(Literal::Boolean(false)));
+                }
+                if self.consume_keyword("null") {
+                    return Ok(Operand::Literal(Literal::Null));
+                }
+                Ok(Operand::Function(self.parse_function()?))
+            }
+            _ => self.error("expected a literal, query or function"),
+        }
+    }
+
+    /// Parse a number literal: (int / "-0") [ frac ] [ exp ]
+    fn parse_number(&mut self) -> ChiselResult<Literal> {
+        let start = self.position;
+        self.consume("-");
+        let digits_start = self.position;
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        let digits = self.position - digits_start;
+        if digits == 0 || (digits > 1 && self.chars[digits_start] == '0') {
+            self.position = start;
+            return self.error("invalid number");
+        }
+        let mut integral = true;
+        if self.peek() == Some('.') && matches!(self.peek_at(1), Some(c) if c.is_ascii_digit()) {
+            integral = false;
+            self.position += 1;
+            while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+                self.position += 1;
+            }
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            integral = false;
+            self.position += 1;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.position += 1;
+            }
+            if !matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+                return self.error("invalid exponent");
+            }
+            while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+                self.position += 1;
+            }
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        if integral {
+            if let Ok(value) = text.parse::<i64>() {
+                return Ok(Literal::Integer(value));
+            }
+        }
+        match text.parse::<f64>() {
+            Ok(value) => Ok(Literal::Float(value)),
+            Err(_) => {
+                self.position = start;
+                self.error("invalid number")
+            }
+        }
+    }
+
+    /// Parse a function invocation, checking that the arguments are well-typed
+    fn parse_function(&mut self) -> ChiselResult<FunctionExpr> {
+        let start = self.position;
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+                break;
+            }
+            name.push(c);
+            self.position += 1;
+        }
+        let function = match Function::from_name(&name) {
+            Some(f) => f,
+            None => {
+                self.position = start;
+                return self.error(&format!("unknown function '{}'", name));
+            }
+        };
+        self.expect('(')?;
+        self.skip_blanks();
+
+        let mut args = vec![];
+        if self.peek() != Some(')') {
+            loop {
+                let index = args.len();
+                let arg_start = self.position;
+                let declared = match function.parameters().get(index) {
+                    Some(declared) => *declared,
+                    None => return self.error(&format!("too many arguments for '{}'", name)),
+                };
+                args.push(self.parse_function_arg(declared, arg_start)?);
+                self.skip_blanks();
+                if self.consume(",") {
+                    self.skip_blanks();
+                } else {
+                    break;
+                }
+            }
+        }
+        self.expect(')')?;
+        if args.len() != function.parameters().len() {
+            self.position = start;
+            return self.error(&format!("wrong number of arguments for '{}'", name));
+        }
+        Ok(FunctionExpr { function, args })
+    }
+
+    /// Parse a function argument, checking it against the declared parameter type
+    fn parse_function_arg(
+        &mut self,
+        declared: FunctionType,
+        start: usize,
+    ) -> ChiselResult<FunctionArg> {
+        let is_logical_start = matches!(self.peek(), Some('!' | '('));
+        if declared == FunctionType::Logical || is_logical_start {
+            let expr = self.parse_logical_or()?;
+            return match (declared, expr) {
+                (FunctionType::Logical, expr) => Ok(FunctionArg::Logical(expr)),
+                (_, _) => {
+                    self.position = start;
+                    self.error("argument isn't of the expected type")
+                }
+            };
+        }
+        match (declared, self.parse_operand()?) {
+            (FunctionType::Value, Operand::Literal(literal)) => Ok(FunctionArg::Literal(literal)),
+            (FunctionType::Value, Operand::Query(query)) if query.is_singular() => {
+                Ok(FunctionArg::Query(query))
+            }
+            (FunctionType::Nodes, Operand::Query(query)) => Ok(FunctionArg::Query(query)),
+            (FunctionType::Value, Operand::Function(f))
+                if f.function.result() == FunctionType::Value =>
+            {
+                Ok(FunctionArg::Function(f))
+            }
+            _ => {
+                self.position = start;
+                self.error("argument isn't of the expected type")
+            }
+        }
+    }
+
+    /// Check that an operand may be used within a comparison
+    fn comparable(&mut self, operand: Operand, start: usize) -> ChiselResult<Comparable> {
+        match operand {
+            Operand::Literal(literal) => Ok(Comparable::Literal(literal)),
+            Operand::Query(query) if query.is_singular() => Ok(Comparable::Query(query)),
+            Operand::Function(f) if f.function.result() == FunctionType::Value => {
+                Ok(Comparable::Function(f))
+            }
+            _ => {
+                self.position = start;
+                self.error("only singular queries and value functions can be compared")
+            }
+        }
+    }
+
+    /// Check that an operand may be used on its own as a test expression
+    fn testable(&mut self, operand: Operand, start: usize) -> ChiselResult<LogicalExpr> {
+        match operand {
+            Operand::Query(query) => Ok(LogicalExpr::Exists(query)),
+            Operand::Function(f) if f.function.result() != FunctionType::Value => {
+                Ok(LogicalExpr::Function(f))
+            }
+            _ => {
+                self.position = start;
+                self.error("expected a query or a logical function")
+            }
+        }
+    }
+}
+
+/// name-first = ALPHA / "_" / %x80-D7FF / %xE000-10FFFF
+fn is_name_first(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || (c as u32) >= 0x80
+}
+
+/// name-char = name-first / DIGIT
+fn is_name_char(c: char) -> bool {
+    is_name_first(c) || c.is_ascii_digit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_shorthands_into_the_equivalent_brackets() {
+        assert_eq!(
+            parse_query("$.a.*..b").unwrap(),
+            parse_query("$['a'][*]..['b']").unwrap()
+        );
+        assert_eq!(parse_query("$..*").unwrap(), parse_query("$..[*]").unwrap());
+        assert_eq!(
+            parse_query("$ .a [ 0 ]").unwrap(),
+            parse_query("$['a'][0]").unwrap()
+        );
+        assert_eq!(
+            parse_query(r#"$["a"]"#).unwrap(),
+            parse_query("$['a']").unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_segments_and_selectors() {
+        let query = parse_query("$.a[0, -1, 1:5:2, *]..['b']").unwrap();
+        assert!(!query.relative);
+        assert_eq!(
+            query.segments,
+            vec![
+                Segment::Child(vec![Selector::Name(String::from("a"))]),
+                Segment::Child(vec![
+                    Selector::Index(0),
+                    Selector::Index(-1),
+                    Selector::Slice {
+                        start: Some(1),
+                        end: Some(5),
+                        step: Some(2)
+                    },
+                    Selector::Wildcard,
+                ]),
+                Segment::Descendant(vec![Selector::Name(String::from("b"))]),
+            ]
+        );
+    }
+
+    #[test]
+    fn unescapes_string_literals() {
+        let query = parse_query(r#"$['\'', "\"", '☺', '😀', '\t']"#).unwrap();
+        assert_eq!(
+            query.segments,
+            vec![Segment::Child(vec![
+                Selector::Name(String::from("'")),
+                Selector::Name(String::from("\"")),
+                Selector::Name(String::from("\u{263a}")),
+                Selector::Name(String::from("\u{1f600}")),
+                Selector::Name(String::from("\t")),
+            ])]
+        );
+    }
+
+    #[test]
+    fn identifies_singular_queries() {
+        assert!(parse_query("$").unwrap().is_singular());
+        assert!(parse_query("$.a[0]['b']").unwrap().is_singular());
+        assert!(!parse_query("$.a[*]").unwrap().is_singular());
+        assert!(!parse_query("$.a[0:1]").unwrap().is_singular());
+        assert!(!parse_query("$..a").unwrap().is_singular());
+        assert!(!parse_query("$[0, 1]").unwrap().is_singular());
+    }
+
+    #[test]
+    fn accepts_well_typed_filters() {
+        for query in [
+            "$[?@.a]",
+            "$[?!@.a]",
+            "$[?(@.a)]",
+            "$[?@.a == 1 && (@.b < 2 || !@.c)]",
+            "$[?@.a == $.b]",
+            "$[?@ == true]",
+            "$[?@.a != null]",
+            "$[?@.a >= -1.5e3]",
+            "$[?length(@.a) > 2]",
+            "$[?count(@.*) == 1]",
+            "$[?value(@..a) == 'x']",
+            "$[?match(@.a, 'a.*')]",
+            "$[?!search(@.a, 'b')]",
+            "$[?@[?@.a]]",
+        ] {
+            assert!(parse_query(query).is_ok(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_queries() {
+        for query in [
+            "",
+            "a",
+            "@",
+            "$.",
+            "$[",
+            "$a",
+            "$.a ",
+            "$..",
+            "$...a",
+            "$.1",
+            "$['a'",
+            "$['a\"]",
+            "$[01]",
+            "$[-0]",
+            "$[1.0]",
+            "$[9007199254740992]",
+            "$[::1:]",
+            "$['\\x']",
+            "$['\\uD83D']",
+            "$[?]",
+            "$[?@.a == ]",
+            "$[?@.a = 1]",
+            "$[?@.a == 1 = 2]",
+            "$[?1 == 01]",
+        ] {
+            assert!(parse_query(query).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn rejects_badly_typed_filters() {
+        for query in [
+            // comparisons need singular queries
+            "$[?@.* == 1]",
+            "$[?@..a == 1]",
+            // literals can't stand on their own as tests
+            "$[?1]",
+            "$[?'a']",
+            // logical functions can't be compared, value functions can't be tests
+            "$[?match(@.a, 'a') == true]",
+            "$[?length(@.a)]",
+            // arguments need to be of the right type, and number
+            "$[?length(@.*) < 3]",
+            "$[?count(1) == 1]",
+            "$[?count(@.a, @.b) == 1]",
+            "$[?match(@.a)]",
+            "$[?unknown(@.a)]",
+        ] {
+            assert!(parse_query(query).is_err(), "{}", query);
+        }
+    }
+}
@@ -2,21 +2,32 @@
 
 use crate::render::options::RenderOptions;
 use crate::state::AppChangeState;
+use atty::Stream;
 use clap::Parser;
 use cli::{AppArguments, AppCommand};
 use commands::{Command, CommandContext};
 
 mod cli;
 mod commands;
+mod dom;
 mod errors;
+mod escapes;
+mod formats;
+mod jsonpath;
 mod render;
+mod sinks;
 mod sources;
 mod state;
 mod threads;
+mod transform;
 
 /// Create a new [CommandContext] and execute the specified [Command] instance
 fn execute_command(cmd: &mut impl Command) -> i32 {
-    let render_options = RenderOptions::default();
+    // only colour output that's headed straight for a terminal, and respect NO_COLOR
+    let render_options = RenderOptions {
+        colour: atty::is(Stream::Stdout) && std::env::var_os("NO_COLOR").is_none(),
+        ..RenderOptions::default()
+    };
     let mut state = AppChangeState::new(render_options);
     let mut context = CommandContext::new(state.get_render_pipeline());
     match cmd.execute(&mut context) {
@@ -42,6 +53,17 @@ fn main() {
         AppCommand::Print(mut cmd) => execute_command(&mut cmd),
         AppCommand::Filter(mut cmd) => execute_command(&mut cmd),
         AppCommand::Pointers(mut cmd) => execute_command(&mut cmd),
+        AppCommand::Locate(mut cmd) => execute_command(&mut cmd),
+        AppCommand::Set(mut cmd) => execute_command(&mut cmd),
+        AppCommand::Delete(mut cmd) => execute_command(&mut cmd),
+        AppCommand::Query(mut cmd) => execute_command(&mut cmd),
+        AppCommand::Transform(mut cmd) => execute_command(&mut cmd),
+        AppCommand::Flatten(mut cmd) => execute_command(&mut cmd),
+        AppCommand::Unflatten(mut cmd) => execute_command(&mut cmd),
+        AppCommand::Dotted(mut cmd) => execute_command(&mut cmd),
+        AppCommand::Convert(mut cmd) => execute_command(&mut cmd),
+        AppCommand::Minify(mut cmd) => execute_command(&mut cmd),
+        AppCommand::Hash(mut cmd) => execute_command(&mut cmd),
     };
 
     // return a well-behaved error code
@@ -0,0 +1,49 @@
+//! A renderer which collects the output from a rendering pipeline into an in-memory buffer, rather than the
+//! terminal.  Used by commands that need to send their output somewhere other than stdout
+use super::display_lists::{ChangeState, DisplayList, DisplayListCommand, DisplayListMode, Draw};
+use super::themes::Theme;
+use crate::errors::ChiselResult;
+use std::sync::mpsc::{channel, Sender};
+
+/// Hand a fresh pipeline to a producer, and then gather up everything that it rendered into a [String]. Only
+/// [Draw] commands contribute to the output, and the only state change honoured is a change of indent character
+pub fn render_to_string<Producer>(mut theme: Theme, producer: Producer) -> ChiselResult<String>
+where
+    Producer: FnOnce(Sender<DisplayList>) -> ChiselResult<()>,
+{
+    let (tx, rx) = channel::<DisplayList>();
+    producer(tx)?;
+
+    // the producer owned the only sender, so the receiver will drain and then stop
+    let mut buffer = String::new();
+    for list in rx.iter() {
+        if list.mode != DisplayListMode::Immediate {
+            continue;
+        }
+        for cmd in list.cmds {
+            match cmd {
+                DisplayListCommand::Draw(draw) => render_draw_command(&mut buffer, &theme, &draw),
+                DisplayListCommand::ChangeState(ChangeState::SetIndent(indent)) => {
+                    theme.indent = indent
+                }
+                _ => (),
+            }
+        }
+    }
+    Ok(buffer)
+}
+
+/// Append the output for a single [Draw] command to the buffer
+fn render_draw_command(buffer: &mut String, theme: &Theme, cmd: &Draw) {
+    match cmd {
+        Draw::NewLine => buffer.push('\n'),
+        Draw::Indent(n) => (0..*n).for_each(|_| buffer.push(theme.indent)),
+        Draw::Char(c) => buffer.push(*c),
+        Draw::Repeat(c, n) => (0..*n).for_each(|_| buffer.push(*c)),
+        Draw::FixedWidthText(s, width) => {
+            buffer.push_str(&format!("{:width$}", s, width = *width as usize))
+        }
+        Draw::Text(s) => buffer.push_str(s),
+        Draw::Slice(s) => buffer.push_str(s),
+    }
+}
@@ -0,0 +1,132 @@
+//! Compact printer logic, which renders documents as the smallest valid JSON, with no insignificant whitespace at all
+//!
+//! The printer is driven by SAX events rather than a DOM, so it never needs to hold more than a single token. Where
+//! the raw text of a token is available (i.e. for JSON input) it's copied across byte for byte, otherwise the token is
+//! serialised from its value
+use crate::cl_immediate;
+use crate::errors::{ChiselError, ChiselResult};
+use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode, Draw};
+use chisel_json::events::Match;
+use std::sync::mpsc::Sender;
+
+/// The amount of output that's gathered up before being sent down the pipeline, when streaming
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Format a float as compactly as possible, whilst making sure that it's still read back as a float
+pub fn compact_float(value: f64) -> String {
+    let plain = value.to_string();
+    let plain = match plain.contains(['.', 'e', 'E']) {
+        true => plain,
+        false => format!("{}.0", plain),
+    };
+    let exponent = format!("{:e}", value);
+    match exponent.len() < plain.len() {
+        true => exponent,
+        false => plain,
+    }
+}
+
+/// Printer for rendering streams of SAX events as compact JSON
+pub struct CompactPrinter {
+    /// The pipeline to render to
+    pub pipeline: Sender<DisplayList>,
+
+    /// Whether output is sent down the pipeline as it's produced, rather than all at once when finished
+    pub streaming: bool,
+
+    /// Output which hasn't yet been sent down the pipeline
+    output: String,
+
+    /// Whether a comma is needed ahead of the next key or value
+    needs_comma: bool,
+}
+
+impl CompactPrinter {
+    /// Construct a new instance, based on a supplied pipeline. When not streaming, nothing is rendered until
+    /// [CompactPrinter::finish] is called, so that nothing at all is written for invalid input
+    pub fn new(pipeline: Sender<DisplayList>, streaming: bool) -> Self {
+        CompactPrinter {
+            pipeline,
+            streaming,
+            output: String::new(),
+            needs_comma: false,
+        }
+    }
+
+    /// Chuck a [DisplayList] at the rendering pipeline and perform error conversion if necessary
+    #[inline]
+    fn submit_command_list(&self, cmds: DisplayList) -> ChiselResult<()> {
+        match self.pipeline.send(cmds) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ChiselError::DisplayListFailed),
+        }
+    }
+
+    /// Render a single event, given the raw text of the token it was matched against (if available)
+    pub fn render_event(&mut self, matched: &Match, raw: Option<&str>) -> ChiselResult<()> {
+        match matched {
+            Match::StartObject | Match::StartArray => {
+                self.separate();
+                self.output.push(match matched {
+                    Match::StartObject => '{',
+                    _ => '[',
+                });
+                self.needs_comma = false;
+            }
+            Match::EndObject => {
+                self.output.push('}');
+                self.needs_comma = true;
+            }
+            Match::EndArray => {
+                self.output.push(']');
+                self.needs_comma = true;
+            }
+            Match::ObjectKey(key) => {
+                self.separate();
+                self.output.push_str(raw.unwrap_or(key));
+                self.output.push(':');
+
+                // the value follows straight on from the colon
+                self.needs_comma = false;
+            }
+            Match::String(value) => self.push_value(raw.unwrap_or(value)),
+            Match::Integer(value) => self.push_value(raw.unwrap_or(&value.to_string())),
+            Match::Float(value) => self.push_value(raw.unwrap_or(&compact_float(*value))),
+            Match::Boolean(value) => self.push_value(raw.unwrap_or(&value.to_string())),
+            Match::Null => self.push_value(raw.unwrap_or("null")),
+            _ => (),
+        }
+        if self.streaming && self.output.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Send everything that's been rendered so far down the pipeline
+    pub fn finish(mut self) -> ChiselResult<()> {
+        self.flush()
+    }
+
+    /// Add a comma if one is needed
+    fn separate(&mut self) {
+        if self.needs_comma {
+            self.output.push(',');
+        }
+    }
+
+    /// Add a scalar value
+    fn push_value(&mut self, text: &str) {
+        self.separate();
+        self.output.push_str(text);
+        self.needs_comma = true;
+    }
+
+    /// Send any pending output down the pipeline
+    fn flush(&mut self) -> ChiselResult<()> {
+        if self.output.is_empty() {
+            return Ok(());
+        }
+        let output = std::mem::take(&mut self.output);
+        self.submit_command_list(cl_immediate!(Draw::Text(output)))
+    }
+}
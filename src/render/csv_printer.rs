@@ -0,0 +1,63 @@
+//! CSV and TSV printer logic, used when converting records into delimited text
+//!
+//! CSV fields are quoted as per RFC 4180, and only where necessary. TSV has no quoting mechanism, so tabs, line
+//! breaks and backslashes within TSV fields are replaced with their usual backslash escapes instead
+use crate::cl_immediate;
+use crate::dom::compact_json;
+use crate::errors::{ChiselError, ChiselResult};
+use crate::escapes::{escape_tsv_field, quote_csv_field, unquote_json_string};
+use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode, Draw};
+use chisel_json::JsonValue;
+use std::sync::mpsc::Sender;
+
+/// Printer for rendering records as either CSV or TSV
+pub struct CsvPrinter {
+    /// The pipeline to render to
+    pub pipeline: Sender<DisplayList>,
+
+    /// Whether fields are separated by tabs rather than commas
+    pub tabs: bool,
+}
+
+/// The text of a single field, given the value it holds. Strings are written without their quotes, nulls are left
+/// empty and any objects or arrays are written out as compact JSON
+pub fn field_text(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(raw) => unquote_json_string(raw),
+        JsonValue::Null => String::new(),
+        value => compact_json(value),
+    }
+}
+
+impl CsvPrinter {
+    /// Construct a new instance, based on a supplied pipeline
+    pub fn new(pipeline: Sender<DisplayList>, tabs: bool) -> Self {
+        CsvPrinter { pipeline, tabs }
+    }
+
+    /// Chuck a [DisplayList] at the rendering pipeline and perform error conversion if necessary
+    #[inline]
+    fn submit_command_list(&self, cmds: DisplayList) -> ChiselResult<()> {
+        match self.pipeline.send(cmds) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ChiselError::DisplayListFailed),
+        }
+    }
+
+    /// Render a single record (or the header), quoting or escaping each field as required
+    pub fn render_record(&self, fields: &[String]) -> ChiselResult<()> {
+        let line = match self.tabs {
+            true => fields
+                .iter()
+                .map(|field| escape_tsv_field(field))
+                .collect::<Vec<String>>()
+                .join("\t"),
+            false => fields
+                .iter()
+                .map(|field| quote_csv_field(field))
+                .collect::<Vec<String>>()
+                .join(","),
+        };
+        self.submit_command_list(cl_immediate!(Draw::Text(line), Draw::NewLine))
+    }
+}
@@ -62,6 +62,8 @@ pub enum ChangeState {
     PopAlignment,
     /// Push an alignment
     PushAlignment(Alignment),
+    /// Set the character used for indentation
+    SetIndent(char),
     /// Terminate the
     Terminate,
 }
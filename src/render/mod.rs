@@ -1,5 +1,12 @@
+pub mod buffered_renderer;
+pub mod compact_printer;
+pub mod csv_printer;
 pub mod display_lists;
 pub mod options;
+pub mod ordering;
 pub mod pretty_printer;
 pub mod terminal_renderer;
 pub mod themes;
+pub mod toml_printer;
+pub mod xml_printer;
+pub mod yaml_printer;
@@ -3,11 +3,16 @@
 pub struct RenderOptions {
     /// Should raw mode be enabled?
     pub raw: bool,
+    /// Should colour changes be honoured?
+    pub colour: bool,
 }
 
 /// Setup a sensible set of defaults for the rendering options
 impl Default for RenderOptions {
     fn default() -> Self {
-        Self { raw: false }
+        Self {
+            raw: false,
+            colour: false,
+        }
     }
 }
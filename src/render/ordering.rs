@@ -0,0 +1,280 @@
+//! Orderings used when sorting object members and array elements on output
+//!
+//! Keys and strings are compared after unescaping, so that `"\u0041"` and `"A"` sort together. Scalars of different
+//! types are ranked null, false, true, numbers and then strings, with containers last of all
+use crate::dom::find_pointer;
+use crate::escapes::unquote_json_string;
+use chisel_json::JsonValue;
+use clap::ValueEnum;
+use feruca::Collator;
+use std::cmp::Ordering;
+
+/// The different orders that keys (and strings) can be sorted into
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum KeyOrder {
+    /// Order by Unicode code point
+    Lexicographic,
+    /// Order runs of digits by their numeric value, so that "item2" comes before "item10"
+    Natural,
+    /// Order using the Unicode Collation Algorithm, with the default (root) collation
+    Unicode,
+}
+
+/// Compares strings in a given [KeyOrder]. Collation needs some scratch space, so a comparer should be created for
+/// each sort rather than shared
+pub struct TextComparer {
+    /// The order to compare in
+    order: KeyOrder,
+
+    /// The collator, only used for [KeyOrder::Unicode]
+    collator: Option<Collator>,
+}
+
+impl TextComparer {
+    /// Create a new comparer for a given order
+    pub fn new(order: KeyOrder) -> Self {
+        TextComparer {
+            order,
+            collator: match order {
+                KeyOrder::Unicode => Some(Collator::default()),
+                _ => None,
+            },
+        }
+    }
+
+    /// Compare two (unescaped) strings. Strings which the order considers equal fall back to code point order, so
+    /// that sorting is deterministic
+    pub fn compare(&mut self, lhs: &str, rhs: &str) -> Ordering {
+        let ordering = match (self.order, &mut self.collator) {
+            (KeyOrder::Natural, _) => natural_cmp(lhs, rhs),
+            (KeyOrder::Unicode, Some(collator)) => collator.collate(lhs, rhs),
+            _ => Ordering::Equal,
+        };
+        ordering.then_with(|| lhs.cmp(rhs))
+    }
+}
+
+/// Compare two strings, treating each run of ASCII digits as a single number
+pub fn natural_cmp(lhs: &str, rhs: &str) -> Ordering {
+    let (mut lhs, mut rhs) = (lhs, rhs);
+    loop {
+        let (l, r) = match (lhs.chars().next(), rhs.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(l), Some(r)) => (l, r),
+        };
+
+        if l.is_ascii_digit() && r.is_ascii_digit() {
+            let (ldigits, lrest) = split_digits(lhs);
+            let (rdigits, rrest) = split_digits(rhs);
+
+            // with leading zeros out of the way, the longer run is the larger number
+            let (lvalue, rvalue) = (
+                ldigits.trim_start_matches('0'),
+                rdigits.trim_start_matches('0'),
+            );
+            let ordering = lvalue
+                .len()
+                .cmp(&rvalue.len())
+                .then_with(|| lvalue.cmp(rvalue));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            (lhs, rhs) = (lrest, rrest);
+        } else {
+            if l != r {
+                return l.cmp(&r);
+            }
+            (lhs, rhs) = (&lhs[l.len_utf8()..], &rhs[r.len_utf8()..]);
+        }
+    }
+}
+
+/// Split a string into its leading run of ASCII digits, and whatever follows
+fn split_digits(value: &str) -> (&str, &str) {
+    let end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    value.split_at(end)
+}
+
+/// The rank of a value's type, used to order values of different types
+fn type_rank(value: &JsonValue) -> u8 {
+    match value {
+        JsonValue::Null => 0,
+        JsonValue::Boolean(false) => 1,
+        JsonValue::Boolean(true) => 2,
+        JsonValue::Integer(_) | JsonValue::Float(_) => 3,
+        JsonValue::String(_) => 4,
+        JsonValue::Object(_) | JsonValue::Array(_) => 5,
+    }
+}
+
+/// Work out whether a value is a scalar, i.e. neither an object nor an array
+pub fn is_scalar(value: &JsonValue) -> bool {
+    type_rank(value) < 5
+}
+
+/// Compare two values by type, and then by value for numbers and strings. Containers all compare as equal
+pub fn compare_values(comparer: &mut TextComparer, lhs: &JsonValue, rhs: &JsonValue) -> Ordering {
+    let ordering = type_rank(lhs).cmp(&type_rank(rhs));
+    if ordering != Ordering::Equal {
+        return ordering;
+    }
+    match (lhs, rhs) {
+        (JsonValue::Integer(l), JsonValue::Integer(r)) => l.cmp(r),
+        (JsonValue::Integer(l), JsonValue::Float(r)) => (*l as f64).total_cmp(r),
+        (JsonValue::Float(l), JsonValue::Integer(r)) => l.total_cmp(&(*r as f64)),
+        (JsonValue::Float(l), JsonValue::Float(r)) => l.total_cmp(r),
+        (JsonValue::String(l), JsonValue::String(r)) => {
+            comparer.compare(&unquote_json_string(l), &unquote_json_string(r))
+        }
+        _ => Ordering::Equal,
+    }
+}
+
+/// Sort the members of an object by key
+pub fn sort_members<'a>(order: KeyOrder, members: &mut Vec<(String, JsonValue<'a>)>) {
+    let mut comparer = TextComparer::new(order);
+    let mut keyed: Vec<(String, (String, JsonValue<'a>))> = members
+        .drain(..)
+        .map(|member| (unquote_json_string(&member.0), member))
+        .collect();
+    keyed.sort_by(|(l, _), (r, _)| comparer.compare(l, r));
+    members.extend(keyed.into_iter().map(|(_, member)| member));
+}
+
+/// Sort an array of scalars by value. Arrays holding any objects or arrays are left alone
+pub fn sort_scalars(order: KeyOrder, values: &mut [JsonValue]) {
+    if values.iter().all(is_scalar) {
+        let mut comparer = TextComparer::new(order);
+        values.sort_by(|l, r| compare_values(&mut comparer, l, r));
+    }
+}
+
+/// Sort an array of objects by the value found at a pointer (given as reference tokens) within each object.
+/// Objects where the pointer doesn't resolve go last, and the sort is stable so they keep their relative order.
+/// Arrays holding anything other than objects are left alone
+pub fn sort_objects_by(order: KeyOrder, tokens: &[String], values: &mut [JsonValue]) {
+    if values.iter().all(|v| matches!(v, JsonValue::Object(_))) {
+        let mut comparer = TextComparer::new(order);
+        values.sort_by(
+            |l, r| match (find_pointer(l, tokens), find_pointer(r, tokens)) {
+                (Some(l), Some(r)) => compare_values(&mut comparer, l, r),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::compact_json;
+    use crate::dom::parse_literal;
+
+    #[test]
+    fn natural_order_compares_digit_runs_numerically() {
+        assert_eq!(natural_cmp("item2", "item10"), Ordering::Less);
+        assert_eq!(natural_cmp("item10", "item2"), Ordering::Greater);
+        assert_eq!(natural_cmp("item10", "item10"), Ordering::Equal);
+        assert_eq!(natural_cmp("a2b10", "a2b9"), Ordering::Greater);
+        assert_eq!(natural_cmp("9", "10"), Ordering::Less);
+        assert_eq!(
+            natural_cmp("18446744073709551616", "18446744073709551615"),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn natural_order_ignores_leading_zeros() {
+        assert_eq!(natural_cmp("a01", "a1"), Ordering::Equal);
+        assert_eq!(natural_cmp("a007", "a10"), Ordering::Less);
+        assert_eq!(natural_cmp("0", "000"), Ordering::Equal);
+
+        // the comparer breaks ties by code point, so that equal numbers still sort deterministically
+        let mut comparer = TextComparer::new(KeyOrder::Natural);
+        assert_eq!(comparer.compare("a01", "a1"), Ordering::Less);
+        assert_eq!(comparer.compare("a1", "a01"), Ordering::Greater);
+    }
+
+    #[test]
+    fn natural_order_compares_text_by_code_point() {
+        assert_eq!(natural_cmp("abc", "abd"), Ordering::Less);
+        assert_eq!(natural_cmp("ab", "abc"), Ordering::Less);
+        assert_eq!(natural_cmp("", "a"), Ordering::Less);
+        assert_eq!(natural_cmp("B", "a"), Ordering::Less);
+        assert_eq!(natural_cmp("a1", "aa"), Ordering::Less);
+        assert_eq!(natural_cmp("é2", "é10"), Ordering::Less);
+    }
+
+    #[test]
+    fn sorts_members_in_each_order() {
+        let keys = |order: KeyOrder| {
+            let mut members: Vec<(String, JsonValue)> =
+                ["\"b\"", "\"item10\"", "\"B\"", "\"item2\"", "\"\\u0061\""]
+                    .iter()
+                    .map(|key| (key.to_string(), JsonValue::Null))
+                    .collect();
+            sort_members(order, &mut members);
+            members
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            keys(KeyOrder::Lexicographic),
+            ["\"B\"", "\"\\u0061\"", "\"b\"", "\"item10\"", "\"item2\""]
+        );
+        assert_eq!(
+            keys(KeyOrder::Natural),
+            ["\"B\"", "\"\\u0061\"", "\"b\"", "\"item2\"", "\"item10\""]
+        );
+        assert_eq!(
+            keys(KeyOrder::Unicode),
+            ["\"\\u0061\"", "\"b\"", "\"B\"", "\"item10\"", "\"item2\""]
+        );
+    }
+
+    #[test]
+    fn sorts_scalars_by_type_then_value() {
+        let mut values =
+            match parse_literal(r#"["b", 2, null, true, 1.5, "a", false, 10]"#).unwrap() {
+                JsonValue::Array(values) => values,
+                _ => unreachable!(),
+            };
+        sort_scalars(KeyOrder::Lexicographic, &mut values);
+        assert_eq!(
+            compact_json(&JsonValue::Array(values)),
+            r#"[null,false,true,1.5,2,10,"a","b"]"#
+        );
+
+        // arrays holding containers are left as they are
+        let mut values = match parse_literal(r#"[2, [1], 1]"#).unwrap() {
+            JsonValue::Array(values) => values,
+            _ => unreachable!(),
+        };
+        sort_scalars(KeyOrder::Lexicographic, &mut values);
+        assert_eq!(compact_json(&JsonValue::Array(values)), "[2,[1],1]");
+    }
+
+    #[test]
+    fn sorts_objects_by_pointer() {
+        let mut values = match parse_literal(
+            r#"[{"n": "x10"}, {"m": 1}, {"n": "x9"}, {"n": {"deep": 1}}, {"n": "x1"}]"#,
+        )
+        .unwrap()
+        {
+            JsonValue::Array(values) => values,
+            _ => unreachable!(),
+        };
+        sort_objects_by(KeyOrder::Natural, &[String::from("n")], &mut values);
+        assert_eq!(
+            compact_json(&JsonValue::Array(values)),
+            r#"[{"n":"x1"},{"n":"x9"},{"n":"x10"},{"n":{"deep":1}},{"m":1}]"#
+        );
+    }
+}
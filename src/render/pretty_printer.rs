@@ -1,8 +1,15 @@
 //! Pretty-printer logic for use by various commands
 use crate::cl_immediate;
+use crate::dom::{canonical_json, compact_json};
 use crate::errors::{ChiselError, ChiselResult};
-use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode, Draw};
+use crate::render::display_lists::{
+    ChangeState, DisplayList, DisplayListCommand, DisplayListMode, Draw,
+};
+use crate::render::ordering::{sort_members, sort_objects_by, sort_scalars, KeyOrder};
+use crate::render::themes::{Colour, DATE_COLOUR, DECIMAL_COLOUR, OBJECT_ID_COLOUR};
+use crate::state;
 use chisel_json::JsonValue;
+use clap::ValueEnum;
 use std::sync::mpsc::Sender;
 
 /// Options that control the output from a given printer instance
@@ -12,6 +19,33 @@ pub struct FormatOptions {
 
     /// The padding between KV pairs
     pub kvpadding: u16,
+
+    /// The order to sort object keys into, if they're to be sorted at all
+    pub sort_keys: Option<KeyOrder>,
+
+    /// Whether arrays made up entirely of scalars are sorted by value
+    pub sort_arrays: bool,
+
+    /// The reference tokens of a pointer, used to sort arrays made up entirely of objects
+    pub sort_arrays_by: Option<Vec<String>>,
+
+    /// The width available for output. If set, objects and arrays are drawn on a single line wherever they fit
+    pub width: Option<usize>,
+
+    /// The character used for indentation
+    pub indent_char: IndentChar,
+
+    /// Whether the ":" of every pair within an object is lined up, by padding out the shorter keys
+    pub align_colons: bool,
+
+    /// Where the opening braces and brackets of objects and arrays are placed
+    pub braces: BraceStyle,
+
+    /// Whether a line ending is added after the document
+    pub final_newline: bool,
+
+    /// The line ending to use
+    pub line_ending: LineEnding,
 }
 
 /// Default implementation uses some sensible default for the various options
@@ -20,10 +54,145 @@ impl Default for FormatOptions {
         Self {
             indent: 2,
             kvpadding: 1,
+            sort_keys: None,
+            sort_arrays: false,
+            sort_arrays_by: None,
+            width: None,
+            indent_char: IndentChar::Space,
+            align_colons: false,
+            braces: BraceStyle::Kr,
+            final_newline: false,
+            line_ending: LineEnding::Lf,
         }
     }
 }
 
+/// The characters that can be used for indentation
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum IndentChar {
+    /// Indent using spaces
+    Space,
+    /// Indent using tabs
+    Tab,
+}
+
+impl IndentChar {
+    /// The character itself
+    fn char(self) -> char {
+        match self {
+            IndentChar::Space => ' ',
+            IndentChar::Tab => '\t',
+        }
+    }
+
+    /// The number of columns taken up by the character, assuming tab stops every 8 columns
+    fn columns(self) -> usize {
+        match self {
+            IndentChar::Space => 1,
+            IndentChar::Tab => 8,
+        }
+    }
+}
+
+/// The placement of opening braces and brackets
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum BraceStyle {
+    /// K&R style, where an opening brace follows on from its key
+    Kr,
+    /// Allman style, where an opening brace goes on a line of its own, beneath its key
+    Allman,
+}
+
+/// The line endings that can be used
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum LineEnding {
+    /// Unix style line endings
+    Lf,
+    /// Windows style line endings
+    Crlf,
+}
+
+impl LineEnding {
+    /// Draw the line ending
+    pub fn draw(self) -> Draw {
+        match self {
+            LineEnding::Lf => Draw::NewLine,
+            LineEnding::Crlf => Draw::Slice("\r\n"),
+        }
+    }
+}
+
+/// How a value is laid out
+#[derive(Debug, Copy, Clone)]
+enum Layout {
+    /// Spread over multiple lines, at a given indent level and starting from a given column. Values following a key
+    /// are breakable if they're moved on to a line of their own (as per Allman style) when expanded, in which case
+    /// the padding after the ":" is left for the value to draw
+    Expanded {
+        level: u16,
+        column: usize,
+        breakable: bool,
+    },
+
+    /// All on the current line
+    Inline,
+}
+
+/// Take the width of some text out of what's left of the budget, failing if there isn't enough left
+fn spend(budget: &mut usize, width: usize) -> bool {
+    match budget.checked_sub(width) {
+        Some(remaining) => {
+            *budget = remaining;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Work out whether a value fits within the budget when drawn on a single line, taking its width out of the budget.
+/// Measuring stops as soon as the budget runs out, so large containers are never measured in full
+fn fits_inline(value: &JsonValue, kvpadding: usize, budget: &mut usize) -> bool {
+    match value {
+        JsonValue::Object(kids) => fits_members(kids, kvpadding, budget),
+        JsonValue::Array(kids) => fits_elements(kids, kvpadding, budget),
+        JsonValue::String(raw) => spend(budget, raw.chars().count()),
+        JsonValue::Float(value) => spend(budget, value.to_string().len()),
+        JsonValue::Integer(value) => spend(budget, value.to_string().len()),
+        JsonValue::Boolean(value) => spend(budget, if *value { 4 } else { 5 }),
+        JsonValue::Null => spend(budget, 4),
+    }
+}
+
+/// Work out whether an array fits within the budget, with its elements separated by ", "
+fn fits_elements(kids: &[JsonValue], kvpadding: usize, budget: &mut usize) -> bool {
+    spend(budget, 2 * kids.len().max(1))
+        && kids
+            .iter()
+            .all(|value| fits_inline(value, kvpadding, budget))
+}
+
+/// Work out whether an object fits within the budget, with its members separated by ", "
+fn fits_members(kids: &[(String, JsonValue)], kvpadding: usize, budget: &mut usize) -> bool {
+    spend(budget, 2 * kids.len().max(1))
+        && kids.iter().all(|(key, value)| {
+            spend(budget, key.chars().count() + 2 * kvpadding + 1)
+                && fits_inline(value, kvpadding, budget)
+        })
+}
+
+/// Work out whether an object is an Extended JSON wrapper for one of the BSON types that's given its own colour
+fn extended_json_colour(kids: &[(String, JsonValue)]) -> Option<Colour> {
+    match kids {
+        [(key, _)] => match key.as_str() {
+            "\"$oid\"" => Some(OBJECT_ID_COLOUR),
+            "\"$date\"" => Some(DATE_COLOUR),
+            "\"$numberDecimal\"" => Some(DECIMAL_COLOUR),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 /// Pretty printer for [JsonValue]s
 pub struct PrettyPrinter {
     /// The [ActionContext] associated with the printer
@@ -48,16 +217,92 @@ impl PrettyPrinter {
         }
     }
 
+    /// Send a single state change down the rendering pipeline
+    fn submit_state_change(&self, change: ChangeState) -> ChiselResult<()> {
+        self.submit_command_list(DisplayList {
+            mode: DisplayListMode::Immediate,
+            cmds: vec![state!(change)],
+        })
+    }
+
     /// Recursively render a JSON value
     pub fn render_json(&self, value: JsonValue) -> ChiselResult<()> {
-        self.render_json_value(0, value)
+        self.submit_state_change(ChangeState::SetIndent(self.options.indent_char.char()))?;
+        self.render_json_value(
+            Layout::Expanded {
+                level: 0,
+                column: 0,
+                breakable: false,
+            },
+            value,
+        )?;
+        self.render_final_newline()
+    }
+
+    /// Draw a line ending
+    fn newline(&self) -> Draw {
+        self.options.line_ending.draw()
+    }
+
+    /// Draw the indentation for a given level
+    fn indent(&self, level: u16) -> Draw {
+        Draw::Indent(level * self.options.indent)
+    }
+
+    /// Draw the padding either side of a ":"
+    fn padding(&self) -> Draw {
+        Draw::Repeat(' ', self.options.kvpadding)
+    }
+
+    /// The column reached after indenting to a given level
+    fn indent_columns(&self, level: u16) -> usize {
+        (level * self.options.indent) as usize * self.options.indent_char.columns()
+    }
+
+    /// Draw whatever leads up to a container. A breakable container is moved on to a line of its own if it's
+    /// expanded, otherwise it follows on from its key after the usual padding
+    fn render_lead(&self, layout: Layout, expanded: bool) -> ChiselResult<()> {
+        match layout {
+            Layout::Expanded {
+                level,
+                breakable: true,
+                ..
+            } => match expanded {
+                true => self.submit_command_list(cl_immediate!(self.newline(), self.indent(level))),
+                false => self.submit_command_list(cl_immediate!(self.padding())),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Render a JSON value as compact JSON on a line of its own, as used for NDJSON output
+    pub fn render_json_line(&self, value: &JsonValue) -> ChiselResult<()> {
+        self.submit_command_list(cl_immediate!(
+            Draw::Text(compact_json(value)),
+            Draw::NewLine
+        ))
+    }
+
+    /// Render a JSON value in its canonical (RFC 8785) form. The canonical form has no whitespace of its own, but
+    /// the final newline and line ending options are still honoured
+    pub fn render_json_canonical(&self, value: &JsonValue) -> ChiselResult<()> {
+        self.submit_command_list(cl_immediate!(Draw::Text(canonical_json(value)?)))?;
+        self.render_final_newline()
+    }
+
+    /// Draw a line ending after a document, if required
+    fn render_final_newline(&self) -> ChiselResult<()> {
+        match self.options.final_newline {
+            true => self.submit_command_list(cl_immediate!(self.newline())),
+            false => Ok(()),
+        }
     }
 
     /// Draw a [JsonValue]
-    fn render_json_value(&self, level: u16, value: JsonValue) -> ChiselResult<()> {
+    fn render_json_value(&self, layout: Layout, value: JsonValue) -> ChiselResult<()> {
         match value {
-            JsonValue::Object(kids) => self.render_json_object(level, kids),
-            JsonValue::Array(kids) => self.render_json_array(level, kids),
+            JsonValue::Object(kids) => self.render_json_object(layout, kids),
+            JsonValue::Array(kids) => self.render_json_array(layout, kids),
             JsonValue::String(value) => self.render_json_string(value.into_owned()),
             JsonValue::Float(value) => self.render_json_float(value),
             JsonValue::Integer(value) => self.render_json_integer(value),
@@ -66,49 +311,100 @@ impl PrettyPrinter {
         }
     }
 
-    /// Draw a json array
-    fn render_json_array(&self, level: u16, kids: Vec<JsonValue>) -> ChiselResult<()> {
+    /// Decide how a container is laid out. Anything within an inline container is also inline, otherwise a
+    /// container is only inline if its single line form (plus a trailing comma) fits within the width, starting from
+    /// the column it's drawn at
+    fn container_layout(
+        &self,
+        layout: Layout,
+        fits: impl FnOnce(usize, &mut usize) -> bool,
+    ) -> Layout {
+        match (layout, self.options.width) {
+            (Layout::Expanded { column, .. }, Some(width)) => {
+                let mut budget = width.saturating_sub(column + 1);
+                match fits(self.options.kvpadding as usize, &mut budget) {
+                    true => Layout::Inline,
+                    false => layout,
+                }
+            }
+            _ => layout,
+        }
+    }
+
+    /// The order used for strings when sorting array elements, which follows the key order if there is one
+    fn value_order(&self) -> KeyOrder {
+        self.options.sort_keys.unwrap_or(KeyOrder::Lexicographic)
+    }
+
+    /// Draw a json array, sorting its elements first if required
+    fn render_json_array(&self, layout: Layout, mut kids: Vec<JsonValue>) -> ChiselResult<()> {
+        if let Some(tokens) = &self.options.sort_arrays_by {
+            sort_objects_by(self.value_order(), tokens, &mut kids);
+        }
+        if self.options.sort_arrays {
+            sort_scalars(self.value_order(), &mut kids);
+        }
+
+        let level = match self.container_layout(layout, |padding, budget| {
+            fits_elements(&kids, padding, budget)
+        }) {
+            Layout::Expanded { level, .. } => {
+                self.render_lead(layout, true)?;
+                level
+            }
+            Layout::Inline => {
+                self.render_lead(layout, false)?;
+                return self.render_inline_array(kids);
+            }
+        };
+
         let kidcount = kids.len();
         let empty = kids.is_empty();
 
         // opening bracket
         if !empty {
-            self.submit_command_list(cl_immediate!(Draw::Char('['), Draw::NewLine))?;
+            self.submit_command_list(cl_immediate!(Draw::Char('['), self.newline()))?;
         } else {
             self.submit_command_list(cl_immediate!(Draw::Char('['),))?;
         }
 
         for (i, value) in kids.into_iter().enumerate() {
-            self.submit_command_list(cl_immediate!(Draw::Indent(
-                (level + 1) * self.options.indent
-            )))?;
-            match value {
-                JsonValue::Object(pairs) => self.render_json_object(level + 1, pairs)?,
-                JsonValue::Array(kids) => self.render_json_array(level + 1, kids)?,
-                JsonValue::String(value) => self.render_json_string(value.into_owned())?,
-                JsonValue::Float(value) => self.render_json_float(value)?,
-                JsonValue::Integer(value) => self.render_json_integer(value)?,
-                JsonValue::Boolean(value) => self.render_json_boolean(value)?,
-                JsonValue::Null => self.render_json_null()?,
-            }
+            self.submit_command_list(cl_immediate!(self.indent(level + 1)))?;
+            self.render_json_value(
+                Layout::Expanded {
+                    level: level + 1,
+                    column: self.indent_columns(level + 1),
+                    breakable: false,
+                },
+                value,
+            )?;
             if i != kidcount - 1 {
-                self.submit_command_list(cl_immediate!(Draw::Char(','), Draw::NewLine))?
+                self.submit_command_list(cl_immediate!(Draw::Char(','), self.newline()))?
             } else {
-                self.submit_command_list(cl_immediate!(Draw::NewLine))?
+                self.submit_command_list(cl_immediate!(self.newline()))?
             }
         }
 
         // closing bracket
         if !empty {
-            self.submit_command_list(cl_immediate!(
-                Draw::Indent(level * self.options.indent),
-                Draw::Char(']'),
-            ))
+            self.submit_command_list(cl_immediate!(self.indent(level), Draw::Char(']'),))
         } else {
             self.submit_command_list(cl_immediate!(Draw::Char(']'),))
         }
     }
 
+    /// Draw a json array on a single line
+    fn render_inline_array(&self, kids: Vec<JsonValue>) -> ChiselResult<()> {
+        self.submit_command_list(cl_immediate!(Draw::Char('[')))?;
+        for (i, value) in kids.into_iter().enumerate() {
+            if i > 0 {
+                self.submit_command_list(cl_immediate!(Draw::Slice(", ")))?;
+            }
+            self.render_json_value(Layout::Inline, value)?;
+        }
+        self.submit_command_list(cl_immediate!(Draw::Char(']')))
+    }
+
     /// Draw a string value
     fn render_json_string(&self, value: String) -> ChiselResult<()> {
         self.submit_command_list(cl_immediate!(Draw::Text(value)))
@@ -138,64 +434,139 @@ impl PrettyPrinter {
         self.submit_command_list(cl_immediate!(Draw::Slice("null")))
     }
 
+    /// Draw an object, sorting its members by key if required, and picking out the Extended JSON wrappers for
+    /// ObjectIds, dates and decimals in their own colours
+    fn render_json_object(
+        &self,
+        layout: Layout,
+        mut kids: Vec<(String, JsonValue)>,
+    ) -> ChiselResult<()> {
+        if let Some(order) = self.options.sort_keys {
+            sort_members(order, &mut kids);
+        }
+        match extended_json_colour(&kids) {
+            Some((r, g, b)) => {
+                self.submit_state_change(ChangeState::PushForegroundColour(r, g, b))?;
+                self.render_json_members(layout, kids)?;
+                self.submit_state_change(ChangeState::PopForegroundColour)
+            }
+            None => self.render_json_members(layout, kids),
+        }
+    }
+
     /// Surround an object with braces at the correct indent level, and recursively render
     /// children at the next indent level
-    fn render_json_object(&self, level: u16, kids: Vec<(String, JsonValue)>) -> ChiselResult<()> {
+    fn render_json_members(
+        &self,
+        layout: Layout,
+        kids: Vec<(String, JsonValue)>,
+    ) -> ChiselResult<()> {
+        let level = match self.container_layout(layout, |padding, budget| {
+            fits_members(&kids, padding, budget)
+        }) {
+            Layout::Expanded { level, .. } => {
+                self.render_lead(layout, true)?;
+                level
+            }
+            Layout::Inline => {
+                self.render_lead(layout, false)?;
+                return self.render_inline_members(kids);
+            }
+        };
+
         let kidcount = kids.len();
         let empty = kids.is_empty();
 
         // opening brace
         if !empty {
-            self.submit_command_list(cl_immediate!(Draw::Char('{'), Draw::NewLine))?;
+            self.submit_command_list(cl_immediate!(Draw::Char('{'), self.newline()))?;
         } else {
             self.submit_command_list(cl_immediate!(Draw::Char('{'),))?;
         }
 
-        // render the kids
+        // render the kids, lining up their keys if required
+        let key_width = match self.options.align_colons {
+            true => kids.iter().map(|(key, _)| key.chars().count()).max(),
+            false => None,
+        };
         for (i, (key, value)) in kids.into_iter().enumerate() {
             if i == kidcount - 1 {
-                self.render_json_pair(level + 1, false, key, value)?;
+                self.render_json_pair(level + 1, false, key_width, key, value)?;
             } else {
-                self.render_json_pair(level + 1, true, key, value)?;
+                self.render_json_pair(level + 1, true, key_width, key, value)?;
             }
         }
 
         // closing brace with optional newline
         if !empty {
-            self.submit_command_list(cl_immediate!(
-                Draw::Indent(level * self.options.indent),
-                Draw::Char('}'),
-            ))
+            self.submit_command_list(cl_immediate!(self.indent(level), Draw::Char('}'),))
         } else {
             self.submit_command_list(cl_immediate!(Draw::Char('}'),))
         }
     }
 
-    /// Output a KV pair from within an object
+    /// Surround an object with braces, with all of its members on a single line
+    fn render_inline_members(&self, kids: Vec<(String, JsonValue)>) -> ChiselResult<()> {
+        self.submit_command_list(cl_immediate!(Draw::Char('{')))?;
+        for (i, (key, value)) in kids.into_iter().enumerate() {
+            if i > 0 {
+                self.submit_command_list(cl_immediate!(Draw::Slice(", ")))?;
+            }
+            self.submit_command_list(cl_immediate!(
+                Draw::Text(key),
+                self.padding(),
+                Draw::Slice(":"),
+                self.padding(),
+            ))?;
+            self.render_json_value(Layout::Inline, value)?;
+        }
+        self.submit_command_list(cl_immediate!(Draw::Char('}')))
+    }
+
+    /// Output a KV pair from within an object, padding the key out to a given width if required
     fn render_json_pair(
         &self,
         level: u16,
         trailing: bool,
+        key_width: Option<usize>,
         key: String,
         value: JsonValue,
     ) -> ChiselResult<()> {
-        // the key
+        // the key, after which the value starts
+        let key_width = key_width.unwrap_or(0).max(key.chars().count());
+        let column =
+            self.indent_columns(level) + key_width + 2 * self.options.kvpadding as usize + 1;
         self.submit_command_list(cl_immediate!(
-            Draw::Indent(level * self.options.indent),
-            Draw::Text(key.to_string()),
-            Draw::Indent(self.options.kvpadding),
+            self.indent(level),
+            Draw::Text(format!("{:width$}", key, width = key_width)),
+            self.padding(),
             Draw::Slice(":"),
-            Draw::Indent(self.options.kvpadding),
         ))?;
 
-        // the value
-        self.render_json_value(level, value)?;
+        // the value, which draws its own padding if it might be moved on to the next line
+        let breakable = self.options.braces == BraceStyle::Allman
+            && match &value {
+                JsonValue::Object(kids) => !kids.is_empty(),
+                JsonValue::Array(kids) => !kids.is_empty(),
+                _ => false,
+            };
+        if !breakable {
+            self.submit_command_list(cl_immediate!(self.padding()))?;
+        }
+        self.render_json_value(
+            Layout::Expanded {
+                level,
+                column,
+                breakable,
+            },
+            value,
+        )?;
 
         // add trailing comma as required
         if trailing {
-            self.submit_command_list(cl_immediate!(Draw::Char(','), Draw::NewLine))?;
+            self.submit_command_list(cl_immediate!(Draw::Char(','), self.newline()))?;
         } else {
-            self.submit_command_list(cl_immediate!(Draw::NewLine))?;
+            self.submit_command_list(cl_immediate!(self.newline()))?;
         }
 
         Ok(())
@@ -2,6 +2,8 @@
 use super::display_lists::{ChangeState, DisplayList, DisplayListCommand, DisplayListMode, Draw};
 use super::{options::RenderOptions, themes::Theme};
 use crate::threads::AppThread;
+use crossterm::queue;
+use crossterm::style::{Color, ResetColor, SetForegroundColor};
 use crossterm::terminal;
 use std::io::{stdout, Write};
 use std::sync::mpsc::{channel, Receiver};
@@ -39,7 +41,7 @@ fn initial_render_state(options: &RenderOptions) -> RenderState {
     RenderState {
         options: *options,
         control_code: LoopControlCode::Continue,
-        theme: Theme { indent: ' ' },
+        theme: Theme::default(),
     }
 }
 
@@ -54,26 +56,23 @@ fn render(options: RenderOptions, pipeline: Receiver<DisplayList>) {
     // default to stdout but this may become pluggable in the future
     let mut stdout = stdout();
     loop {
-        match pipeline.recv() {
-            Ok(list) => {
-                if list.mode == DisplayListMode::Immediate {
-                    for cmd in list.cmds {
-                        state = match cmd {
-                            DisplayListCommand::ChangeState(inner) => {
-                                handle_state_command(&mut state, &inner)
-                            }
-                            DisplayListCommand::Draw(inner) => {
-                                handle_render_command(&mut stdout, &mut state, &inner)
-                            }
-                        };
-                        if state.control_code == LoopControlCode::Terminate {
-                            terminal::disable_raw_mode().unwrap();
-                            return;
+        if let Ok(list) = pipeline.recv() {
+            if list.mode == DisplayListMode::Immediate {
+                for cmd in list.cmds {
+                    state = match cmd {
+                        DisplayListCommand::ChangeState(inner) => {
+                            handle_state_command(&mut stdout, &mut state, &inner)
                         }
+                        DisplayListCommand::Draw(inner) => {
+                            handle_render_command(&mut stdout, &mut state, &inner)
+                        }
+                    };
+                    if state.control_code == LoopControlCode::Terminate {
+                        terminal::disable_raw_mode().unwrap();
+                        return;
                     }
                 }
             }
-            Err(_) => (),
         }
     }
 }
@@ -85,13 +84,34 @@ fn update_render_state(state: &mut RenderState) -> RenderState {
     *state
 }
 
-/// Handle any [DisplayListCommand::ChangeState] commands
+/// Handle any [DisplayListCommand::ChangeState] commands. Colours are only applied if enabled within the
+/// [RenderOptions], and don't nest, so popping a colour always reverts to the terminal default
 #[cfg(feature = "crossterm")]
-fn handle_state_command(state: &mut RenderState, cmd: &ChangeState) -> RenderState {
-    match cmd {
-        ChangeState::Terminate => state.control_code = LoopControlCode::Terminate,
-        _ => (),
-    }
+fn handle_state_command<W: Write>(
+    out: &mut W,
+    state: &mut RenderState,
+    cmd: &ChangeState,
+) -> RenderState {
+    let _result = match cmd {
+        ChangeState::Terminate => {
+            state.control_code = LoopControlCode::Terminate;
+            Ok(())
+        }
+        ChangeState::SetIndent(indent) => {
+            state.theme.indent = *indent;
+            Ok(())
+        }
+        ChangeState::PushForegroundColour(r, g, b) if state.options.colour => queue!(
+            out,
+            SetForegroundColor(Color::Rgb {
+                r: *r,
+                g: *g,
+                b: *b
+            })
+        ),
+        ChangeState::PopForegroundColour if state.options.colour => queue!(out, ResetColor),
+        _ => Ok(()),
+    };
     update_render_state(state)
 }
 
@@ -99,7 +119,7 @@ fn handle_state_command(state: &mut RenderState, cmd: &ChangeState) -> RenderSta
 #[cfg(feature = "crossterm")]
 fn handle_render_command(out: &mut dyn Write, state: &mut RenderState, cmd: &Draw) -> RenderState {
     let _result = match cmd {
-        Draw::NewLine => write!(out, "\n"),
+        Draw::NewLine => writeln!(out),
         Draw::Indent(n) => {
             for _ in 0..*n {
                 let _ = write!(out, "{}", state.theme.indent);
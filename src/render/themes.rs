@@ -2,3 +2,24 @@
 pub struct Theme {
     pub indent: char,
 }
+
+/// Themes start off indenting with spaces, until told otherwise by a [ChangeState::SetIndent]
+///
+/// [ChangeState::SetIndent]: super::display_lists::ChangeState::SetIndent
+impl Default for Theme {
+    fn default() -> Self {
+        Self { indent: ' ' }
+    }
+}
+
+/// An RGB colour
+pub type Colour = (u8, u8, u8);
+
+/// The colour used for BSON ObjectIds
+pub const OBJECT_ID_COLOUR: Colour = (229, 192, 123);
+
+/// The colour used for BSON dates
+pub const DATE_COLOUR: Colour = (97, 175, 239);
+
+/// The colour used for BSON Decimal128 values
+pub const DECIMAL_COLOUR: Colour = (198, 120, 221);
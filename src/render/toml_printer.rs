@@ -0,0 +1,224 @@
+//! TOML printer logic, used when converting documents into TOML 1.0
+//!
+//! Objects become tables, and arrays of objects become arrays of tables, with everything else being written inline.
+//! Within each table, any values that can be written inline are placed ahead of the sub-tables, as TOML requires.
+//! Documents which can't be represented (anything other than an object at the root, nulls and arrays holding values
+//! of different types) are rejected up front, before any output is produced
+use crate::cl_immediate;
+use crate::errors::{ChiselError, ChiselResult};
+use crate::escapes::{quote_json_string, unquote_json_string};
+use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode, Draw};
+use chisel_json::JsonValue;
+use std::sync::mpsc::Sender;
+
+/// Printer for rendering [JsonValue]s as TOML
+pub struct TomlPrinter {
+    /// The pipeline to render to
+    pub pipeline: Sender<DisplayList>,
+}
+
+/// The name of the TOML type that a value maps onto
+fn type_name(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Object(_) => "table",
+        JsonValue::Array(_) => "array",
+        JsonValue::String(_) => "string",
+        JsonValue::Integer(_) => "integer",
+        JsonValue::Float(_) => "float",
+        JsonValue::Boolean(_) => "boolean",
+        JsonValue::Null => "null",
+    }
+}
+
+/// Check that a value (and everything beneath it) can be represented, given its JSON pointer
+fn check_value(value: &JsonValue, pointer: &mut String) -> ChiselResult<()> {
+    let length = pointer.len();
+    match value {
+        JsonValue::Null => {
+            return Err(ChiselError::NotRepresentable(format!(
+                "null value at \"{}\"",
+                pointer
+            )))
+        }
+        JsonValue::Object(pairs) => {
+            for (key, value) in pairs {
+                pointer.push('/');
+                pointer.push_str(
+                    &unquote_json_string(key)
+                        .replace('~', "~0")
+                        .replace('/', "~1"),
+                );
+                check_value(value, pointer)?;
+                pointer.truncate(length);
+            }
+        }
+        JsonValue::Array(values) => {
+            if let Some(first) = values.first() {
+                if values.iter().any(|v| type_name(v) != type_name(first)) {
+                    return Err(ChiselError::NotRepresentable(format!(
+                        "mixed-type array at \"{}\"",
+                        pointer
+                    )));
+                }
+            }
+            for (index, value) in values.iter().enumerate() {
+                pointer.push_str(&format!("/{}", index));
+                check_value(value, pointer)?;
+                pointer.truncate(length);
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+/// Checks whether a value is written out as a table (or an array of tables) rather than inline
+fn is_table(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Object(_) => true,
+        JsonValue::Array(values) => {
+            !values.is_empty() && values.iter().all(|v| matches!(v, JsonValue::Object(_)))
+        }
+        _ => false,
+    }
+}
+
+/// Format a (decoded) key, quoting it unless it's a valid bare key
+fn key_text(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_string()
+    } else {
+        basic_string(key)
+    }
+}
+
+/// Format a (decoded) string as a TOML basic string. JSON escapes are almost all valid in TOML, the only difference
+/// being that TOML also requires the delete character to be escaped
+fn basic_string(value: &str) -> String {
+    quote_json_string(value).replace('\u{7f}', "\\u007f")
+}
+
+/// Format a value for use inline, within a key/value pair or an array
+fn inline_text(value: JsonValue) -> String {
+    match value {
+        JsonValue::Object(pairs) if pairs.is_empty() => String::from("{}"),
+        JsonValue::Object(pairs) => {
+            let members: Vec<String> = pairs
+                .into_iter()
+                .map(|(key, value)| {
+                    format!(
+                        "{} = {}",
+                        key_text(&unquote_json_string(&key)),
+                        inline_text(value)
+                    )
+                })
+                .collect();
+            format!("{{ {} }}", members.join(", "))
+        }
+        JsonValue::Array(values) => {
+            let values: Vec<String> = values.into_iter().map(inline_text).collect();
+            format!("[{}]", values.join(", "))
+        }
+        JsonValue::String(raw) => basic_string(&unquote_json_string(&raw)),
+        JsonValue::Integer(value) => value.to_string(),
+        JsonValue::Float(value) => {
+            // TOML floats must have either a fractional part or an exponent
+            let text = value.to_string();
+            if text.contains(['.', 'e', 'E']) || !value.is_finite() {
+                text
+            } else {
+                format!("{}.0", text)
+            }
+        }
+        JsonValue::Boolean(value) => value.to_string(),
+        JsonValue::Null => String::from("null"),
+    }
+}
+
+impl TomlPrinter {
+    /// Construct a new instance, based on a supplied pipeline
+    pub fn new(pipeline: Sender<DisplayList>) -> Self {
+        TomlPrinter { pipeline }
+    }
+
+    /// Chuck a [DisplayList] at the rendering pipeline and perform error conversion if necessary
+    #[inline]
+    fn submit_command_list(&self, cmds: DisplayList) -> ChiselResult<()> {
+        match self.pipeline.send(cmds) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ChiselError::DisplayListFailed),
+        }
+    }
+
+    /// Render a JSON value as a TOML document, provided that it can be represented
+    pub fn render_toml(&self, value: JsonValue) -> ChiselResult<()> {
+        match value {
+            JsonValue::Object(_) => check_value(&value, &mut String::new())?,
+            value => {
+                return Err(ChiselError::NotRepresentable(format!(
+                    "top-level {} at \"\"",
+                    type_name(&value)
+                )))
+            }
+        }
+        let mut first = true;
+        match value {
+            JsonValue::Object(pairs) => self.render_table(&[], pairs, false, &mut first),
+            _ => Ok(()),
+        }
+    }
+
+    /// Render a table, given the path of (decoded) keys leading to it. Headers are omitted for tables which only
+    /// hold sub-tables, as they're implied by the headers of those sub-tables
+    fn render_table(
+        &self,
+        path: &[String],
+        pairs: Vec<(String, JsonValue)>,
+        array: bool,
+        first: &mut bool,
+    ) -> ChiselResult<()> {
+        let (tables, inline): (Vec<_>, Vec<_>) = pairs
+            .into_iter()
+            .map(|(key, value)| (unquote_json_string(&key), value))
+            .partition(|(_, value)| is_table(value));
+
+        if !path.is_empty() && (array || !inline.is_empty() || tables.is_empty()) {
+            let header: Vec<String> = path.iter().map(|key| key_text(key)).collect();
+            let header = match array {
+                true => format!("[[{}]]", header.join(".")),
+                false => format!("[{}]", header.join(".")),
+            };
+            if !*first {
+                self.submit_command_list(cl_immediate!(Draw::NewLine))?;
+            }
+            self.submit_command_list(cl_immediate!(Draw::Text(header), Draw::NewLine))?;
+            *first = false;
+        }
+        for (key, value) in inline {
+            let line = format!("{} = {}", key_text(&key), inline_text(value));
+            self.submit_command_list(cl_immediate!(Draw::Text(line), Draw::NewLine))?;
+            *first = false;
+        }
+
+        for (key, value) in tables {
+            let mut child = path.to_vec();
+            child.push(key);
+            match value {
+                JsonValue::Object(pairs) => self.render_table(&child, pairs, false, first)?,
+                JsonValue::Array(values) => {
+                    for value in values {
+                        if let JsonValue::Object(pairs) = value {
+                            self.render_table(&child, pairs, true, first)?
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
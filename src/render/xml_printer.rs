@@ -0,0 +1,303 @@
+//! XML printer logic, used when converting documents into XML 1.0
+//!
+//! This is the reverse of the mapping used when reading XML (see [crate::formats::xml]). Keys with the attribute
+//! prefix become attributes, the text key becomes text content and arrays become repeated elements. A document
+//! which isn't an object with a single member is wrapped within a `root` element, and arrays nested directly within
+//! arrays are written as `item` elements. In strict mode, anything that wouldn't be read back exactly as it was
+//! written is rejected instead
+use crate::cl_immediate;
+use crate::dom::compact_json;
+use crate::errors::{ChiselError, ChiselResult};
+use crate::escapes::{quote_json_string, unquote_json_string};
+use crate::formats::xml::XmlOptions;
+use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode, Draw};
+use crate::render::pretty_printer::FormatOptions;
+use chisel_json::JsonValue;
+use std::sync::mpsc::Sender;
+
+/// The name given to the root element, when the document doesn't supply one
+const ROOT_ELEMENT: &str = "root";
+
+/// The name given to the elements of arrays nested directly within arrays
+const ITEM_ELEMENT: &str = "item";
+
+/// Printer for rendering [JsonValue]s as XML
+pub struct XmlPrinter {
+    /// The pipeline to render to
+    pub pipeline: Sender<DisplayList>,
+
+    /// The formatting options. Only the indent is relevant here
+    pub options: FormatOptions,
+
+    /// The mapping options
+    pub xml: XmlOptions,
+}
+
+/// Lines of output, along with their indentation level. Output is only sent down the pipeline once the whole
+/// document has been rendered, so that nothing is written for documents that can't be represented
+type Lines = Vec<(u16, String)>;
+
+/// Build an error for something that can't be represented, given its JSON pointer
+fn not_representable(what: &str, pointer: &str) -> ChiselError {
+    ChiselError::NotRepresentable(format!("{} at \"{}\"", what, pointer))
+}
+
+/// Extend a JSON pointer with a (decoded) key
+fn push_key(pointer: &str, key: &str) -> String {
+    format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"))
+}
+
+/// Check whether a name is a valid XML name
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' || c == ':' => (),
+        _ => return false,
+    }
+    chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'))
+}
+
+/// Escape text for use within either element content or an attribute value. Characters which aren't allowed
+/// anywhere within an XML 1.0 document can't be escaped, and so are rejected
+fn escape_text(value: &str, attribute: bool, pointer: &str) -> ChiselResult<String> {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\r' => escaped.push_str("&#13;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            '\n' if attribute => escaped.push_str("&#10;"),
+            '\t' if attribute => escaped.push_str("&#9;"),
+            '\t' | '\n' => escaped.push(ch),
+            c if c < ' ' || c == '\u{fffe}' || c == '\u{ffff}' => {
+                return Err(not_representable("character not allowed in XML", pointer))
+            }
+            c => escaped.push(c),
+        }
+    }
+    Ok(escaped)
+}
+
+impl XmlPrinter {
+    /// Construct a new instance, based on a supplied pipeline and set of options
+    pub fn new(pipeline: Sender<DisplayList>, options: FormatOptions, xml: XmlOptions) -> Self {
+        XmlPrinter {
+            pipeline,
+            options,
+            xml,
+        }
+    }
+
+    /// Chuck a [DisplayList] at the rendering pipeline and perform error conversion if necessary
+    #[inline]
+    fn submit_command_list(&self, cmds: DisplayList) -> ChiselResult<()> {
+        match self.pipeline.send(cmds) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ChiselError::DisplayListFailed),
+        }
+    }
+
+    /// Add a single line at a given indentation level
+    fn push_line(&self, lines: &mut Lines, level: u16, line: String) -> ChiselResult<()> {
+        lines.push((level, line));
+        Ok(())
+    }
+
+    /// Render a JSON value as an XML document
+    pub fn render_xml(&self, value: JsonValue) -> ChiselResult<()> {
+        let (name, value, pointer) = match value {
+            JsonValue::Object(mut pairs)
+                if pairs.len() == 1 && !matches!(pairs[0].1, JsonValue::Array(_)) =>
+            {
+                let (key, value) = pairs.remove(0);
+                let name = unquote_json_string(&key);
+                let pointer = push_key("", &name);
+                (name, value, pointer)
+            }
+            _ if self.xml.strict => {
+                return Err(not_representable(
+                    "document without a single root element",
+                    "",
+                ))
+            }
+            JsonValue::Array(values) => {
+                let item = (quote_json_string(ITEM_ELEMENT), JsonValue::Array(values));
+                (
+                    String::from(ROOT_ELEMENT),
+                    JsonValue::Object(vec![item]),
+                    String::new(),
+                )
+            }
+            value => (String::from(ROOT_ELEMENT), value, String::new()),
+        };
+        let mut lines = vec![(
+            0,
+            String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"),
+        )];
+        self.render_element(&mut lines, 0, &name, value, &pointer)?;
+        for (level, line) in lines {
+            self.submit_command_list(cl_immediate!(
+                Draw::Indent(level * self.options.indent),
+                Draw::Text(line),
+                Draw::NewLine
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Convert a scalar into text, for use as either content or an attribute value. Outside of strict mode, anything
+    /// other than a string is simply written out as JSON
+    fn scalar_text(&self, value: &JsonValue, pointer: &str) -> ChiselResult<String> {
+        match value {
+            JsonValue::String(raw) => Ok(unquote_json_string(raw)),
+            JsonValue::Null if !self.xml.strict => Ok(String::new()),
+            _ if !self.xml.strict => Ok(compact_json(value)),
+            JsonValue::Integer(_) | JsonValue::Float(_) => {
+                Err(not_representable("number", pointer))
+            }
+            JsonValue::Boolean(_) => Err(not_representable("boolean", pointer)),
+            JsonValue::Null => Err(not_representable("null", pointer)),
+            _ => Err(not_representable("nested value", pointer)),
+        }
+    }
+
+    /// Render an object member, which is either a single element or (for arrays) a series of elements
+    fn render_member(
+        &self,
+        lines: &mut Lines,
+        level: u16,
+        name: &str,
+        value: JsonValue,
+        pointer: &str,
+    ) -> ChiselResult<()> {
+        let always_array = self.xml.array_elements.iter().any(|n| n == name);
+        let JsonValue::Array(values) = value else {
+            if self.xml.strict && always_array {
+                return Err(not_representable(
+                    "non-array value for an array element",
+                    pointer,
+                ));
+            }
+            return self.render_element(lines, level, name, value, pointer);
+        };
+        if self.xml.strict && values.is_empty() {
+            return Err(not_representable("empty array", pointer));
+        }
+        if self.xml.strict && values.len() == 1 && !always_array {
+            return Err(not_representable("single element array", pointer));
+        }
+        for (index, value) in values.into_iter().enumerate() {
+            let pointer = format!("{}/{}", pointer, index);
+            match value {
+                JsonValue::Array(_) if self.xml.strict => {
+                    return Err(not_representable("nested array", &pointer))
+                }
+                JsonValue::Array(values) => {
+                    let item = (quote_json_string(ITEM_ELEMENT), JsonValue::Array(values));
+                    self.render_element(
+                        lines,
+                        level,
+                        name,
+                        JsonValue::Object(vec![item]),
+                        &pointer,
+                    )?
+                }
+                value => self.render_element(lines, level, name, value, &pointer)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Render a single element
+    fn render_element(
+        &self,
+        lines: &mut Lines,
+        level: u16,
+        name: &str,
+        value: JsonValue,
+        pointer: &str,
+    ) -> ChiselResult<()> {
+        if !is_valid_name(name) {
+            return Err(not_representable(
+                &format!("invalid element name \"{}\"", name),
+                pointer,
+            ));
+        }
+        let pairs = match value {
+            JsonValue::Object(pairs) => pairs,
+            JsonValue::Null => return self.push_line(lines, level, format!("<{}/>", name)),
+            value => {
+                let text = escape_text(&self.scalar_text(&value, pointer)?, false, pointer)?;
+                return self.push_line(lines, level, format!("<{0}>{1}</{0}>", name, text));
+            }
+        };
+
+        // split the members up into attributes, text and child elements
+        let mut attributes = String::new();
+        let mut text = None;
+        let mut children = vec![];
+        let has_pairs = !pairs.is_empty();
+        for (key, value) in pairs {
+            let key = unquote_json_string(&key);
+            let pointer = push_key(pointer, &key);
+            let attribute = match self.xml.attribute_prefix.is_empty() {
+                true => None,
+                false => key.strip_prefix(&self.xml.attribute_prefix),
+            };
+            if let Some(attribute) = attribute {
+                if !is_valid_name(attribute) {
+                    return Err(not_representable(
+                        &format!("invalid attribute name \"{}\"", attribute),
+                        &pointer,
+                    ));
+                }
+                let value = escape_text(&self.scalar_text(&value, &pointer)?, true, &pointer)?;
+                attributes.push_str(&format!(" {}=\"{}\"", attribute, value));
+            } else if key == self.xml.text_key {
+                text = Some(escape_text(
+                    &self.scalar_text(&value, &pointer)?,
+                    false,
+                    &pointer,
+                )?);
+            } else {
+                children.push((key, value, pointer));
+            }
+        }
+
+        if self.xml.strict {
+            if !has_pairs {
+                return Err(not_representable("empty object", pointer));
+            }
+            if attributes.is_empty() && children.is_empty() {
+                return Err(not_representable(
+                    "object holding nothing but text",
+                    pointer,
+                ));
+            }
+            if text.is_some() && !children.is_empty() {
+                return Err(not_representable("text alongside child elements", pointer));
+            }
+        }
+
+        match (children.is_empty(), text) {
+            (true, None) => self.push_line(lines, level, format!("<{}{}/>", name, attributes)),
+            (true, Some(text)) => self.push_line(
+                lines,
+                level,
+                format!("<{0}{1}>{2}</{0}>", name, attributes, text),
+            ),
+            (false, text) => {
+                self.push_line(lines, level, format!("<{}{}>", name, attributes))?;
+                if let Some(text) = text {
+                    self.push_line(lines, level + 1, text)?;
+                }
+                for (key, value, pointer) in children {
+                    self.render_member(lines, level + 1, &key, value, &pointer)?;
+                }
+                self.push_line(lines, level, format!("</{}>", name))
+            }
+        }
+    }
+}
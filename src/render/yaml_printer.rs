@@ -0,0 +1,269 @@
+//! YAML printer logic, used when converting documents into YAML 1.2
+//!
+//! Output is always in block style, with flow style only being used for empty collections.  Scalars are only
+//! quoted where a plain scalar would either be invalid, or would be resolved as something other than a string, and
+//! multi-line strings are written as literal block scalars wherever possible
+use crate::cl_immediate;
+use crate::errors::{ChiselError, ChiselResult};
+use crate::escapes::{quote_json_string, unquote_json_string};
+use crate::render::compact_printer::compact_float;
+use crate::render::display_lists::{DisplayList, DisplayListCommand, DisplayListMode, Draw};
+use crate::render::pretty_printer::FormatOptions;
+use chisel_json::JsonValue;
+use std::sync::mpsc::Sender;
+
+/// Characters which can't appear at the start of a plain scalar
+const INDICATORS: &str = "-?:,[]{}#&*!|>'\"%@`";
+
+/// Words which would be resolved as booleans or nulls by YAML 1.1 and 1.2 parsers
+const RESERVED: [&str; 12] = [
+    "null", "~", "true", "false", "yes", "no", "on", "off", "y", "n", ".inf", ".nan",
+];
+
+/// Printer for rendering [JsonValue]s as YAML
+pub struct YamlPrinter {
+    /// The pipeline to render to
+    pub pipeline: Sender<DisplayList>,
+
+    /// The formatting options. Only the indent is relevant here
+    pub options: FormatOptions,
+}
+
+/// Checks whether a string needs to be quoted, rather than being written out as a plain scalar
+fn needs_quotes(value: &str) -> bool {
+    let first = match value.chars().next() {
+        Some(c) => c,
+        None => return true,
+    };
+    INDICATORS.contains(first)
+        || value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+        || value.ends_with(':')
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.chars().any(|c| c.is_control() || c == '\u{feff}')
+        || RESERVED.iter().any(|r| value.eq_ignore_ascii_case(r))
+        || looks_numeric(value)
+        || looks_like_date(value)
+}
+
+/// Checks whether a plain scalar might be resolved as a number. Rust will happily parse words such as "inf" and
+/// "nan" as floats, whereas YAML only gives the dotted forms any special meaning, so a digit is required here
+fn looks_numeric(value: &str) -> bool {
+    let unsigned = value.trim_start_matches(['+', '-']);
+    let digits = unsigned.bytes().any(|b| b.is_ascii_digit());
+    (digits && value.parse::<f64>().is_ok())
+        || unsigned.eq_ignore_ascii_case(".inf")
+        || unsigned.starts_with("0x")
+        || unsigned.starts_with("0o")
+        || (digits
+            && unsigned
+                .bytes()
+                .all(|b| b.is_ascii_digit() || b == b'_' || b == b'.' || b == b':'))
+}
+
+/// Format a float so that it's always read back as a float, rather than as an integer, by YAML 1.1 and 1.2 parsers
+/// alike. That means keeping a fractional part even where there's an exponent, which must also be signed
+fn float_text(value: f64) -> String {
+    if value.is_nan() {
+        return String::from(".nan");
+    }
+    if value.is_infinite() {
+        return String::from(if value > 0.0 { ".inf" } else { "-.inf" });
+    }
+    let text = compact_float(value);
+    match text.split_once('e') {
+        Some((mantissa, exponent)) => {
+            let mantissa = match mantissa.contains('.') {
+                true => mantissa.to_string(),
+                false => format!("{}.0", mantissa),
+            };
+            match exponent.starts_with('-') {
+                true => format!("{}e{}", mantissa, exponent),
+                false => format!("{}e+{}", mantissa, exponent),
+            }
+        }
+        None => text,
+    }
+}
+
+/// Checks whether a plain scalar might be resolved as a timestamp
+fn looks_like_date(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    bytes.len() >= 10
+        && bytes[..4].iter().all(u8::is_ascii_digit)
+        && bytes[4] == b'-'
+        && bytes[5..7].iter().all(u8::is_ascii_digit)
+        && bytes[7] == b'-'
+        && bytes[8..10].iter().all(u8::is_ascii_digit)
+}
+
+/// Checks whether a string can be written out as a literal block scalar
+fn is_literal_block(value: &str) -> bool {
+    value.contains('\n')
+        && !value.starts_with([' ', '\t', '\n'])
+        && !value
+            .chars()
+            .any(|c| (c.is_control() && c != '\n' && c != '\t') || c == '\u{feff}')
+}
+
+/// Format a (decoded) string as a scalar, quoting it if required
+fn scalar_text(value: &str) -> String {
+    if needs_quotes(value) {
+        quote_json_string(value)
+    } else {
+        value.to_string()
+    }
+}
+
+impl YamlPrinter {
+    /// Construct a new instance, based on a supplied pipeline and set of options
+    pub fn new(pipeline: Sender<DisplayList>, options: FormatOptions) -> Self {
+        YamlPrinter { pipeline, options }
+    }
+
+    /// Chuck a [DisplayList] at the rendering pipeline and perform error conversion if necessary
+    #[inline]
+    fn submit_command_list(&self, cmds: DisplayList) -> ChiselResult<()> {
+        match self.pipeline.send(cmds) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ChiselError::DisplayListFailed),
+        }
+    }
+
+    /// The indent to use for each level of nesting. YAML requires at least one space
+    #[inline]
+    fn indent(&self) -> u16 {
+        self.options.indent.max(1)
+    }
+
+    /// Render a JSON value as a YAML document
+    pub fn render_yaml(&self, value: JsonValue) -> ChiselResult<()> {
+        match value {
+            JsonValue::Object(pairs) if !pairs.is_empty() => self.render_mapping(0, pairs, false),
+            JsonValue::Array(values) if !values.is_empty() => {
+                self.render_sequence(0, values, false)
+            }
+            value => self.render_scalar(0, value),
+        }
+    }
+
+    /// Render a document start marker, as used to separate multiple documents
+    pub fn render_document_start(&self) -> ChiselResult<()> {
+        self.submit_command_list(cl_immediate!(Draw::Slice("---"), Draw::NewLine))
+    }
+
+    /// Render a mapping, with each entry starting at the given column. If `inline` is set, then the cursor is
+    /// already positioned for the first entry
+    fn render_mapping(
+        &self,
+        column: u16,
+        pairs: Vec<(String, JsonValue)>,
+        inline: bool,
+    ) -> ChiselResult<()> {
+        for (i, (key, value)) in pairs.into_iter().enumerate() {
+            let key = unquote_json_string(&key);
+            let key = if key.contains('\n') {
+                quote_json_string(&key)
+            } else {
+                scalar_text(&key)
+            };
+            if !(inline && i == 0) {
+                self.submit_command_list(cl_immediate!(Draw::Indent(column)))?;
+            }
+            self.submit_command_list(cl_immediate!(Draw::Text(key), Draw::Char(':')))?;
+
+            // nested collections start on the next line, whereas scalars follow on directly
+            match value {
+                JsonValue::Object(pairs) if !pairs.is_empty() => {
+                    self.submit_command_list(cl_immediate!(Draw::NewLine))?;
+                    self.render_mapping(column + self.indent(), pairs, false)?
+                }
+                JsonValue::Array(values) if !values.is_empty() => {
+                    self.submit_command_list(cl_immediate!(Draw::NewLine))?;
+                    self.render_sequence(column + self.indent(), values, false)?
+                }
+                value => {
+                    self.submit_command_list(cl_immediate!(Draw::Char(' ')))?;
+                    self.render_scalar(column + self.indent(), value)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Render a sequence, with each entry starting at the given column. If `inline` is set, then the cursor is
+    /// already positioned for the first entry
+    fn render_sequence(
+        &self,
+        column: u16,
+        values: Vec<JsonValue>,
+        inline: bool,
+    ) -> ChiselResult<()> {
+        for (i, value) in values.into_iter().enumerate() {
+            if !(inline && i == 0) {
+                self.submit_command_list(cl_immediate!(Draw::Indent(column)))?;
+            }
+            self.submit_command_list(cl_immediate!(Draw::Slice("- ")))?;
+
+            // nested collections are written in the compact form, starting on the same line as the indicator
+            match value {
+                JsonValue::Object(pairs) if !pairs.is_empty() => {
+                    self.render_mapping(column + 2, pairs, true)?
+                }
+                JsonValue::Array(values) if !values.is_empty() => {
+                    self.render_sequence(column + 2, values, true)?
+                }
+                value => self.render_scalar(column + 2, value)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Render a scalar (or empty collection) followed by a newline. Any literal block content is placed at the
+    /// given column
+    fn render_scalar(&self, column: u16, value: JsonValue) -> ChiselResult<()> {
+        let text = match value {
+            JsonValue::Object(_) => String::from("{}"),
+            JsonValue::Array(_) => String::from("[]"),
+            JsonValue::String(raw) => {
+                let value = unquote_json_string(&raw);
+                if is_literal_block(&value) {
+                    return self.render_literal_block(column, &value);
+                }
+                scalar_text(&value)
+            }
+            JsonValue::Float(value) => float_text(value),
+            JsonValue::Integer(value) => value.to_string(),
+            JsonValue::Boolean(value) => value.to_string(),
+            JsonValue::Null => String::from("null"),
+        };
+        self.submit_command_list(cl_immediate!(Draw::Text(text), Draw::NewLine))
+    }
+
+    /// Render a multi-line string as a literal block scalar, choosing a chomping indicator that preserves any
+    /// trailing line breaks
+    fn render_literal_block(&self, column: u16, value: &str) -> ChiselResult<()> {
+        let content = value.trim_end_matches('\n');
+        let trailing = value.len() - content.len();
+        let header = match trailing {
+            0 => "|-",
+            1 => "|",
+            _ => "|+",
+        };
+        let mut list = cl_immediate!(Draw::Slice(header), Draw::NewLine);
+        for line in content.split('\n') {
+            if !line.is_empty() {
+                list.cmds
+                    .push(DisplayListCommand::Draw(Draw::Indent(column)));
+                list.cmds
+                    .push(DisplayListCommand::Draw(Draw::Text(line.to_string())));
+            }
+            list.cmds.push(DisplayListCommand::Draw(Draw::NewLine));
+        }
+        for _ in 1..trailing {
+            list.cmds.push(DisplayListCommand::Draw(Draw::NewLine));
+        }
+        self.submit_command_list(list)
+    }
+}
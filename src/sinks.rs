@@ -0,0 +1,63 @@
+//! Utilities for writing output to destinations other than the rendering pipeline
+
+use crate::errors::{ChiselError, ChiselResult};
+use atty::Stream;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+/// Replace the contents of a file atomically.  The new contents are written to a temporary file alongside the
+/// original, which is then renamed over the top of it, so readers will only ever see either the old or new content.
+/// Symbolic links are followed, so that it's the file they point at which gets replaced rather than the link itself
+pub fn write_file_atomically<PathLike: AsRef<Path>>(
+    path: PathLike,
+    contents: &[u8],
+) -> ChiselResult<()> {
+    let path = fs::canonicalize(path.as_ref()).unwrap_or_else(|_| path.as_ref().to_path_buf());
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => return Err(ChiselError::InvalidFile),
+    };
+    let temp_path = path.with_file_name(format!(".{}.chisel.tmp", name));
+
+    let result = File::create(&temp_path)
+        .and_then(|mut f| {
+            // carry across the permissions from the original, before any of the new contents are written
+            if let Ok(metadata) = fs::metadata(&path) {
+                f.set_permissions(metadata.permissions())?;
+            }
+            f.write_all(contents)?;
+            f.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, &path));
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            eprintln!("{}", err);
+            let _ = fs::remove_file(&temp_path);
+            Err(ChiselError::OutputFailed)
+        }
+    }
+}
+
+/// Write binary content directly to stdout, bypassing the rendering pipeline. Binary content is never written to a
+/// terminal, as it would only end up mangling it
+pub fn write_binary_to_stdout(contents: &[u8]) -> ChiselResult<()> {
+    if atty::is(Stream::Stdout) {
+        return Err(ChiselError::BinaryToTerminal);
+    }
+    let mut stdout = std::io::stdout().lock();
+    stdout
+        .write_all(contents)
+        .and_then(|_| stdout.flush())
+        .or(Err(ChiselError::OutputFailed))
+}
+
+/// The width of the terminal that stdout is attached to, falling back to 80 columns if it can't be worked out
+pub fn terminal_width() -> usize {
+    match crossterm::terminal::size() {
+        Ok((columns, _)) if columns > 0 => columns as usize,
+        _ => 80,
+    }
+}
@@ -4,7 +4,7 @@ use atty::Stream;
 
 use crate::errors::{ChiselError, ChiselResult};
 use std::fs::File;
-use std::io::{stdin, BufReader, Read};
+use std::io::{stdin, BufRead, BufReader, Read};
 use std::path::Path;
 
 /// Create a source buffer from something that smells like a [Path]
@@ -36,3 +36,23 @@ pub fn source_from_stdin(buffer: &mut Vec<u8>) -> ChiselResult<usize> {
         .read_to_end(buffer)
         .or(Err(ChiselError::InvalidInput))
 }
+
+/// Create a buffered reader over something that smells like a [Path], for input which is to be processed a piece at a
+/// time rather than read up front
+pub fn reader_from_file<PathLike: AsRef<Path>>(path: PathLike) -> ChiselResult<Box<dyn BufRead>> {
+    match File::open(&path) {
+        Ok(f) => Ok(Box::new(BufReader::new(f))),
+        Err(err) => {
+            eprintln!("{}", err);
+            Err(ChiselError::InvalidFile)
+        }
+    }
+}
+
+/// Create a buffered reader over [stdin], subject to the same TTY check as [source_from_stdin]
+pub fn reader_from_stdin() -> ChiselResult<Box<dyn BufRead>> {
+    if atty::is(Stream::Stdin) {
+        return Err(ChiselError::NoPipedInput);
+    }
+    Ok(Box::new(BufReader::new(stdin())))
+}
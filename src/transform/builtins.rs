@@ -0,0 +1,1318 @@
+//! Built-in functions, formats and operators.  Functions which are most naturally expressed in terms of the
+//! language itself live within the prelude, and everything else is implemented natively
+use super::evaluator::{path_of, Env, Evaluator, Failure, Flow, Item, Sink};
+use super::paths::{delete_paths, get_path, path_components, set_path};
+use super::value::Value;
+use super::{BinaryOp, Expr};
+use crate::dom::parse_literal;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+/// The longest string (in bytes) that repeating a string may produce
+const MAX_REPEAT_LENGTH: usize = 256 * 1024 * 1024;
+
+/// Functions defined in terms of other functions. The prelude is parsed once, and its definitions are then in
+/// scope for every program
+pub(super) const PRELUDE: &str = r#"
+def select(f): if f then . else empty end;
+def map(f): [.[] | f];
+def values: select(. != null);
+def nulls: select(. == null);
+def booleans: select(type == "boolean");
+def numbers: select(type == "number");
+def strings: select(type == "string");
+def arrays: select(type == "array");
+def objects: select(type == "object");
+def iterables: select(type | . == "array" or . == "object");
+def scalars: select(type | . != "array" and . != "object");
+def finites: select(isinfinite or isnan | not);
+def recurse(f; cond): def r: ., (f | select(cond) | r); r;
+def to_entries: [keys_unsorted[] as $k | {key: $k, value: .[$k]}];
+def from_entries: reduce .[] as $x ({};
+    . + { ($x | if .key == null then .k // .name // .Name // .K // .Key else .key end
+             | if type == "string" then . else tojson end):
+          ($x | if has("value") then .value else .v end) });
+def with_entries(f): to_entries | map(f) | from_entries;
+def map_values(f): .[] |= f;
+def add(f): reduce f as $x (null; . + $x);
+def any: reduce .[] as $x (false; . or $x);
+def all: reduce .[] as $x (true; . and $x);
+def any(f): reduce (.[] | f) as $x (false; . or $x);
+def all(f): reduce (.[] | f) as $x (true; . and $x);
+def isempty(g): first((g | false), true);
+def any(g; cond): isempty(first(g | cond | select(.))) | not;
+def all(g; cond): isempty(first(g | cond | select(. | not)));
+def IN(s): any(s == .; .);
+def IN(src; s): any(src == s; .);
+def INDEX(stream; idx_expr): reduce stream as $row ({}; .[$row | idx_expr | tostring] |= $row);
+def INDEX(idx_expr): INDEX(.[]; idx_expr);
+def in(xs): . as $x | xs | has($x);
+def inside(xs): . as $x | xs | contains($x);
+def paths: path(..) | select(length > 0);
+def paths(node_filter): . as $dot | paths | select(. as $p | $dot | getpath($p) | node_filter);
+def leaf_paths: paths(scalars);
+def pick(pathexps): . as $top | reduce path(pathexps) as $p (null; setpath($p; $top | getpath($p)));
+def del(f): delpaths([path(f)]);
+def to_array: if type == "array" then . else [.] end;
+def toarray: to_array;
+def walk(f): def w: if type == "object" then map_values(w) elif type == "array" then map(w) else . end | f; w;
+def while(cond; update): def _while: if cond then ., (update | _while) else empty end; _while;
+def until(cond; update): def _until: if cond then . else (update | _until) end; _until;
+def repeat(f): def _repeat: ., (f | _repeat); _repeat;
+def first: .[0];
+def last: .[-1];
+def nth($n): .[$n];
+def nth($n; f): if $n < 0 then error("Out of bounds negative array index") else last(limit($n + 1; f)) end;
+def combinations: if length == 0 then [] else .[0][] as $x | (.[1:] | combinations) as $w | [$x] + $w end;
+def combinations(n): . as $dot | [range(n)] | map($dot) | combinations;
+def transpose: if . == [] then [] else . as $in | (map(length) | max) as $max
+    | [range(0; $max) as $j | [range(0; $in | length) as $i | $in[$i][$j]]] end;
+def indices($i): if type == "array" and ($i | type) == "array" then .[$i]
+    elif type == "array" then .[[$i]]
+    elif ($i | type) == "string" then _strindices($i)
+    else .[[$i]] end;
+def index($i): indices($i) | .[0];
+def rindex($i): indices($i) | .[-1:][0];
+def tostream: path(def r: (.[]? | r), .; r) as $p | getpath($p) | reduce path(.[]?) as $q ([$p, .]; [$p + $q]);
+def env: $ENV;
+def debug(msg): (msg | debug | empty), .;
+def match(re; mode): _match_impl(re; mode; false) | .[];
+def match($val): ($val | type) as $vt
+    | if $vt == "string" then match($val; null)
+      elif $vt == "array" and ($val | length) > 1 then match($val[0]; $val[1])
+      elif $vt == "array" and ($val | length) > 0 then match($val[0]; null)
+      else error($vt + " not a string or array") end;
+def test(re; mode): _match_impl(re; mode; true);
+def test($val): ($val | type) as $vt
+    | if $vt == "string" then test($val; null)
+      elif $vt == "array" and ($val | length) > 1 then test($val[0]; $val[1])
+      elif $vt == "array" and ($val | length) > 0 then test($val[0]; null)
+      else error($vt + " not a string or array") end;
+def capture(re; mods): match(re; mods)
+    | reduce (.captures | .[] | select(.name != null) | {key: .name, value: .string}) as $pair
+        ({}; . + {($pair.key): $pair.value});
+def capture($val): ($val | type) as $vt
+    | if $vt == "string" then capture($val; null)
+      elif $vt == "array" and ($val | length) > 1 then capture($val[0]; $val[1])
+      elif $vt == "array" and ($val | length) > 0 then capture($val[0]; null)
+      else error($vt + " not a string or array") end;
+def scan($re; $flags): match($re; "g" + $flags)
+    | if (.captures | length) > 0 then [.captures | .[] | .string] else .string end;
+def scan($re): scan($re; null);
+def _nwise($n): def n: if length <= $n then . else .[0:$n], (.[$n:] | n) end; n;
+def split($re; flags): . as $s
+    | [match($re; "g" + flags) | (.offset, .offset + .length)] as $ms
+    | [0] + $ms + [$s | length] | [_nwise(2) | $s[.[0]:.[1]]];
+def splits($re; flags): split($re; flags) | .[];
+def splits($re): splits($re; null);
+def sub($re; str; $flags): . as $in
+    | (reduce (match($re; $flags) | . as $edit
+        | ($edit.captures | map(select(.name != null) | {key: .name, value: .string}) | from_entries) as $caps
+        | {$edit, s: ($caps | str)}) as $x
+        ({result: "", previous: 0};
+         .result += $in[.previous:$x.edit.offset] + $x.s | .previous = $x.edit.offset + $x.edit.length))
+    | .result + $in[.previous:];
+def sub($re; str): sub($re; str; "");
+def gsub($re; str; $flags): sub($re; str; $flags + "g");
+def gsub($re; str): sub($re; str; "g");
+.
+"#;
+
+impl Evaluator {
+    /// Call a natively implemented function, returning [None] if there's no such function
+    pub(super) fn call_builtin<'p>(
+        &self,
+        name: &str,
+        args: &'p [Expr],
+        env: &Rc<Env<'p>>,
+        input: &Item,
+        out: Sink,
+    ) -> Option<Flow> {
+        let flow = match (name, args.len()) {
+            ("empty", 0) => Ok(()),
+            ("error", 0) => Err(Failure::Error(input.value.clone())),
+            ("error", 1) => self.eval(&args[0], env, input, &mut |message| {
+                Err(Failure::Error(message.value))
+            }),
+            ("recurse", 0) => self.recurse_all(input, out),
+            ("recurse", 1) => self.recurse_with(&args[0], env, input, out),
+            ("path", 1) => self.eval(
+                &args[0],
+                env,
+                &Item::root(input.value.clone()),
+                &mut |item| out(Item::new(Value::array(path_of(item)?))),
+            ),
+            ("getpath", 1) => self.eval(&args[0], env, input, &mut |path| {
+                let components = path_components(&path.value)?;
+                let value = get_path(&input.value, components)?;
+                out(Item {
+                    value,
+                    path: input.path.as_ref().map(|base| {
+                        let mut base = base.clone();
+                        base.extend(components.iter().cloned());
+                        base
+                    }),
+                })
+            }),
+            ("first", 1) => match self.first(&args[0], env, input) {
+                Ok(Some(item)) => out(item),
+                Ok(None) => Ok(()),
+                Err(failure) => Err(failure),
+            },
+            ("last", 1) => {
+                let mut last = None;
+                match self.eval(&args[0], env, input, &mut |item| {
+                    last = Some(item);
+                    Ok(())
+                }) {
+                    Ok(()) => match last {
+                        Some(item) => out(item),
+                        None => Ok(()),
+                    },
+                    Err(failure) => Err(failure),
+                }
+            }
+            ("limit", 2) => self.eval(&args[0], env, input, &mut |n| {
+                let n = match n.value.as_f64() {
+                    Some(n) => n,
+                    None => return Err(Failure::message("Invalid limit: must be a number")),
+                };
+                if n <= 0.0 {
+                    return Ok(());
+                }
+                let label = self.next_label();
+                let mut count = 0.0;
+                let outcome = self.eval(&args[1], env, input, &mut |item| {
+                    count += 1.0;
+                    out(item)?;
+                    if count >= n {
+                        Err(Failure::Break(label))
+                    } else {
+                        Ok(())
+                    }
+                });
+                match outcome {
+                    Err(Failure::Break(l)) if l == label => Ok(()),
+                    outcome => outcome,
+                }
+            }),
+            ("range", 1..=3) => self.eval_args(args, env, input, vec![], &mut |values| {
+                let bounds: Option<Vec<f64>> = values.iter().map(Value::as_f64).collect();
+                let all_integers = values.iter().all(|v| matches!(v, Value::Integer(_)));
+                match bounds {
+                    Some(bounds) if all_integers => {
+                        let bounds: Vec<i64> = bounds.iter().map(|b| *b as i64).collect();
+                        let (from, upto, by) = match bounds.as_slice() {
+                            [upto] => (0, *upto, 1),
+                            [from, upto] => (*from, *upto, 1),
+                            [from, upto, by] => (*from, *upto, *by),
+                            _ => (0, 0, 1),
+                        };
+                        let mut current = from;
+                        while (by > 0 && current < upto) || (by < 0 && current > upto) {
+                            out(Item::new(Value::Integer(current)))?;
+                            current += by;
+                        }
+                        Ok(())
+                    }
+                    Some(bounds) => {
+                        let (from, upto, by) = match bounds.as_slice() {
+                            [upto] => (0.0, *upto, 1.0),
+                            [from, upto] => (*from, *upto, 1.0),
+                            [from, upto, by] => (*from, *upto, *by),
+                            _ => (0.0, 0.0, 1.0),
+                        };
+                        let mut current = from;
+                        while (by > 0.0 && current < upto) || (by < 0.0 && current > upto) {
+                            out(Item::new(Value::number(current)))?;
+                            current += by;
+                        }
+                        Ok(())
+                    }
+                    None => Err(Failure::message("Range bounds must be numeric")),
+                }
+            }),
+            ("sort_by" | "group_by" | "unique_by" | "min_by" | "max_by", 1) => {
+                match self.keyed(name, &args[0], env, &input.value) {
+                    Ok(keyed) => out(Item::new(by_key(name, keyed))),
+                    Err(failure) => Err(failure),
+                }
+            }
+            ("debug", 0) => {
+                eprintln!("[\"DEBUG:\",{}]", input.value.to_json_text());
+                out(input.clone())
+            }
+            ("stderr", 0) => {
+                eprint!("{}", input.value.to_json_text());
+                out(input.clone())
+            }
+            ("_match_impl", 3) => self.eval_args(args, env, input, vec![], &mut |values| {
+                out(Item::new(self.match_regex(&input.value, values)?))
+            }),
+            (_, 0) => match native(name, &input.value)? {
+                Ok(value) => out(Item::new(value)),
+                Err(failure) => Err(failure),
+            },
+            (_, arity) if NATIVES_WITH_ARGS.contains(&(name, arity)) => self.eval_args(
+                args,
+                env,
+                input,
+                vec![],
+                &mut |values| match native_with_args(name, &input.value, values) {
+                    Some(Ok(value)) => out(Item::new(value)),
+                    Some(Err(failure)) => Err(failure),
+                    None => Ok(()),
+                },
+            ),
+            _ => return None,
+        };
+        Some(flow)
+    }
+
+    /// Evaluate a list of value arguments, calling back with each combination of argument values
+    fn eval_args<'p>(
+        &self,
+        args: &'p [Expr],
+        env: &Rc<Env<'p>>,
+        input: &Item,
+        values: Vec<Value>,
+        out: &mut dyn FnMut(&[Value]) -> Flow,
+    ) -> Flow {
+        match args.split_first() {
+            None => out(&values),
+            Some((arg, rest)) => self.eval(arg, env, input, &mut |item| {
+                let mut values = values.clone();
+                values.push(item.value);
+                self.eval_args(rest, env, input, values, out)
+            }),
+        }
+    }
+
+    /// Output an item, followed by the recursive application of a filter to it
+    fn recurse_with<'p>(&self, f: &'p Expr, env: &Rc<Env<'p>>, item: &Item, out: Sink) -> Flow {
+        out(item.clone())?;
+        self.eval(f, env, item, &mut |child| {
+            self.recurse_with(f, env, &child, out)
+        })
+    }
+
+    /// Pair up each element of an array with the outputs of a key filter applied to it
+    fn keyed<'p>(
+        &self,
+        name: &str,
+        f: &'p Expr,
+        env: &Rc<Env<'p>>,
+        input: &Value,
+    ) -> Result<Vec<(Value, Value)>, Failure> {
+        let values = match input {
+            Value::Array(values) => values,
+            value => {
+                return Err(Failure::message(format!(
+                    "Cannot apply {} to {}, as it is not an array",
+                    name,
+                    value.describe()
+                )))
+            }
+        };
+        let mut keyed = Vec::with_capacity(values.len());
+        for value in values.iter() {
+            let mut keys = vec![];
+            self.eval(f, env, &Item::new(value.clone()), &mut |key| {
+                keys.push(key.value);
+                Ok(())
+            })?;
+            keyed.push((Value::array(keys), value.clone()));
+        }
+        Ok(keyed)
+    }
+
+    /// Match a regular expression against a string, with arguments of the form `[regex, flags, test]`. Offsets and
+    /// lengths within the resulting match objects are in codepoints
+    fn match_regex(&self, input: &Value, args: &[Value]) -> Result<Value, Failure> {
+        let text = match input {
+            Value::String(s) => s,
+            value => {
+                return Err(Failure::message(format!(
+                    "{} cannot be matched, as it is not a string",
+                    value.describe()
+                )))
+            }
+        };
+        let pattern = match &args[0] {
+            Value::String(s) => s,
+            value => {
+                return Err(Failure::message(format!(
+                    "{} cannot be matched, as it is not a string",
+                    value.describe()
+                )))
+            }
+        };
+        let flags = match &args[1] {
+            Value::Null => "",
+            Value::String(s) => s,
+            value => {
+                return Err(Failure::message(format!(
+                    "{} is not a string",
+                    value.describe()
+                )))
+            }
+        };
+
+        let mut global = false;
+        let mut skip_empty = false;
+        let mut inline = String::new();
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'n' => skip_empty = true,
+                'i' | 'x' | 's' => inline.push(flag),
+                'l' | 'p' => {}
+                _ => {
+                    return Err(Failure::message(format!(
+                        "{} is not a valid modifier string",
+                        flags
+                    )))
+                }
+            }
+        }
+        let pattern = if inline.is_empty() {
+            pattern.to_string()
+        } else {
+            format!("(?{}){}", inline, pattern)
+        };
+        let regex = self.regex(&pattern)?;
+
+        if args[2].is_truthy() {
+            return Ok(Value::Boolean(regex.is_match(text)));
+        }
+
+        let offset = |byte: usize| Value::Integer(text[..byte].chars().count() as i64);
+        let mut matches = vec![];
+        for captures in regex.captures_iter(text) {
+            let whole = match captures.get(0) {
+                Some(whole) => whole,
+                None => continue,
+            };
+            if skip_empty && whole.as_str().is_empty() {
+                continue;
+            }
+            let groups = regex
+                .capture_names()
+                .enumerate()
+                .skip(1)
+                .map(|(i, name)| {
+                    let name = name.map_or(Value::Null, Value::string);
+                    match captures.get(i) {
+                        Some(group) => Value::object(vec![
+                            (String::from("offset"), offset(group.start())),
+                            (
+                                String::from("length"),
+                                Value::Integer(group.as_str().chars().count() as i64),
+                            ),
+                            (String::from("string"), Value::string(group.as_str())),
+                            (String::from("name"), name),
+                        ]),
+                        None => Value::object(vec![
+                            (String::from("offset"), Value::Integer(-1)),
+                            (String::from("length"), Value::Integer(0)),
+                            (String::from("string"), Value::Null),
+                            (String::from("name"), name),
+                        ]),
+                    }
+                })
+                .collect();
+            matches.push(Value::object(vec![
+                (String::from("offset"), offset(whole.start())),
+                (
+                    String::from("length"),
+                    Value::Integer(whole.as_str().chars().count() as i64),
+                ),
+                (String::from("string"), Value::string(whole.as_str())),
+                (String::from("captures"), Value::array(groups)),
+            ]));
+            if !global {
+                break;
+            }
+        }
+        Ok(Value::array(matches))
+    }
+}
+
+/// Complete one of the `*_by` functions, given the elements of the input paired up with their keys
+fn by_key(name: &str, mut keyed: Vec<(Value, Value)>) -> Value {
+    match name {
+        "min_by" => keyed
+            .into_iter()
+            .reduce(|min, next| if next.0 < min.0 { next } else { min })
+            .map_or(Value::Null, |(_, value)| value),
+        "max_by" => keyed
+            .into_iter()
+            .reduce(|max, next| if next.0 >= max.0 { next } else { max })
+            .map_or(Value::Null, |(_, value)| value),
+        _ => {
+            keyed.sort_by(|a, b| a.0.cmp(&b.0));
+            match name {
+                "sort_by" => Value::array(keyed.into_iter().map(|(_, value)| value).collect()),
+                _ => {
+                    let mut groups: Vec<(Value, Vec<Value>)> = vec![];
+                    for (key, value) in keyed {
+                        match groups.last_mut() {
+                            Some((last, members)) if *last == key => members.push(value),
+                            _ => groups.push((key, vec![value])),
+                        }
+                    }
+                    Value::array(
+                        groups
+                            .into_iter()
+                            .map(|(_, mut members)| {
+                                if name == "unique_by" {
+                                    members.swap_remove(0)
+                                } else {
+                                    Value::array(members)
+                                }
+                            })
+                            .collect(),
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// Native functions of arity zero, which map their input onto a single output. Returns [None] if there's no such
+/// function
+fn native(name: &str, input: &Value) -> Option<Result<Value, Failure>> {
+    let result = match name {
+        "not" => Ok(Value::Boolean(!input.is_truthy())),
+        "length" => match input {
+            Value::Null => Ok(Value::Integer(0)),
+            Value::Integer(i) => Ok(Value::Integer(i.saturating_abs())),
+            Value::Float(f) => Ok(Value::number(f.abs())),
+            Value::String(s) => Ok(Value::Integer(s.chars().count() as i64)),
+            Value::Array(values) => Ok(Value::Integer(values.len() as i64)),
+            Value::Object(members) => Ok(Value::Integer(members.len() as i64)),
+            value => Err(Failure::message(format!(
+                "{} has no length",
+                value.describe()
+            ))),
+        },
+        "utf8bytelength" => match input {
+            Value::String(s) => Ok(Value::Integer(s.len() as i64)),
+            value => Err(Failure::message(format!(
+                "{} only strings have UTF-8 byte length",
+                value.describe()
+            ))),
+        },
+        "keys" | "keys_unsorted" => match input {
+            Value::Object(members) => {
+                let keys = if name == "keys" {
+                    input.sorted_keys().into_iter().map(Value::string).collect()
+                } else {
+                    members
+                        .iter()
+                        .map(|(k, _)| Value::string(k.as_str()))
+                        .collect()
+                };
+                Ok(Value::array(keys))
+            }
+            Value::Array(values) => Ok(Value::array(
+                (0..values.len() as i64).map(Value::Integer).collect(),
+            )),
+            value => Err(Failure::message(format!(
+                "{} has no keys",
+                value.describe()
+            ))),
+        },
+        "add" => match input {
+            Value::Null => Ok(Value::Null),
+            Value::Array(values) => values
+                .iter()
+                .try_fold(Value::Null, |acc, v| binary_op(BinaryOp::Add, &acc, v)),
+            Value::Object(members) => members
+                .iter()
+                .try_fold(Value::Null, |acc, (_, v)| binary_op(BinaryOp::Add, &acc, v)),
+            value => Err(Failure::message(format!(
+                "Cannot iterate over {}",
+                value.describe()
+            ))),
+        },
+        "type" => Ok(Value::string(input.type_name())),
+        "tostring" => Ok(Value::string(input.to_text())),
+        "tojson" => Ok(Value::string(input.to_json_text())),
+        "fromjson" => match input {
+            Value::String(s) => match parse_literal(s) {
+                Ok(value) => Ok(Value::from_json(value)),
+                Err(_) => Err(Failure::message(format!(
+                    "{} (while parsing '{}')",
+                    "Invalid JSON text", s
+                ))),
+            },
+            value => Err(Failure::message(format!(
+                "{} cannot be parsed as JSON",
+                value.describe()
+            ))),
+        },
+        "tonumber" => match input {
+            Value::Integer(_) | Value::Float(_) => Ok(input.clone()),
+            Value::String(s) => match (s.parse::<i64>(), s.parse::<f64>()) {
+                (Ok(i), _) => Ok(Value::Integer(i)),
+                (_, Ok(f)) if f.is_finite() => Ok(Value::number(f)),
+                _ => Err(Failure::message(format!(
+                    "Cannot parse '{}' as a number",
+                    s
+                ))),
+            },
+            value => Err(Failure::message(format!(
+                "{} cannot be parsed as a number",
+                value.describe()
+            ))),
+        },
+        "ascii_downcase" | "ascii_upcase" | "explode" | "ltrim" | "rtrim" | "trim" => match input {
+            Value::String(s) => Ok(match name {
+                "ascii_downcase" => Value::string(s.to_ascii_lowercase()),
+                "ascii_upcase" => Value::string(s.to_ascii_uppercase()),
+                "explode" => Value::array(s.chars().map(|c| Value::Integer(c as i64)).collect()),
+                "ltrim" => Value::string(s.trim_start()),
+                "rtrim" => Value::string(s.trim_end()),
+                _ => Value::string(s.trim()),
+            }),
+            value => Err(Failure::message(format!(
+                "{} input must be a string, not {}",
+                name,
+                value.describe()
+            ))),
+        },
+        "implode" => match input {
+            Value::Array(codes) => codes
+                .iter()
+                .map(|code| match code.as_f64() {
+                    Some(code) => Ok(char::from_u32(code as u32).unwrap_or('\u{fffd}')),
+                    None => Err(Failure::message("Unicode codepoints must be numeric")),
+                })
+                .collect::<Result<String, Failure>>()
+                .map(Value::string),
+            _ => Err(Failure::message("Implode input must be an array")),
+        },
+        "floor" | "ceil" | "round" | "trunc" | "sqrt" | "fabs" | "abs" | "exp" | "exp2"
+        | "exp10" | "log" | "log2" | "log10" | "sin" | "cos" | "tan" | "asin" | "acos" | "atan"
+        | "significand" => match (input, input.as_f64()) {
+            (Value::Integer(i), _) if matches!(name, "floor" | "ceil" | "round" | "trunc") => {
+                Ok(Value::Integer(*i))
+            }
+            (_, Some(f)) => Ok(Value::number(match name {
+                "floor" => f.floor(),
+                "ceil" => f.ceil(),
+                "round" => f.round(),
+                "trunc" => f.trunc(),
+                "sqrt" => f.sqrt(),
+                "fabs" | "abs" => f.abs(),
+                "exp" => f.exp(),
+                "exp2" => f.exp2(),
+                "exp10" => 10f64.powf(f),
+                "log" => f.ln(),
+                "log2" => f.log2(),
+                "log10" => f.log10(),
+                "sin" => f.sin(),
+                "cos" => f.cos(),
+                "tan" => f.tan(),
+                "asin" => f.asin(),
+                "acos" => f.acos(),
+                "atan" => f.atan(),
+                _ => significand(f),
+            })),
+            (value, None) => Err(Failure::message(format!(
+                "{} number required",
+                value.describe()
+            ))),
+        },
+        "infinite" => Ok(Value::Float(f64::INFINITY)),
+        "nan" => Ok(Value::Float(f64::NAN)),
+        "isinfinite" | "isnan" | "isnormal" => match input.as_f64() {
+            Some(f) => Ok(Value::Boolean(match name {
+                "isinfinite" => f.is_infinite(),
+                "isnan" => f.is_nan(),
+                _ => f.is_normal(),
+            })),
+            None => Err(Failure::message(format!(
+                "{} number required",
+                input.describe()
+            ))),
+        },
+        "sort" | "unique" | "min" | "max" => match input {
+            Value::Array(values) => {
+                let mut sorted = values.to_vec();
+                sorted.sort();
+                Ok(match name {
+                    "sort" => Value::array(sorted),
+                    "unique" => {
+                        sorted.dedup();
+                        Value::array(sorted)
+                    }
+                    "min" => sorted.into_iter().next().unwrap_or(Value::Null),
+                    _ => sorted.pop().unwrap_or(Value::Null),
+                })
+            }
+            value => Err(Failure::message(format!(
+                "{} cannot be sorted, as it is not an array",
+                value.describe()
+            ))),
+        },
+        "reverse" => match input {
+            Value::Null => Ok(Value::array(vec![])),
+            Value::String(s) => Ok(Value::string(s.chars().rev().collect::<String>())),
+            Value::Array(values) => Ok(Value::array(values.iter().rev().cloned().collect())),
+            value => Err(Failure::message(format!(
+                "Cannot reverse {}",
+                value.describe()
+            ))),
+        },
+        "flatten" => flatten(input, f64::INFINITY),
+        "input_filename" => Ok(Value::Null),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// The names and arities of the native functions that take value arguments
+const NATIVES_WITH_ARGS: &[(&str, usize)] = &[
+    ("has", 1),
+    ("contains", 1),
+    ("split", 1),
+    ("join", 1),
+    ("ltrimstr", 1),
+    ("rtrimstr", 1),
+    ("startswith", 1),
+    ("endswith", 1),
+    ("_strindices", 1),
+    ("flatten", 1),
+    ("pow", 2),
+    ("atan2", 2),
+    ("fmin", 2),
+    ("fmax", 2),
+    ("setpath", 2),
+    ("delpaths", 1),
+];
+
+/// Native functions that take value arguments.  Returns [None] if there's no such function
+fn native_with_args(name: &str, input: &Value, args: &[Value]) -> Option<Result<Value, Failure>> {
+    let result = match (name, args) {
+        ("has", [key]) => match (input, key) {
+            (Value::Object(_), Value::String(k)) => Ok(Value::Boolean(input.get(k).is_some())),
+            (Value::Array(values), Value::Integer(_) | Value::Float(_)) => {
+                let i = key.as_f64().unwrap_or(-1.0);
+                Ok(Value::Boolean(i >= 0.0 && i < values.len() as f64))
+            }
+            _ => Err(Failure::message(format!(
+                "Cannot check whether {} has a {} key",
+                input.type_name(),
+                key.type_name()
+            ))),
+        },
+        ("contains", [other]) => {
+            if input.type_name() == other.type_name() {
+                Ok(Value::Boolean(contains(input, other)))
+            } else {
+                Err(Failure::message(format!(
+                    "{} and {} cannot have their containment checked",
+                    input.describe(),
+                    other.describe()
+                )))
+            }
+        }
+        ("split", [separator]) => match (input, separator) {
+            (Value::String(s), Value::String(sep)) => Ok(Value::array(if s.is_empty() {
+                vec![]
+            } else if sep.is_empty() {
+                s.chars().map(|c| Value::string(c.to_string())).collect()
+            } else {
+                s.split(&**sep).map(Value::string).collect()
+            })),
+            _ => Err(Failure::message(
+                "split input and separator must be strings",
+            )),
+        },
+        ("join", [separator]) => {
+            let elements: Vec<&Value> = match input {
+                Value::Array(values) => values.iter().collect(),
+                Value::Object(members) => members.iter().map(|(_, v)| v).collect(),
+                value => {
+                    return Some(Err(Failure::message(format!(
+                        "Cannot iterate over {}",
+                        value.describe()
+                    ))))
+                }
+            };
+            let separator = match separator {
+                Value::String(s) => s,
+                value => {
+                    return Some(Err(Failure::message(format!(
+                        "{} is not a valid separator",
+                        value.describe()
+                    ))))
+                }
+            };
+            let mut joined = String::new();
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    joined.push_str(separator);
+                }
+                match element {
+                    Value::Null => {}
+                    Value::Array(_) | Value::Object(_) => {
+                        return Some(Err(Failure::message(format!(
+                            "Cannot join with {}",
+                            element.describe()
+                        ))))
+                    }
+                    value => joined.push_str(&value.to_text()),
+                }
+            }
+            Ok(Value::string(joined))
+        }
+        ("ltrimstr" | "rtrimstr", [affix]) => Ok(match (input, affix) {
+            (Value::String(s), Value::String(affix)) => {
+                let trimmed = if name == "ltrimstr" {
+                    s.strip_prefix(&**affix)
+                } else {
+                    s.strip_suffix(&**affix)
+                };
+                trimmed.map_or_else(|| input.clone(), Value::string)
+            }
+            _ => input.clone(),
+        }),
+        ("startswith" | "endswith", [affix]) => match (input, affix) {
+            (Value::String(s), Value::String(affix)) => {
+                Ok(Value::Boolean(if name == "startswith" {
+                    s.starts_with(&**affix)
+                } else {
+                    s.ends_with(&**affix)
+                }))
+            }
+            _ => Err(Failure::message(format!(
+                "{}() requires string inputs",
+                name
+            ))),
+        },
+        ("_strindices", [needle]) => match (input, needle) {
+            (Value::String(s), Value::String(needle)) if !needle.is_empty() => {
+                let positions = s
+                    .char_indices()
+                    .enumerate()
+                    .filter(|(_, (byte, _))| s[*byte..].starts_with(&**needle))
+                    .map(|(i, _)| Value::Integer(i as i64))
+                    .collect();
+                Ok(Value::array(positions))
+            }
+            (Value::String(_), Value::String(_)) => Ok(Value::Null),
+            _ => Err(Failure::message("Cannot determine indices of non-strings")),
+        },
+        ("flatten", [depth]) => match depth.as_f64() {
+            Some(depth) if depth < 0.0 => {
+                Err(Failure::message("flatten depth must not be negative"))
+            }
+            Some(depth) => flatten(input, depth),
+            None => Err(Failure::message("flatten depth must be a number")),
+        },
+        ("pow" | "atan2" | "fmin" | "fmax", [a, b]) => match (a.as_f64(), b.as_f64()) {
+            (Some(a), Some(b)) => Ok(Value::number(match name {
+                "pow" => a.powf(b),
+                "atan2" => a.atan2(b),
+                "fmin" => a.min(b),
+                _ => a.max(b),
+            })),
+            _ => Err(Failure::message(format!(
+                "{} requires numeric arguments",
+                name
+            ))),
+        },
+        ("setpath", [path, value]) => match path_components(path) {
+            Ok(components) => set_path(input.clone(), components, value.clone()),
+            Err(failure) => Err(failure),
+        },
+        ("delpaths", [paths]) => match paths {
+            Value::Array(paths) => paths
+                .iter()
+                .map(|path| path_components(path).map(|c| c.to_vec()))
+                .collect::<Result<Vec<_>, Failure>>()
+                .and_then(|paths| delete_paths(input.clone(), paths)),
+            _ => Err(Failure::message("Paths must be specified as an array")),
+        },
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Flatten nested arrays, down to a given depth
+fn flatten(input: &Value, depth: f64) -> Result<Value, Failure> {
+    fn flatten_into(values: &[Value], depth: f64, flattened: &mut Vec<Value>) {
+        for value in values {
+            match value {
+                Value::Array(nested) if depth > 0.0 => flatten_into(nested, depth - 1.0, flattened),
+                value => flattened.push(value.clone()),
+            }
+        }
+    }
+    match input {
+        Value::Array(values) => {
+            let mut flattened = vec![];
+            flatten_into(values, depth, &mut flattened);
+            Ok(Value::array(flattened))
+        }
+        value => Err(Failure::message(format!(
+            "Cannot flatten {}",
+            value.describe()
+        ))),
+    }
+}
+
+/// Containment, as performed by `contains`.  Strings are checked for substrings, arrays for elements contained
+/// within any element and objects for members contained within the corresponding member
+fn contains(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Object(_), Value::Object(members)) => {
+            members.iter().all(|(k, bv)| match a.get(k) {
+                Some(av) => av.type_name() == bv.type_name() && contains(av, bv),
+                None => false,
+            })
+        }
+        (Value::Array(avs), Value::Array(bvs)) => bvs.iter().all(|bv| {
+            avs.iter()
+                .any(|av| av.type_name() == bv.type_name() && contains(av, bv))
+        }),
+        (Value::String(a), Value::String(b)) => a.contains(&**b),
+        _ => a == b,
+    }
+}
+
+/// The mantissa of a float, scaled to lie within [0.5, 1)
+fn significand(f: f64) -> f64 {
+    if f == 0.0 || !f.is_finite() {
+        return f;
+    }
+    let exponent = f.abs().log2().floor() + 1.0;
+    f / 2f64.powf(exponent)
+}
+
+/// Apply a binary operator
+pub(super) fn binary_op(op: BinaryOp, lhs: &Value, rhs: &Value) -> Result<Value, Failure> {
+    let comparison = |test: fn(Ordering) -> bool| Ok(Value::Boolean(test(lhs.compare(rhs))));
+    match op {
+        BinaryOp::Eq => comparison(|o| o == Ordering::Equal),
+        BinaryOp::Ne => comparison(|o| o != Ordering::Equal),
+        BinaryOp::Lt => comparison(|o| o == Ordering::Less),
+        BinaryOp::Le => comparison(|o| o != Ordering::Greater),
+        BinaryOp::Gt => comparison(|o| o == Ordering::Greater),
+        BinaryOp::Ge => comparison(|o| o != Ordering::Less),
+        BinaryOp::Add => add(lhs, rhs),
+        BinaryOp::Subtract => match (lhs, rhs) {
+            (Value::Integer(l), Value::Integer(r)) => Ok(match l.checked_sub(*r) {
+                Some(difference) => Value::Integer(difference),
+                None => Value::Float(*l as f64 - *r as f64),
+            }),
+            (Value::Array(l), Value::Array(r)) => Ok(Value::array(
+                l.iter().filter(|v| !r.contains(v)).cloned().collect(),
+            )),
+            _ => arithmetic(lhs, rhs, "subtracted", |l, r| l - r),
+        },
+        BinaryOp::Multiply => match (lhs, rhs) {
+            (Value::Integer(l), Value::Integer(r)) => Ok(match l.checked_mul(*r) {
+                Some(product) => Value::Integer(product),
+                None => Value::Float(*l as f64 * *r as f64),
+            }),
+            (Value::String(s), n) | (n, Value::String(s)) if n.as_f64().is_some() => {
+                let n = n.as_f64().unwrap_or(0.0);
+                if n <= 0.0 {
+                    return Ok(Value::Null);
+                }
+                let count = n.ceil() as usize;
+                match count.checked_mul(s.len()) {
+                    Some(length) if length <= MAX_REPEAT_LENGTH => {
+                        Ok(Value::string(s.repeat(count)))
+                    }
+                    _ => Err(Failure::message("Repeat string result too long")),
+                }
+            }
+            (Value::Object(_), Value::Object(_)) => Ok(deep_merge(lhs, rhs)),
+            _ => arithmetic(lhs, rhs, "multiplied", |l, r| l * r),
+        },
+        BinaryOp::Divide => match (lhs, rhs) {
+            (Value::String(_), Value::String(_)) => {
+                native_with_args("split", lhs, std::slice::from_ref(rhs)).unwrap_or(Ok(Value::Null))
+            }
+            _ if rhs.as_f64() == Some(0.0) && lhs.as_f64().is_some() => {
+                Err(Failure::message(format!(
+                    "{} and {} cannot be divided because the divisor is zero",
+                    lhs.describe(),
+                    rhs.describe()
+                )))
+            }
+            (Value::Integer(l), Value::Integer(r)) if l % r == 0 => Ok(Value::Integer(l / r)),
+            _ => arithmetic(lhs, rhs, "divided", |l, r| l / r),
+        },
+        BinaryOp::Modulo => match (lhs.as_f64(), rhs.as_f64()) {
+            (Some(l), Some(r)) => {
+                let (l, r) = (l as i64, r as i64);
+                if r == 0 {
+                    Err(Failure::message(format!(
+                        "{} and {} cannot be divided because the divisor is zero",
+                        lhs.describe(),
+                        rhs.describe()
+                    )))
+                } else {
+                    Ok(Value::Integer(l.wrapping_rem(r.wrapping_abs())))
+                }
+            }
+            _ => Err(Failure::message(format!(
+                "{} and {} cannot be divided",
+                lhs.describe(),
+                rhs.describe()
+            ))),
+        },
+    }
+}
+
+/// Addition, which is also defined for strings, arrays and objects. `null` acts as the identity
+fn add(lhs: &Value, rhs: &Value) -> Result<Value, Failure> {
+    match (lhs, rhs) {
+        (Value::Null, value) | (value, Value::Null) => Ok(value.clone()),
+        (Value::Integer(l), Value::Integer(r)) => Ok(match l.checked_add(*r) {
+            Some(sum) => Value::Integer(sum),
+            None => Value::Float(*l as f64 + *r as f64),
+        }),
+        (Value::String(l), Value::String(r)) => Ok(Value::string(format!("{}{}", l, r))),
+        (Value::Array(l), Value::Array(r)) => {
+            Ok(Value::array(l.iter().chain(r.iter()).cloned().collect()))
+        }
+        (Value::Object(l), Value::Object(r)) => {
+            let mut members = l.to_vec();
+            for (key, value) in r.iter() {
+                match members.iter_mut().find(|(existing, _)| existing == key) {
+                    Some(member) => member.1 = value.clone(),
+                    None => members.push((key.clone(), value.clone())),
+                }
+            }
+            Ok(Value::object(members))
+        }
+        _ => arithmetic(lhs, rhs, "added", |l, r| l + r),
+    }
+}
+
+/// Numeric arithmetic, which fails for anything other than a pair of numbers
+fn arithmetic(
+    lhs: &Value,
+    rhs: &Value,
+    operation: &str,
+    op: fn(f64, f64) -> f64,
+) -> Result<Value, Failure> {
+    match (lhs.as_f64(), rhs.as_f64()) {
+        (Some(l), Some(r)) => Ok(Value::number(op(l, r))),
+        _ => Err(Failure::message(format!(
+            "{} and {} cannot be {}",
+            lhs.describe(),
+            rhs.describe(),
+            operation
+        ))),
+    }
+}
+
+/// Recursively merge two objects, with members of the right hand side taking precedence
+fn deep_merge(lhs: &Value, rhs: &Value) -> Value {
+    match (lhs, rhs) {
+        (Value::Object(l), Value::Object(r)) => {
+            let mut members = l.to_vec();
+            for (key, value) in r.iter() {
+                match members.iter_mut().find(|(existing, _)| existing == key) {
+                    Some(member) => member.1 = deep_merge(&member.1, value),
+                    None => members.push((key.clone(), value.clone())),
+                }
+            }
+            Value::object(members)
+        }
+        _ => rhs.clone(),
+    }
+}
+
+/// Apply a named format (e.g. `@csv`) to a value
+pub(super) fn apply_format(format: &str, value: &Value) -> Result<String, Failure> {
+    match format {
+        "text" => Ok(value.to_text()),
+        "json" => Ok(value.to_json_text()),
+        "csv" | "tsv" => {
+            let values = match value {
+                Value::Array(values) => values,
+                value => {
+                    return Err(Failure::message(format!(
+                        "{} cannot be {}-formatted, only an array can be",
+                        value.describe(),
+                        format
+                    )))
+                }
+            };
+            let fields = values
+                .iter()
+                .map(|field| match field {
+                    Value::Null => Ok(String::new()),
+                    Value::String(s) if format == "csv" => {
+                        Ok(format!("\"{}\"", s.replace('"', "\"\"")))
+                    }
+                    Value::String(s) => Ok(s
+                        .replace('\\', "\\\\")
+                        .replace('\t', "\\t")
+                        .replace('\n', "\\n")
+                        .replace('\r', "\\r")),
+                    Value::Array(_) | Value::Object(_) => Err(Failure::message(format!(
+                        "{} is not valid in a {} row",
+                        field.describe(),
+                        format
+                    ))),
+                    field => Ok(field.to_text()),
+                })
+                .collect::<Result<Vec<String>, Failure>>()?;
+            Ok(fields.join(if format == "csv" { "," } else { "\t" }))
+        }
+        "html" => Ok(value
+            .to_text()
+            .chars()
+            .map(|c| match c {
+                '<' => String::from("&lt;"),
+                '>' => String::from("&gt;"),
+                '&' => String::from("&amp;"),
+                '\'' => String::from("&#39;"),
+                '"' => String::from("&quot;"),
+                c => c.to_string(),
+            })
+            .collect()),
+        "uri" => Ok(value
+            .to_text()
+            .bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    (b as char).to_string()
+                }
+                b => format!("%{:02X}", b),
+            })
+            .collect()),
+        "sh" => {
+            let quote = |v: &Value| match v {
+                Value::String(s) => Ok(format!("'{}'", s.replace('\'', "'\\''"))),
+                Value::Array(_) | Value::Object(_) => Err(Failure::message(format!(
+                    "{} can not be escaped for shell",
+                    v.describe()
+                ))),
+                v => Ok(v.to_text()),
+            };
+            match value {
+                Value::Array(values) => Ok(values
+                    .iter()
+                    .map(quote)
+                    .collect::<Result<Vec<String>, Failure>>()?
+                    .join(" ")),
+                value => quote(value),
+            }
+        }
+        "base64" => Ok(base64_encode(value.to_text().as_bytes())),
+        "base64d" => {
+            let text = value.to_text();
+            match base64_decode(&text) {
+                Some(bytes) => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+                None => Err(Failure::message(format!(
+                    "{} is not valid base64 data",
+                    value.describe()
+                ))),
+            }
+        }
+        _ => Err(Failure::message(format!(
+            "{} is not a valid format",
+            format
+        ))),
+    }
+}
+
+/// The standard base64 alphabet
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes as (padded) base64
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let triple = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, b)| acc | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(triple >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decode base64 text, with or without padding
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(text.len() / 4 * 3);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.trim_end_matches('=').bytes() {
+        let sextet = BASE64_ALPHABET.iter().position(|a| *a == c)? as u32;
+        buffer = (buffer << 6) | sextet;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits & 0xff) as u8);
+        }
+    }
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::tests::{failure, outputs, run};
+
+    #[test]
+    fn repeats_strings() {
+        let repeat = |n: Value| binary_op(BinaryOp::Multiply, &Value::string("ab"), &n);
+        assert_eq!(
+            repeat(Value::Integer(3)).unwrap().to_json_text(),
+            r#""ababab""#
+        );
+        assert_eq!(
+            repeat(Value::Float(1.5)).unwrap().to_json_text(),
+            r#""abab""#
+        );
+        assert_eq!(repeat(Value::Integer(0)).unwrap().to_json_text(), "null");
+        assert_eq!(outputs(r#"2 * "x""#), [r#""xx""#]);
+    }
+
+    #[test]
+    fn refuses_oversized_repeats() {
+        assert_eq!(
+            failure(r#""abc" * 100000000"#),
+            "Repeat string result too long"
+        );
+        assert_eq!(failure(r#""a" * 1e300"#), "Repeat string result too long");
+        assert_eq!(
+            failure(r#"[limit(64; repeat(1))] | reduce .[] as $x ("ab"; . * 2)"#),
+            "Repeat string result too long"
+        );
+    }
+
+    #[test]
+    fn arrays_and_objects() {
+        assert_eq!(
+            outputs("[3, 1, 2] | sort, reverse, add, length, min, max"),
+            ["[1,2,3]", "[2,1,3]", "6", "3", "1", "3"]
+        );
+        assert_eq!(
+            outputs("[1, [2, [3]]] | flatten, flatten(1)"),
+            ["[1,2,3]", "[1,2,[3]]"]
+        );
+        assert_eq!(outputs("[1, 2, 1] | unique"), ["[1,2]"]);
+        assert_eq!(
+            outputs(r#"[{a: 2, b: "x"}, {a: 1, b: "y"}] | sort_by(.a) | map(.b)"#),
+            [r#"["y","x"]"#]
+        );
+        assert_eq!(
+            outputs(r#"[{a: 1}, {a: 2}, {a: 1}] | group_by(.a) | map(length)"#),
+            ["[2,1]"]
+        );
+        assert_eq!(
+            outputs(r#"{b: 1, a: 2} | keys, keys_unsorted"#),
+            [r#"["a","b"]"#, r#"["b","a"]"#]
+        );
+        assert_eq!(
+            outputs(
+                r#"{a: 1} | to_entries, (to_entries | from_entries), with_entries(.value += 1)"#
+            ),
+            [r#"[{"key":"a","value":1}]"#, r#"{"a":1}"#, r#"{"a":2}"#]
+        );
+        assert_eq!(outputs("[range(5)] | map(select(. % 2 == 0))"), ["[0,2,4]"]);
+        assert_eq!(outputs("[1, null, 2] | [.[] | values]"), ["[1,2]"]);
+        assert_eq!(outputs(r#"{a: [1, {b: 2}]} | [paths] | length"#), ["4"]);
+        assert_eq!(outputs(r#"{a: 1} | has("a"), has("b")"#), ["true", "false"]);
+        assert_eq!(
+            outputs("[1, 2] | any(. > 1), all(. > 1)"),
+            ["true", "false"]
+        );
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(
+            outputs(r#""a,b,c" | split(",") | join("-")"#),
+            [r#""a-b-c""#]
+        );
+        assert_eq!(
+            outputs(r#""Hello" | ascii_downcase, ascii_upcase"#),
+            [r#""hello""#, r#""HELLO""#]
+        );
+        assert_eq!(
+            outputs(r#""  x  " | trim, ltrim, rtrim"#),
+            [r#""x""#, r#""x  ""#, r#""  x""#]
+        );
+        assert_eq!(outputs(r#""héllo" | length, utf8bytelength"#), ["5", "6"]);
+        assert_eq!(
+            outputs(r#""abc" | explode, (explode | implode)"#),
+            ["[97,98,99]", r#""abc""#]
+        );
+        assert_eq!(
+            outputs(r#""foobar" | startswith("foo"), endswith("foo")"#),
+            ["true", "false"]
+        );
+        assert_eq!(
+            outputs(r#""foobar" | ltrimstr("foo"), rtrimstr("bar")"#),
+            [r#""bar""#, r#""foo""#]
+        );
+        assert_eq!(
+            outputs(r#""test 123" | test("\\d+"), [match("\\d").string]"#),
+            ["true", r#"["1"]"#]
+        );
+        assert_eq!(
+            outputs(r#""aXbX" | sub("X"; "-"), gsub("X"; "-")"#),
+            [r#""a-bX""#, r#""a-b-""#]
+        );
+        assert_eq!(
+            outputs(r#""hi" | @base64, (@base64 | @base64d)"#),
+            [r#""aGk=""#, r#""hi""#]
+        );
+        assert_eq!(
+            outputs(r#""a b" | @uri, @sh"#),
+            [r#""a%20b""#, r#""'a b'""#]
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(
+            outputs(r#"[1, "1", [1]] | map(tostring)"#),
+            [r#"["1","1","[1]"]"#]
+        );
+        assert_eq!(outputs(r#""12" | tonumber"#), ["12"]);
+        assert_eq!(outputs(r#""[1,2]" | fromjson"#), ["[1,2]"]);
+        assert_eq!(outputs("[1, 2] | tojson"), [r#""[1,2]""#]);
+        assert_eq!(
+            outputs(r#"[null, true, 1, "a", [], {}] | map(type)"#),
+            [r#"["null","boolean","number","string","array","object"]"#]
+        );
+        assert!(run(r#""x" | tonumber"#, "null").is_err());
+        assert!(run("{} | fromjson", "null").is_err());
+    }
+
+    #[test]
+    fn maths() {
+        assert_eq!(outputs("[3.7 | floor, ceil, round, trunc]"), ["[3,4,4,3]"]);
+        assert_eq!(
+            outputs("[-2 | abs], [16 | sqrt], [pow(2; 10)], [10 | log10]"),
+            ["[2]", "[4]", "[1024]", "[1]"]
+        );
+        assert_eq!(
+            outputs("[nan | isnan], [infinite | isinfinite], [1 | isnormal]"),
+            ["[true]", "[true]", "[true]"]
+        );
+        assert_eq!(outputs("[nan] | tojson"), [r#""[null]""#]);
+    }
+}
@@ -0,0 +1,792 @@
+//! Evaluation of transformation programs.  Evaluation is performed in continuation passing style, with each
+//! expression pushing its outputs into a sink.  This means that generators can be cut short (e.g. by `first` or
+//! `limit`) without having to be evaluated in full, and also allows paths to be tracked alongside values so that
+//! `path(f)` and the assignment operators work with arbitrary path expressions
+use super::builtins::{apply_format, binary_op, PRELUDE};
+use super::parser::parse_program;
+use super::paths::{delete_paths, get_path, index, set_path, slice, slice_key};
+use super::value::Value;
+use super::{AssignOp, Expr, FunctionDef, Literal, ObjectKey, StringPart};
+use crate::errors::{ChiselError, ChiselResult};
+use chisel_json::JsonValue;
+use regex::Regex;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The deepest that evaluation may nest before it's abandoned, which keeps well within the stack of the evaluation
+/// thread. Every function call nests evaluation, as does every output of a recursive generator
+const MAX_EVAL_DEPTH: usize = 5_000;
+
+/// A path to a location within a value, made up of object keys, array indices and slices
+pub(super) type Path = Vec<Value>;
+
+/// A value flowing through a program, along with its path relative to the input of an enclosing `path(f)`
+#[derive(Debug, Clone)]
+pub(super) struct Item {
+    pub value: Value,
+    pub path: Option<Path>,
+}
+
+impl Item {
+    /// An item without any path information
+    pub fn new(value: Value) -> Item {
+        Item { value, path: None }
+    }
+
+    /// An item which is the root of a path expression
+    pub fn root(value: Value) -> Item {
+        Item {
+            value,
+            path: Some(vec![]),
+        }
+    }
+
+    /// Derive a child item, extending the path (if any) by the given key
+    pub fn child(&self, key: Value, value: Value) -> Item {
+        Item {
+            value,
+            path: self.path.as_ref().map(|path| {
+                let mut path = path.clone();
+                path.push(key);
+                path
+            }),
+        }
+    }
+}
+
+/// The reasons evaluation may stop early
+#[derive(Debug)]
+pub(super) enum Failure {
+    /// A runtime error, carrying the error value
+    Error(Value),
+    /// Unwinding back to the point identified by a label, used to cut generators short
+    Break(usize),
+}
+
+impl Failure {
+    /// An error with a plain message
+    pub fn message(message: impl Into<String>) -> Failure {
+        Failure::Error(Value::string(message.into()))
+    }
+}
+
+/// The result of pushing an output into a sink
+pub(super) type Flow = Result<(), Failure>;
+
+/// A consumer of outputs
+pub(super) type Sink<'s> = &'s mut dyn FnMut(Item) -> Flow;
+
+/// Individual bindings within an environment
+enum Frame<'p> {
+    /// A variable, e.g. `$x`
+    Variable(&'p str, Value),
+    /// A function definition
+    Function(&'p FunctionDef),
+    /// A filter argument, to be evaluated within the environment of the caller
+    Closure(&'p str, &'p Expr, Rc<Env<'p>>),
+    /// A value argument, which may also be called as a filter
+    Value(&'p str, Value),
+}
+
+/// An environment is a linked list of bindings, shared between closures
+pub(super) struct Env<'p> {
+    frame: Option<Frame<'p>>,
+    parent: Option<Rc<Env<'p>>>,
+}
+
+/// The things a function call may resolve to
+enum Callable<'p> {
+    Function(&'p FunctionDef, Rc<Env<'p>>),
+    Closure(&'p Expr, Rc<Env<'p>>),
+    Value(Value),
+}
+
+impl<'p> Env<'p> {
+    fn root() -> Rc<Env<'p>> {
+        Rc::new(Env {
+            frame: None,
+            parent: None,
+        })
+    }
+
+    fn push(parent: &Rc<Env<'p>>, frame: Frame<'p>) -> Rc<Env<'p>> {
+        Rc::new(Env {
+            frame: Some(frame),
+            parent: Some(parent.clone()),
+        })
+    }
+
+    fn variable(&self, name: &str) -> Option<&Value> {
+        let mut current = self;
+        loop {
+            if let Some(Frame::Variable(n, value)) = &current.frame {
+                if *n == name {
+                    return Some(value);
+                }
+            }
+            current = current.parent.as_deref()?;
+        }
+    }
+
+    /// Resolve a function call by name and arity
+    fn lookup(env: &Rc<Env<'p>>, name: &str, arity: usize) -> Option<Callable<'p>> {
+        let mut current = env;
+        loop {
+            match &current.frame {
+                Some(Frame::Function(def)) if def.name == name && def.params.len() == arity => {
+                    return Some(Callable::Function(def, current.clone()));
+                }
+                Some(Frame::Closure(n, expr, closure_env)) if *n == name && arity == 0 => {
+                    return Some(Callable::Closure(expr, closure_env.clone()));
+                }
+                Some(Frame::Value(n, value)) if *n == name && arity == 0 => {
+                    return Some(Callable::Value(value.clone()));
+                }
+                _ => {}
+            }
+            current = current.parent.as_ref()?;
+        }
+    }
+}
+
+/// The evaluator, which holds the parsed prelude of functions defined in terms of the language itself
+pub(super) struct Evaluator {
+    /// The prelude, as a chain of definitions
+    prelude: Expr,
+    /// Source of unique labels
+    labels: Cell<usize>,
+    /// The current nesting depth of evaluation
+    depth: Cell<usize>,
+    /// Compiled regular expressions, keyed on pattern and flags
+    regexes: RefCell<HashMap<String, Regex>>,
+}
+
+impl Evaluator {
+    pub fn new() -> ChiselResult<Evaluator> {
+        Ok(Evaluator {
+            prelude: parse_program(PRELUDE)?,
+            labels: Cell::new(0),
+            depth: Cell::new(0),
+            regexes: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// Run a program against an input, collecting all of the outputs
+    pub fn run<'p>(
+        &'p self,
+        expr: &'p Expr,
+        input: JsonValue<'static>,
+    ) -> ChiselResult<Vec<JsonValue<'static>>> {
+        let mut env = Env::root();
+        let mut definitions = &self.prelude;
+        while let Expr::Define(def, rest) = definitions {
+            env = Env::push(&env, Frame::Function(def));
+            definitions = rest;
+        }
+
+        let mut results = vec![];
+        let input = Item::new(Value::from_json(input));
+        let outcome = self.eval(expr, &env, &input, &mut |item| {
+            results.push(item.value.into_json());
+            Ok(())
+        });
+        match outcome {
+            Ok(_) | Err(Failure::Break(_)) => Ok(results),
+            Err(Failure::Error(Value::String(message))) => {
+                Err(ChiselError::TransformFailed(message.to_string()))
+            }
+            Err(Failure::Error(value)) => Err(ChiselError::TransformFailed(format!(
+                "{} (not a string)",
+                value.to_json_text()
+            ))),
+        }
+    }
+
+    /// Allocate a new label, for use with [Failure::Break]
+    pub fn next_label(&self) -> usize {
+        let label = self.labels.get();
+        self.labels.set(label + 1);
+        label
+    }
+
+    /// Compile a regular expression, caching the result
+    pub fn regex(&self, pattern: &str) -> Result<Regex, Failure> {
+        if let Some(regex) = self.regexes.borrow().get(pattern) {
+            return Ok(regex.clone());
+        }
+        match Regex::new(pattern) {
+            Ok(regex) => {
+                self.regexes
+                    .borrow_mut()
+                    .insert(pattern.to_string(), regex.clone());
+                Ok(regex)
+            }
+            Err(err) => Err(Failure::message(format!(
+                "{} is not a valid regex: {}",
+                pattern, err
+            ))),
+        }
+    }
+
+    /// Evaluate an expression against an input, pushing each output into the sink
+    pub fn eval<'p>(&self, expr: &'p Expr, env: &Rc<Env<'p>>, input: &Item, out: Sink) -> Flow {
+        let depth = self.depth.get();
+        if depth >= MAX_EVAL_DEPTH {
+            return Err(Failure::message("recursion limit exceeded"));
+        }
+        self.depth.set(depth + 1);
+        let flow = self.eval_expr(expr, env, input, out);
+        self.depth.set(depth);
+        flow
+    }
+
+    /// Evaluate an expression, at the current depth
+    fn eval_expr<'p>(&self, expr: &'p Expr, env: &Rc<Env<'p>>, input: &Item, out: Sink) -> Flow {
+        match expr {
+            Expr::Identity => out(input.clone()),
+            Expr::RecurseAll => self.recurse_all(input, out),
+            Expr::Literal(literal) => out(Item::new(literal_value(literal))),
+            Expr::String(parts, format) => {
+                self.interpolate(parts, String::new(), format.as_deref(), env, input, out)
+            }
+            Expr::Format(format) => out(Item::new(Value::string(apply_format(
+                format,
+                &input.value,
+            )?))),
+            Expr::Index(target, key) => self.eval(target, env, input, &mut |t| {
+                self.eval(key, env, input, &mut |k| {
+                    let value = index(&t.value, &k.value)?;
+                    out(t.child(k.value, value))
+                })
+            }),
+            Expr::Slice(target, from, to) => self.eval(target, env, input, &mut |t| {
+                self.eval_optional(from.as_deref(), env, input, &mut |f| {
+                    self.eval_optional(to.as_deref(), env, input, &mut |e| {
+                        let value = slice(&t.value, &f.value, &e.value)?;
+                        out(t.child(slice_key(f.value.clone(), e.value), value))
+                    })
+                })
+            }),
+            Expr::Iterate(target) => self.eval(target, env, input, &mut |t| match &t.value {
+                Value::Array(values) => {
+                    for (i, value) in values.iter().enumerate() {
+                        out(t.child(Value::Integer(i as i64), value.clone()))?;
+                    }
+                    Ok(())
+                }
+                Value::Object(members) => {
+                    for (key, value) in members.iter() {
+                        out(t.child(Value::string(key.as_str()), value.clone()))?;
+                    }
+                    Ok(())
+                }
+                Value::Null => Err(Failure::message("Cannot iterate over null")),
+                value => Err(Failure::message(format!(
+                    "Cannot iterate over {}",
+                    value.describe()
+                ))),
+            }),
+            Expr::Array(None) => out(Item::new(Value::array(vec![]))),
+            Expr::Array(Some(body)) => {
+                let mut values = vec![];
+                self.eval(body, env, input, &mut |item| {
+                    values.push(item.value);
+                    Ok(())
+                })?;
+                out(Item::new(Value::array(values)))
+            }
+            Expr::Object(entries) => self.construct(entries, vec![], env, input, out),
+            Expr::Negate(operand) => self.eval(operand, env, input, &mut |item| match item.value {
+                Value::Integer(i) => out(Item::new(match i.checked_neg() {
+                    Some(negated) => Value::Integer(negated),
+                    None => Value::Float(-(i as f64)),
+                })),
+                Value::Float(f) => out(Item::new(Value::Float(-f))),
+                value => Err(Failure::message(format!(
+                    "{} cannot be negated",
+                    value.describe()
+                ))),
+            }),
+            Expr::Pipe(lhs, rhs) => {
+                self.eval(lhs, env, input, &mut |item| self.eval(rhs, env, &item, out))
+            }
+            Expr::Comma(lhs, rhs) => {
+                self.eval(lhs, env, input, out)?;
+                self.eval(rhs, env, input, out)
+            }
+            Expr::Binary(op, lhs, rhs) => self.eval(rhs, env, input, &mut |r| {
+                self.eval(lhs, env, input, &mut |l| {
+                    out(Item::new(binary_op(*op, &l.value, &r.value)?))
+                })
+            }),
+            Expr::And(lhs, rhs) => self.eval(lhs, env, input, &mut |l| {
+                if !l.value.is_truthy() {
+                    return out(Item::new(Value::Boolean(false)));
+                }
+                self.eval(rhs, env, input, &mut |r| {
+                    out(Item::new(Value::Boolean(r.value.is_truthy())))
+                })
+            }),
+            Expr::Or(lhs, rhs) => self.eval(lhs, env, input, &mut |l| {
+                if l.value.is_truthy() {
+                    return out(Item::new(Value::Boolean(true)));
+                }
+                self.eval(rhs, env, input, &mut |r| {
+                    out(Item::new(Value::Boolean(r.value.is_truthy())))
+                })
+            }),
+            Expr::Alternative(lhs, rhs) => {
+                // errors on the left hand side are suppressed, in the same way as a missing value
+                let mut values = vec![];
+                let outcome = self.eval(lhs, env, input, &mut |item| {
+                    if item.value.is_truthy() {
+                        values.push(item);
+                    }
+                    Ok(())
+                });
+                if let Err(Failure::Break(label)) = outcome {
+                    return Err(Failure::Break(label));
+                }
+                if values.is_empty() {
+                    return self.eval(rhs, env, input, out);
+                }
+                for item in values {
+                    out(item)?;
+                }
+                Ok(())
+            }
+            Expr::Assign(op, lhs, rhs) => self.assign(*op, lhs, rhs, env, input, out),
+            Expr::If(branches, otherwise) => {
+                self.conditional(branches, otherwise.as_deref(), env, input, out)
+            }
+            Expr::Try(body, handler) => {
+                // errors raised downstream of the body pass straight through, rather than being caught
+                let label = self.next_label();
+                let mut downstream = None;
+                let outcome = self.eval(body, env, input, &mut |item| match out(item) {
+                    Ok(()) => Ok(()),
+                    Err(failure) => {
+                        downstream = Some(failure);
+                        Err(Failure::Break(label))
+                    }
+                });
+                match outcome {
+                    Err(Failure::Break(l)) if l == label => match downstream {
+                        Some(failure) => Err(failure),
+                        None => Ok(()),
+                    },
+                    Err(Failure::Error(error)) => match handler {
+                        Some(handler) => self.eval(handler, env, &Item::new(error), out),
+                        None => Ok(()),
+                    },
+                    outcome => outcome,
+                }
+            }
+            Expr::Reduce(source, name, init, update) => {
+                self.eval(init, env, input, &mut |initial| {
+                    let mut state = Some(initial);
+                    self.eval(source, env, input, &mut |item| {
+                        let scoped = Env::push(env, Frame::Variable(name, item.value));
+                        let current = state.take().unwrap_or_else(|| Item::new(Value::Null));
+                        self.eval(update, &scoped, &current, &mut |updated| {
+                            state = Some(updated);
+                            Ok(())
+                        })
+                    })?;
+                    out(state.unwrap_or_else(|| Item::new(Value::Null)))
+                })
+            }
+            Expr::Foreach(source, name, init, update, extract) => {
+                self.eval(init, env, input, &mut |initial| {
+                    let mut state = initial;
+                    self.eval(source, env, input, &mut |item| {
+                        let scoped = Env::push(env, Frame::Variable(name, item.value));
+                        let current = state.clone();
+                        self.eval(update, &scoped, &current, &mut |updated| {
+                            state = updated.clone();
+                            match extract {
+                                Some(extract) => self.eval(extract, &scoped, &updated, out),
+                                None => out(updated),
+                            }
+                        })
+                    })
+                })
+            }
+            Expr::Bind(source, name, body) => self.eval(source, env, input, &mut |item| {
+                let scoped = Env::push(env, Frame::Variable(name, item.value));
+                self.eval(body, &scoped, input, out)
+            }),
+            Expr::Variable(name) => match env.variable(name) {
+                Some(value) => out(Item::new(value.clone())),
+                None if name == "ENV" => out(Item::new(environment())),
+                None => Err(Failure::message(format!("${} is not defined", name))),
+            },
+            Expr::Call(name, args) => match Env::lookup(env, name, args.len()) {
+                Some(Callable::Function(def, def_env)) => {
+                    self.bind_params(def, 0, def_env, args, env, input, out)
+                }
+                Some(Callable::Closure(expr, closure_env)) => {
+                    self.eval(expr, &closure_env, input, out)
+                }
+                Some(Callable::Value(value)) => out(Item::new(value)),
+                None => match self.call_builtin(name, args, env, input, out) {
+                    Some(flow) => flow,
+                    None => Err(Failure::message(format!(
+                        "{}/{} is not defined",
+                        name,
+                        args.len()
+                    ))),
+                },
+            },
+            Expr::Define(def, rest) => {
+                let scoped = Env::push(env, Frame::Function(def));
+                self.eval(rest, &scoped, input, out)
+            }
+        }
+    }
+
+    /// Evaluate an optional expression, with a missing expression producing `null`
+    fn eval_optional<'p>(
+        &self,
+        expr: Option<&'p Expr>,
+        env: &Rc<Env<'p>>,
+        input: &Item,
+        out: Sink,
+    ) -> Flow {
+        match expr {
+            Some(expr) => self.eval(expr, env, input, out),
+            None => out(Item::new(Value::Null)),
+        }
+    }
+
+    /// Evaluate an expression, returning just its first output
+    pub fn first<'p>(
+        &self,
+        expr: &'p Expr,
+        env: &Rc<Env<'p>>,
+        input: &Item,
+    ) -> Result<Option<Item>, Failure> {
+        let label = self.next_label();
+        let mut found = None;
+        let outcome = self.eval(expr, env, input, &mut |item| {
+            found = Some(item);
+            Err(Failure::Break(label))
+        });
+        match outcome {
+            Err(Failure::Break(l)) if l != label => Err(Failure::Break(l)),
+            Err(Failure::Error(error)) => Err(Failure::Error(error)),
+            _ => Ok(found),
+        }
+    }
+
+    /// Evaluate a path expression against a value, collecting the paths of all the outputs
+    pub fn collect_paths<'p>(
+        &self,
+        expr: &'p Expr,
+        env: &Rc<Env<'p>>,
+        value: &Value,
+    ) -> Result<Vec<Path>, Failure> {
+        let mut paths = vec![];
+        self.eval(expr, env, &Item::root(value.clone()), &mut |item| {
+            paths.push(path_of(item)?);
+            Ok(())
+        })?;
+        Ok(paths)
+    }
+
+    /// Output an item and all of its descendants, as performed by `..`
+    pub fn recurse_all(&self, item: &Item, out: Sink) -> Flow {
+        out(item.clone())?;
+        match &item.value {
+            Value::Array(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    self.recurse_all(&item.child(Value::Integer(i as i64), value.clone()), out)?;
+                }
+                Ok(())
+            }
+            Value::Object(members) => {
+                for (key, value) in members.iter() {
+                    self.recurse_all(&item.child(Value::string(key.as_str()), value.clone()), out)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Evaluate a (possibly interpolated) string, working backwards through the parts
+    fn interpolate<'p>(
+        &self,
+        parts: &'p [StringPart],
+        suffix: String,
+        format: Option<&str>,
+        env: &Rc<Env<'p>>,
+        input: &Item,
+        out: Sink,
+    ) -> Flow {
+        match parts.split_last() {
+            None => out(Item::new(Value::string(suffix))),
+            Some((StringPart::Literal(text), rest)) => {
+                self.interpolate(rest, format!("{}{}", text, suffix), format, env, input, out)
+            }
+            Some((StringPart::Interpolation(expr), rest)) => {
+                self.eval(expr, env, input, &mut |item| {
+                    let text = match format {
+                        Some(format) => apply_format(format, &item.value)?,
+                        None => item.value.to_text(),
+                    };
+                    self.interpolate(rest, text + &suffix, format, env, input, out)
+                })
+            }
+        }
+    }
+
+    /// Construct objects from a list of entries, generating one object for each combination of keys and values
+    fn construct<'p>(
+        &self,
+        entries: &'p [(ObjectKey, Option<Expr>)],
+        members: Vec<(String, Value)>,
+        env: &Rc<Env<'p>>,
+        input: &Item,
+        out: Sink,
+    ) -> Flow {
+        let ((entry_key, value), rest) = match entries.split_first() {
+            Some(split) => split,
+            None => return out(Item::new(Value::object(members))),
+        };
+        self.object_keys(entry_key, env, input, &mut |key| {
+            let mut with_value = |value: Value| {
+                let mut members = members.clone();
+                match members.iter_mut().find(|(existing, _)| *existing == key) {
+                    Some(member) => member.1 = value,
+                    None => members.push((key.clone(), value)),
+                }
+                self.construct(rest, members, env, input, out)
+            };
+            match (value, entry_key) {
+                (Some(value), _) => {
+                    self.eval(value, env, input, &mut |item| with_value(item.value))
+                }
+                (None, ObjectKey::Variable(name)) => match env.variable(name) {
+                    Some(value) => with_value(value.clone()),
+                    None => Err(Failure::message(format!("${} is not defined", name))),
+                },
+                (None, _) => with_value(index(&input.value, &Value::string(key.as_str()))?),
+            }
+        })
+    }
+
+    /// Generate the keys for an object construction entry
+    fn object_keys<'p>(
+        &self,
+        key: &'p ObjectKey,
+        env: &Rc<Env<'p>>,
+        input: &Item,
+        out: &mut dyn FnMut(String) -> Flow,
+    ) -> Flow {
+        match key {
+            ObjectKey::Literal(name) | ObjectKey::Variable(name) => out(name.clone()),
+            ObjectKey::String(parts, format) => self.interpolate(
+                parts,
+                String::new(),
+                format.as_deref(),
+                env,
+                input,
+                &mut |item| out(item.value.to_text()),
+            ),
+            ObjectKey::Computed(expr) => {
+                self.eval(expr, env, input, &mut |item| match item.value {
+                    Value::String(key) => out(key.to_string()),
+                    value => Err(Failure::message(format!(
+                        "Object keys must be strings, not {}",
+                        value.describe()
+                    ))),
+                })
+            }
+        }
+    }
+
+    /// Evaluate the branches of a conditional in turn
+    fn conditional<'p>(
+        &self,
+        branches: &'p [(Expr, Expr)],
+        otherwise: Option<&'p Expr>,
+        env: &Rc<Env<'p>>,
+        input: &Item,
+        out: Sink,
+    ) -> Flow {
+        match branches.split_first() {
+            None => match otherwise {
+                Some(otherwise) => self.eval(otherwise, env, input, out),
+                None => out(input.clone()),
+            },
+            Some(((condition, then), rest)) => self.eval(condition, env, input, &mut |c| {
+                if c.value.is_truthy() {
+                    self.eval(then, env, input, out)
+                } else {
+                    self.conditional(rest, otherwise, env, input, out)
+                }
+            }),
+        }
+    }
+
+    /// Evaluate an assignment, by locating the paths selected by the left hand side and then updating each in turn
+    fn assign<'p>(
+        &self,
+        op: AssignOp,
+        lhs: &'p Expr,
+        rhs: &'p Expr,
+        env: &Rc<Env<'p>>,
+        input: &Item,
+        out: Sink,
+    ) -> Flow {
+        let paths = self.collect_paths(lhs, env, &input.value)?;
+        if op == AssignOp::Update {
+            // the first output of the update replaces the current value, with no output deleting it
+            let mut value = input.value.clone();
+            let mut deletions = vec![];
+            for path in paths {
+                let current = get_path(&value, &path)?;
+                match self.first(rhs, env, &Item::new(current))? {
+                    Some(updated) => value = set_path(value, &path, updated.value)?,
+                    None => deletions.push(path),
+                }
+            }
+            return out(Item::new(delete_paths(value, deletions)?));
+        }
+
+        self.eval(rhs, env, input, &mut |r| {
+            let mut value = input.value.clone();
+            for path in &paths {
+                let updated = match op {
+                    AssignOp::Arithmetic(op) => binary_op(op, &get_path(&value, path)?, &r.value)?,
+                    AssignOp::Alternative => match get_path(&value, path)? {
+                        current if current.is_truthy() => current,
+                        _ => r.value.clone(),
+                    },
+                    _ => r.value.clone(),
+                };
+                value = set_path(value, path, updated)?;
+            }
+            out(Item::new(value))
+        })
+    }
+
+    /// Call a user-defined function, binding each of the parameters in turn.  Value parameters are evaluated
+    /// up front, with each combination of argument values resulting in a separate call
+    #[allow(clippy::too_many_arguments)]
+    fn bind_params<'p>(
+        &self,
+        def: &'p FunctionDef,
+        position: usize,
+        scope: Rc<Env<'p>>,
+        args: &'p [Expr],
+        env: &Rc<Env<'p>>,
+        input: &Item,
+        out: Sink,
+    ) -> Flow {
+        let param = match def.params.get(position) {
+            Some(param) => param,
+            None => return self.eval(&def.body, &scope, input, out),
+        };
+        match param.strip_prefix('$') {
+            Some(name) => self.eval(&args[position], env, input, &mut |item| {
+                let scoped = Env::push(&scope, Frame::Variable(name, item.value.clone()));
+                let scoped = Env::push(&scoped, Frame::Value(name, item.value));
+                self.bind_params(def, position + 1, scoped, args, env, input, out)
+            }),
+            None => {
+                let scoped = Env::push(&scope, Frame::Closure(param, &args[position], env.clone()));
+                self.bind_params(def, position + 1, scoped, args, env, input, out)
+            }
+        }
+    }
+}
+
+/// Extract the path from an item produced by a path expression
+pub(super) fn path_of(item: Item) -> Result<Path, Failure> {
+    match item.path {
+        Some(path) => Ok(path),
+        None => Err(Failure::message(format!(
+            "Invalid path expression with result {}",
+            item.value.describe()
+        ))),
+    }
+}
+
+/// Convert a literal into a value
+fn literal_value(literal: &Literal) -> Value {
+    match literal {
+        Literal::Integer(i) => Value::Integer(*i),
+        Literal::Float(f) => Value::Float(*f),
+        Literal::String(s) => Value::string(s.as_str()),
+        Literal::Boolean(b) => Value::Boolean(*b),
+        Literal::Null => Value::Null,
+    }
+}
+
+/// The process environment, as an object
+pub(super) fn environment() -> Value {
+    Value::object(
+        std::env::vars()
+            .map(|(k, v)| (k, Value::string(v)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transform::tests::{failure, outputs};
+
+    #[test]
+    fn fails_on_runaway_recursion() {
+        assert_eq!(failure("def f: f; f"), "recursion limit exceeded");
+        assert_eq!(failure("def f: 1 + f; f"), "recursion limit exceeded");
+        assert_eq!(
+            failure("[recurse(if . < 1000000 then . + 1 else empty end)] | length"),
+            "recursion limit exceeded"
+        );
+    }
+
+    #[test]
+    fn recursion_limit_can_be_caught() {
+        assert_eq!(
+            outputs("def f: 1 + f; try f catch ."),
+            [r#""recursion limit exceeded""#]
+        );
+    }
+
+    #[test]
+    fn allows_reasonably_deep_recursion() {
+        assert_eq!(
+            outputs("def count: if . < 500 then . + 1 | count else . end; 0 | count"),
+            ["500"]
+        );
+        assert_eq!(outputs("[limit(500; repeat(1))] | length"), ["500"]);
+        assert_eq!(
+            outputs("reduce range(10000) as $x (null; [$x, .]) | [..] | length"),
+            ["20001"]
+        );
+        assert_eq!(
+            outputs("reduce range(100000) as $i (0; . + $i)"),
+            ["4999950000"]
+        );
+    }
+
+    #[test]
+    fn paths_follow_the_input() {
+        assert_eq!(
+            outputs(r#"{a: [{b: 1}]} | [paths(type == "number")]"#),
+            [r#"[["a",0,"b"]]"#]
+        );
+        assert_eq!(
+            outputs(r#"{a: [{b: 1}]} | path(.a[0].b), getpath(["a", 0, "b"])"#),
+            [r#"["a",0,"b"]"#, "1"]
+        );
+        assert_eq!(
+            outputs(r#"null | setpath(["a", 1]; 2)"#),
+            [r#"{"a":[null,2]}"#]
+        );
+    }
+}
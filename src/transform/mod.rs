@@ -0,0 +1,379 @@
+//! A jq-style transformation language, evaluated directly against [JsonValue] DOM structures.
+//!
+//! Programs are made up of filters, each of which takes a single input value and produces zero or more outputs.
+//! Filters are combined with pipes (`|`) and commas (`,`), and the usual jq constructs are supported: paths
+//! (`.foo`, `.[0]`, `.[]`, `..`), object and array construction, string interpolation, arithmetic and comparisons,
+//! `if`, `try`, `reduce`, `foreach`, variable bindings, assignment operators and user-defined functions, along
+//! with a reasonably complete set of built-in functions such as `map`, `select`, `to_entries`, `group_by` and
+//! `sort_by`.
+use crate::errors::{ChiselError, ChiselResult};
+use chisel_json::JsonValue;
+use std::sync::Arc;
+use std::thread;
+
+mod builtins;
+mod evaluator;
+mod parser;
+mod paths;
+mod value;
+
+/// Stack size for the threads that programs are parsed and evaluated on. Both are recursive, and the stack needs to
+/// be large enough for the parser's nesting limit and the evaluator's depth limit to be reached (even in unoptimised
+/// builds) before it overflows
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// A parsed transformation program
+#[derive(Debug)]
+pub struct Program {
+    /// The root expression
+    expr: Expr,
+}
+
+impl Program {
+    /// Parse the source for a program
+    pub fn parse(source: &str) -> ChiselResult<Program> {
+        let expr = with_stack(|| parser::parse_program(source))?;
+        Ok(Program { expr })
+    }
+
+    /// Run the program against an input value, collecting all of the outputs
+    pub fn run(&self, input: JsonValue<'static>) -> ChiselResult<Vec<JsonValue<'static>>> {
+        with_stack(|| evaluator::Evaluator::new()?.run(&self.expr, input))
+    }
+}
+
+/// Do some work on a thread of its own, with a stack of [STACK_SIZE]
+fn with_stack<T: Send>(work: impl FnOnce() -> ChiselResult<T> + Send) -> ChiselResult<T> {
+    thread::scope(|scope| {
+        let handle = thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, work)
+            .or(Err(ChiselError::TransformFailed(String::from(
+                "unable to start evaluation",
+            ))))?;
+        handle.join().unwrap_or_else(|_| {
+            Err(ChiselError::TransformFailed(String::from(
+                "evaluation panicked",
+            )))
+        })
+    })
+}
+
+/// Literal values appearing within a program
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+    Null,
+}
+
+/// The parts making up a (possibly interpolated) string
+#[derive(Debug)]
+pub enum StringPart {
+    /// A literal run of characters
+    Literal(String),
+    /// An interpolated expression, i.e. `\(expr)`
+    Interpolation(Expr),
+}
+
+/// Binary operators
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Assignment operators
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AssignOp {
+    /// `=`
+    Set,
+    /// `|=`
+    Update,
+    /// Arithmetic update, e.g. `+=`
+    Arithmetic(BinaryOp),
+    /// `//=`
+    Alternative,
+}
+
+/// The key part of an object construction entry
+#[derive(Debug)]
+pub enum ObjectKey {
+    /// A fixed key, e.g. `{a: 1}` or `{"a": 1}`
+    Literal(String),
+    /// A (possibly interpolated) string key
+    String(Vec<StringPart>, Option<String>),
+    /// A variable, e.g. `{$a}`
+    Variable(String),
+    /// A computed key, e.g. `{(.a): 1}`
+    Computed(Expr),
+}
+
+/// A user-defined function
+#[derive(Debug)]
+pub struct FunctionDef {
+    /// The function name
+    pub name: String,
+    /// Parameter names. Value parameters retain their leading `$`
+    pub params: Vec<String>,
+    /// The function body
+    pub body: Expr,
+}
+
+/// Expressions
+#[derive(Debug)]
+pub enum Expr {
+    /// `.`
+    Identity,
+    /// `..`
+    RecurseAll,
+    /// A literal value
+    Literal(Literal),
+    /// A string, with optional interpolations and format
+    String(Vec<StringPart>, Option<String>),
+    /// A format used as a filter, e.g. `@base64`
+    Format(String),
+    /// `target[key]`, with the key evaluated against the original input
+    Index(Box<Expr>, Box<Expr>),
+    /// `target[from:to]`
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    /// `target[]`
+    Iterate(Box<Expr>),
+    /// `[expr]`
+    Array(Option<Box<Expr>>),
+    /// `{key: value, ...}`, with a missing value implying a lookup of the key in the input
+    Object(Vec<(ObjectKey, Option<Expr>)>),
+    /// `-expr`
+    Negate(Box<Expr>),
+    /// `lhs | rhs`
+    Pipe(Box<Expr>, Box<Expr>),
+    /// `lhs, rhs`
+    Comma(Box<Expr>, Box<Expr>),
+    /// Arithmetic and comparison operators
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    /// `lhs and rhs`
+    And(Box<Expr>, Box<Expr>),
+    /// `lhs or rhs`
+    Or(Box<Expr>, Box<Expr>),
+    /// `lhs // rhs`
+    Alternative(Box<Expr>, Box<Expr>),
+    /// Assignment, e.g. `.a |= . + 1`
+    Assign(AssignOp, Box<Expr>, Box<Expr>),
+    /// `if c then a elif c2 then b else d end`
+    If(Vec<(Expr, Expr)>, Option<Box<Expr>>),
+    /// `try body catch handler`, and also the `?` postfix operator
+    Try(Box<Expr>, Option<Box<Expr>>),
+    /// `reduce source as $var (init; update)`
+    Reduce(Box<Expr>, String, Box<Expr>, Box<Expr>),
+    /// `foreach source as $var (init; update; extract)`
+    Foreach(Box<Expr>, String, Box<Expr>, Box<Expr>, Option<Box<Expr>>),
+    /// `source as $var | body`
+    Bind(Box<Expr>, String, Box<Expr>),
+    /// `$var`
+    Variable(String),
+    /// A call to a built-in or user-defined function
+    Call(String, Vec<Expr>),
+    /// A function definition, scoped over the following expression
+    Define(Arc<FunctionDef>, Box<Expr>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::value::Value;
+    use super::*;
+    use crate::dom::parse_literal;
+
+    /// Run a program against some JSON input, returning its outputs as compact JSON
+    pub(super) fn run(source: &str, input: &str) -> ChiselResult<Vec<String>> {
+        let input = parse_literal(input)?;
+        Ok(Program::parse(source)?
+            .run(input)?
+            .into_iter()
+            .map(|value| Value::from_json(value).to_json_text())
+            .collect())
+    }
+
+    /// Run a program against null, expecting it to succeed
+    pub(super) fn outputs(source: &str) -> Vec<String> {
+        run(source, "null").unwrap_or_else(|err| panic!("{}: {}", source, err))
+    }
+
+    /// Run a program against null, expecting it to fail, and returning the reason why
+    pub(super) fn failure(source: &str) -> String {
+        match run(source, "null") {
+            Err(ChiselError::TransformFailed(reason)) => reason,
+            result => panic!("{}: unexpected {:?}", source, result),
+        }
+    }
+
+    #[test]
+    fn paths_and_iteration() {
+        let input = r#"{"a": {"b": [1, 2, 3]}, "c d": null}"#;
+        assert_eq!(
+            run(".", input).unwrap(),
+            [r#"{"a":{"b":[1,2,3]},"c d":null}"#]
+        );
+        assert_eq!(run(".a.b", input).unwrap(), ["[1,2,3]"]);
+        assert_eq!(run(".a.b[]", input).unwrap(), ["1", "2", "3"]);
+        assert_eq!(run(".a.b[-1]", input).unwrap(), ["3"]);
+        assert_eq!(run(".a.b[1:]", input).unwrap(), ["[2,3]"]);
+        assert_eq!(run(r#".["c d"]"#, input).unwrap(), ["null"]);
+        assert_eq!(run(".missing.deeper", input).unwrap(), ["null"]);
+        assert_eq!(run("[..|numbers]", input).unwrap(), ["[1,2,3]"]);
+        assert_eq!(run("[paths]", input).unwrap().len(), 1);
+        assert!(run(".a.b.c", input).is_err());
+        assert_eq!(run(".a.b.c?", input).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn construction_and_interpolation() {
+        assert_eq!(
+            outputs("[1, 2] | {a: .[0], (\"b\"): .[1]}"),
+            [r#"{"a":1,"b":2}"#]
+        );
+        assert_eq!(outputs("{a: (1, 2)}"), [r#"{"a":1}"#, r#"{"a":2}"#]);
+        assert_eq!(outputs("[range(3)]"), ["[0,1,2]"]);
+        assert_eq!(outputs(r#"1 as $x | "x is \($x + 1)""#), [r#""x is 2""#]);
+        assert_eq!(outputs(r#""<&>" | @html"#), [r#""&lt;&amp;&gt;""#]);
+        assert_eq!(outputs(r#"[1, "a"] | @csv"#), [r#""1,\"a\"""#]);
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(outputs("1 + 2 * 3 - 4 / 2"), ["5"]);
+        assert_eq!(outputs("7 % 3, -7 % 3"), ["1", "-1"]);
+        assert_eq!(
+            outputs(r#""a" + "b", [1] + [2], {a: 1} + {b: 2}, null + 1"#),
+            [r#""ab""#, "[1,2]", r#"{"a":1,"b":2}"#, "1"]
+        );
+        assert_eq!(outputs("[1, 2, 3, 1] - [1]"), ["[2,3]"]);
+        assert_eq!(
+            outputs(r#"{a: {b: 1}} * {a: {c: 2}}"#),
+            [r#"{"a":{"b":1,"c":2}}"#]
+        );
+        assert_eq!(outputs(r#""a,b" / ",""#), [r#"["a","b"]"#]);
+        assert_eq!(
+            outputs("[1 < 2, \"a\" < 1, null < false, [] < {}]"),
+            ["[true,false,true,true]"]
+        );
+        assert_eq!(outputs("(false, null, 0) // 3"), ["0"]);
+        assert_eq!(outputs("(false, null) // 3"), ["3"]);
+        assert_eq!(
+            outputs("[true, false] | .[0] and .[1], .[0] or .[1]"),
+            ["false", "true"]
+        );
+        assert!(run("{} - 1", "null").is_err());
+        assert!(run("1 / 0", "null").is_err());
+    }
+
+    #[test]
+    fn control_flow() {
+        assert_eq!(
+            outputs("range(4) | if . == 0 then \"zero\" elif . == 1 then \"one\" else . end"),
+            [r#""zero""#, r#""one""#, "2", "3"]
+        );
+        assert_eq!(outputs("if false then 1 end"), ["null"]);
+        assert_eq!(outputs("reduce range(5) as $i (0; . + $i)"), ["10"]);
+        assert_eq!(
+            outputs("[foreach range(4) as $i (0; . + $i)]"),
+            ["[0,1,3,6]"]
+        );
+        assert_eq!(
+            outputs("[foreach range(4) as $i (0; . + $i; [$i, .])]"),
+            ["[[0,0],[1,1],[2,3],[3,6]]"]
+        );
+        assert_eq!(outputs("[.[]?]"), ["[]"]);
+        assert_eq!(outputs(r#"try error("x") catch ."#), [r#""x""#]);
+        assert_eq!(outputs(r#"[try (1, error("x"), 3)]"#), ["[1]"]);
+        assert_eq!(failure(r#"error("custom")"#), "custom");
+        assert_eq!(failure(r#"error({a: 1})"#), r#"{"a":1} (not a string)"#);
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(outputs("def inc(f): f + 1; inc(1, 2)"), ["2", "3"]);
+        assert_eq!(outputs("def add($a; $b): $a + $b; add(1; 2)"), ["3"]);
+        assert_eq!(
+            outputs("def fac: if . <= 1 then 1 else . * (. - 1 | fac) end; 10 | fac"),
+            ["3628800"]
+        );
+        assert_eq!(outputs("def f: def g: 3; g * 2; f"), ["6"]);
+        assert!(run("$undefined", "null").is_err());
+        assert!(run("undefined_function", "null").is_err());
+    }
+
+    #[test]
+    fn assignment() {
+        let input = r#"{"a": [1, 2], "b": {"c": 3}}"#;
+        assert_eq!(
+            run(".a[] |= . * 10", input).unwrap(),
+            [r#"{"a":[10,20],"b":{"c":3}}"#]
+        );
+        assert_eq!(run(".b.c += 1 | .b.c", input).unwrap(), ["4"]);
+        assert_eq!(run(".b.d //= 5 | .b", input).unwrap(), [r#"{"c":3,"d":5}"#]);
+        assert_eq!(run(".x = .b.c | .x", input).unwrap(), ["3"]);
+        assert_eq!(run("del(.a[0], .b) ", input).unwrap(), [r#"{"a":[2]}"#]);
+        assert_eq!(
+            run("to_entries | map(.key)", input).unwrap(),
+            [r#"["a","b"]"#]
+        );
+        assert_eq!(run(".a |= empty", input).unwrap(), [r#"{"b":{"c":3}}"#]);
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |depth: usize| format!("{}1{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Program::parse(&nested(200)).is_ok());
+        for source in [
+            nested(1000),
+            format!("{}1", "-".repeat(1000)),
+            format!("{}1", "try ".repeat(1000)),
+            format!("{}1{}", "\"\\(".repeat(1000), ")\"".repeat(1000)),
+        ] {
+            match Program::parse(&source) {
+                Err(ChiselError::InvalidTransform(reason)) => {
+                    assert!(reason.starts_with("expressions are nested too deeply"))
+                }
+                result => panic!("unexpected {:?}", result.map(|_| ())),
+            }
+        }
+
+        // long pipelines and lots of definitions don't nest
+        assert!(Program::parse(&format!("{}.", ". | ".repeat(10000))).is_ok());
+        assert!(Program::parse(&format!("{}.", "def f: .; ".repeat(10000))).is_ok());
+    }
+
+    #[test]
+    fn syntax_errors() {
+        for source in [
+            "1 +",
+            "[1, 2",
+            "{a}b",
+            "if 1 then 2",
+            ".a..b",
+            "def f: 1",
+            "reduce . as $x (0)",
+            "$",
+        ] {
+            assert!(
+                matches!(
+                    Program::parse(source),
+                    Err(ChiselError::InvalidTransform(_))
+                ),
+                "{}",
+                source
+            );
+        }
+    }
+}
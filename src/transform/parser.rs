@@ -0,0 +1,829 @@
+//! A recursive descent parser for transformation programs
+use super::{AssignOp, BinaryOp, Expr, FunctionDef, Literal, ObjectKey, StringPart};
+use crate::errors::{ChiselError, ChiselResult};
+use std::sync::Arc;
+
+/// Words that can't be used as function names
+const KEYWORDS: &[&str] = &[
+    "def", "if", "then", "elif", "else", "end", "as", "reduce", "foreach", "try", "catch", "and",
+    "or", "label", "import", "include", "__loc__",
+];
+
+/// The deepest that expressions may nest, e.g. within brackets or beneath unary minus, which keeps the (recursive)
+/// parser well within the stack of the main thread, even in unoptimised builds
+const MAX_NESTING_DEPTH: usize = 256;
+
+/// Parse the complete source for a program
+pub(super) fn parse_program(source: &str) -> ChiselResult<Expr> {
+    let mut parser = ProgramParser {
+        chars: source.chars().collect(),
+        position: 0,
+        depth: 0,
+    };
+    parser.skip_blanks();
+    if parser.at_end() {
+        return Ok(Expr::Identity);
+    }
+    let expr = parser.parse_pipe(false)?;
+    parser.skip_blanks();
+    if !parser.at_end() {
+        return parser.error("unexpected input");
+    }
+    Ok(expr)
+}
+
+/// Internal parser state
+struct ProgramParser {
+    /// The program source
+    chars: Vec<char>,
+    /// The current position within the source
+    position: usize,
+    /// The current nesting depth
+    depth: usize,
+}
+
+/// A stage within a pipeline, preceding the final expression
+enum Stage {
+    /// A function definition, scoped over the rest of the pipeline
+    Define(FunctionDef),
+    /// An expression, piped into the rest of the pipeline
+    Pipe(Expr),
+}
+
+impl ProgramParser {
+    /// Generate an error, annotated with the current position
+    fn error<T>(&self, reason: &str) -> ChiselResult<T> {
+        Err(ChiselError::InvalidTransform(format!(
+            "{} (at offset {})",
+            reason, self.position
+        )))
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.chars.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    /// Skip whitespace and comments
+    fn skip_blanks(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => self.position += 1,
+                Some('#') => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.position += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Check for (and consume) a symbol, skipping any leading blanks
+    fn symbol(&mut self, symbol: &str) -> bool {
+        self.skip_blanks();
+        let matched = symbol
+            .chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_at(i) == Some(c));
+        if matched {
+            self.position += symbol.chars().count();
+        }
+        matched
+    }
+
+    /// Check for (and consume) an operator, which mustn't form the prefix of a longer operator
+    fn operator(&mut self, op: &str) -> bool {
+        let checkpoint = self.position;
+        if self.symbol(op) {
+            let next = self.peek();
+            let longer = match op {
+                "|" => matches!(next, Some('=')),
+                "/" => matches!(next, Some('/' | '=')),
+                "//" | "+" | "-" | "*" | "%" => matches!(next, Some('=')),
+                "=" => matches!(next, Some('=')),
+                "<" | ">" => matches!(next, Some('=')),
+                _ => false,
+            };
+            if !longer {
+                return true;
+            }
+        }
+        self.position = checkpoint;
+        false
+    }
+
+    fn expect(&mut self, symbol: &str) -> ChiselResult<()> {
+        if self.symbol(symbol) {
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", symbol))
+        }
+    }
+
+    /// Peek at the next identifier without consuming it
+    fn peek_ident(&mut self) -> Option<String> {
+        self.skip_blanks();
+        let mut ident = String::new();
+        let mut offset = 0;
+        while let Some(c) = self.peek_at(offset) {
+            let valid = if offset == 0 {
+                c.is_ascii_alphabetic() || c == '_'
+            } else {
+                c.is_ascii_alphanumeric() || c == '_'
+            };
+            if !valid {
+                break;
+            }
+            ident.push(c);
+            offset += 1;
+        }
+        if ident.is_empty() {
+            None
+        } else {
+            Some(ident)
+        }
+    }
+
+    /// Check for (and consume) a keyword
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_ident().as_deref() == Some(keyword) {
+            self.position += keyword.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> ChiselResult<()> {
+        if self.keyword(keyword) {
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", keyword))
+        }
+    }
+
+    /// Parse an identifier, which may be a keyword
+    fn parse_ident(&mut self) -> ChiselResult<String> {
+        match self.peek_ident() {
+            Some(ident) => {
+                self.position += ident.len();
+                Ok(ident)
+            }
+            None => self.error("expected an identifier"),
+        }
+    }
+
+    /// Parse a variable name following a `$`
+    fn parse_variable(&mut self) -> ChiselResult<String> {
+        self.expect("$")?;
+        if !matches!(self.peek(), Some(c) if c.is_ascii_alphabetic() || c == '_') {
+            return self.error("expected a variable name");
+        }
+        self.parse_ident()
+    }
+
+    /// Parse something which may nest further expressions within it, failing once nesting exceeds
+    /// [MAX_NESTING_DEPTH]
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> ChiselResult<T>) -> ChiselResult<T> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return self.error("expressions are nested too deeply");
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// pipe := 'def' definition pipe | comma ('|' pipe)?
+    ///
+    /// Pipes associate to the right, but a run of stages (or definitions) is parsed iteratively, so that long
+    /// pipelines and the many definitions of the prelude don't recurse
+    fn parse_pipe(&mut self, no_comma: bool) -> ChiselResult<Expr> {
+        let mut stages = vec![];
+        let last = loop {
+            if self.peek_ident().as_deref() == Some("def") {
+                stages.push(Stage::Define(self.parse_definition()?));
+                continue;
+            }
+            let lhs = if no_comma {
+                self.parse_alternative()?
+            } else {
+                self.parse_comma()?
+            };
+            if !self.operator("|") {
+                break lhs;
+            }
+            stages.push(Stage::Pipe(lhs));
+        };
+        Ok(stages
+            .into_iter()
+            .rev()
+            .fold(last, |rest, stage| match stage {
+                Stage::Define(def) => Expr::Define(Arc::new(def), Box::new(rest)),
+                Stage::Pipe(lhs) => Expr::Pipe(Box::new(lhs), Box::new(rest)),
+            }))
+    }
+
+    /// definition := 'def' name params? ':' pipe ';'
+    fn parse_definition(&mut self) -> ChiselResult<FunctionDef> {
+        self.expect_keyword("def")?;
+        let name = self.parse_ident()?;
+        let mut params = vec![];
+        if self.symbol("(") {
+            loop {
+                self.skip_blanks();
+                if self.peek() == Some('$') {
+                    params.push(format!("${}", self.parse_variable()?));
+                } else {
+                    params.push(self.parse_ident()?);
+                }
+                if !self.symbol(";") {
+                    break;
+                }
+            }
+            self.expect(")")?;
+        }
+        self.expect(":")?;
+        let body = self.parse_pipe(false)?;
+        self.expect(";")?;
+        Ok(FunctionDef { name, params, body })
+    }
+
+    /// comma := alternative (',' alternative)*
+    fn parse_comma(&mut self) -> ChiselResult<Expr> {
+        let mut lhs = self.parse_alternative()?;
+        while self.operator(",") {
+            let rhs = self.parse_alternative()?;
+            lhs = Expr::Comma(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// alternative := assignment ('//' alternative)?
+    fn parse_alternative(&mut self) -> ChiselResult<Expr> {
+        let lhs = self.parse_assignment()?;
+        if self.operator("//") {
+            let rhs = self.parse_alternative()?;
+            return Ok(Expr::Alternative(Box::new(lhs), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    /// assignment := or (assign-op alternative)?
+    fn parse_assignment(&mut self) -> ChiselResult<Expr> {
+        let lhs = self.parse_or()?;
+        let op = if self.symbol("|=") {
+            AssignOp::Update
+        } else if self.symbol("//=") {
+            AssignOp::Alternative
+        } else if self.symbol("+=") {
+            AssignOp::Arithmetic(BinaryOp::Add)
+        } else if self.symbol("-=") {
+            AssignOp::Arithmetic(BinaryOp::Subtract)
+        } else if self.symbol("*=") {
+            AssignOp::Arithmetic(BinaryOp::Multiply)
+        } else if self.symbol("/=") {
+            AssignOp::Arithmetic(BinaryOp::Divide)
+        } else if self.symbol("%=") {
+            AssignOp::Arithmetic(BinaryOp::Modulo)
+        } else if self.operator("=") {
+            AssignOp::Set
+        } else {
+            return Ok(lhs);
+        };
+        let rhs = self.parse_alternative()?;
+        Ok(Expr::Assign(op, Box::new(lhs), Box::new(rhs)))
+    }
+
+    /// or := and ('or' and)*
+    fn parse_or(&mut self) -> ChiselResult<Expr> {
+        let mut lhs = self.parse_and()?;
+        while self.keyword("or") {
+            let rhs = self.parse_and()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// and := comparison ('and' comparison)*
+    fn parse_and(&mut self) -> ChiselResult<Expr> {
+        let mut lhs = self.parse_comparison()?;
+        while self.keyword("and") {
+            let rhs = self.parse_comparison()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// comparison := additive (comparison-op additive)?
+    fn parse_comparison(&mut self) -> ChiselResult<Expr> {
+        let lhs = self.parse_additive()?;
+        let op = if self.symbol("==") {
+            BinaryOp::Eq
+        } else if self.symbol("!=") {
+            BinaryOp::Ne
+        } else if self.symbol("<=") {
+            BinaryOp::Le
+        } else if self.symbol(">=") {
+            BinaryOp::Ge
+        } else if self.operator("<") {
+            BinaryOp::Lt
+        } else if self.operator(">") {
+            BinaryOp::Gt
+        } else {
+            return Ok(lhs);
+        };
+        let rhs = self.parse_additive()?;
+        Ok(Expr::Binary(op, Box::new(lhs), Box::new(rhs)))
+    }
+
+    /// additive := multiplicative (('+' | '-') multiplicative)*
+    fn parse_additive(&mut self) -> ChiselResult<Expr> {
+        let mut lhs = self.parse_multiplicative()?;
+        loop {
+            let op = if self.operator("+") {
+                BinaryOp::Add
+            } else if self.operator("-") {
+                BinaryOp::Subtract
+            } else {
+                break;
+            };
+            let rhs = self.parse_multiplicative()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// multiplicative := unary (('*' | '/' | '%') unary)*
+    fn parse_multiplicative(&mut self) -> ChiselResult<Expr> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            let op = if self.operator("*") {
+                BinaryOp::Multiply
+            } else if self.operator("/") {
+                BinaryOp::Divide
+            } else if self.operator("%") {
+                BinaryOp::Modulo
+            } else {
+                break;
+            };
+            let rhs = self.parse_unary()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// unary := '-' unary | postfix
+    fn parse_unary(&mut self) -> ChiselResult<Expr> {
+        if self.operator("-") {
+            return self.nested(|parser| Ok(Expr::Negate(Box::new(parser.parse_unary()?))));
+        }
+        self.parse_postfix(true)
+    }
+
+    /// postfix := primary suffix* ('as' $var '|' pipe)?
+    fn parse_postfix(&mut self, allow_binding: bool) -> ChiselResult<Expr> {
+        self.nested(|parser| parser.parse_postfix_inner(allow_binding))
+    }
+
+    fn parse_postfix_inner(&mut self, allow_binding: bool) -> ChiselResult<Expr> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            self.skip_blanks();
+            match (self.peek(), self.peek_at(1)) {
+                (Some('.'), Some('[')) => {
+                    self.position += 1;
+                    expr = self.parse_bracket_suffix(expr)?;
+                }
+                (Some('['), _) => expr = self.parse_bracket_suffix(expr)?,
+                (Some('.'), Some('"')) => {
+                    self.position += 1;
+                    let key = self.parse_string_expr(None)?;
+                    expr = Expr::Index(Box::new(expr), Box::new(key));
+                }
+                (Some('.'), Some(c)) if c.is_ascii_alphabetic() || c == '_' => {
+                    self.position += 1;
+                    let name = self.parse_ident()?;
+                    expr = Expr::Index(
+                        Box::new(expr),
+                        Box::new(Expr::Literal(Literal::String(name))),
+                    );
+                }
+                (Some('?'), _) if self.peek_at(1) != Some('/') => {
+                    self.position += 1;
+                    expr = Expr::Try(Box::new(expr), None);
+                }
+                _ => break,
+            }
+        }
+
+        if allow_binding && self.keyword("as") {
+            let name = self.parse_variable()?;
+            self.expect("|")?;
+            let body = self.parse_pipe(false)?;
+            return Ok(Expr::Bind(Box::new(expr), name, Box::new(body)));
+        }
+        Ok(expr)
+    }
+
+    /// Parse a bracketed suffix, i.e. `[]`, `[expr]` or `[from:to]`
+    fn parse_bracket_suffix(&mut self, target: Expr) -> ChiselResult<Expr> {
+        self.expect("[")?;
+        if self.symbol("]") {
+            return Ok(Expr::Iterate(Box::new(target)));
+        }
+        if self.symbol(":") {
+            let to = self.parse_pipe(false)?;
+            self.expect("]")?;
+            return Ok(Expr::Slice(Box::new(target), None, Some(Box::new(to))));
+        }
+        let index = self.parse_pipe(false)?;
+        if self.symbol(":") {
+            let to = if self.symbol("]") {
+                return Ok(Expr::Slice(Box::new(target), Some(Box::new(index)), None));
+            } else {
+                self.parse_pipe(false)?
+            };
+            self.expect("]")?;
+            return Ok(Expr::Slice(
+                Box::new(target),
+                Some(Box::new(index)),
+                Some(Box::new(to)),
+            ));
+        }
+        self.expect("]")?;
+        Ok(Expr::Index(Box::new(target), Box::new(index)))
+    }
+
+    /// Parse a primary term
+    fn parse_primary(&mut self) -> ChiselResult<Expr> {
+        self.skip_blanks();
+        match self.peek() {
+            Some('.') => {
+                self.position += 1;
+                match self.peek() {
+                    Some('.') => {
+                        self.position += 1;
+                        Ok(Expr::RecurseAll)
+                    }
+                    Some('"') => {
+                        let key = self.parse_string_expr(None)?;
+                        Ok(Expr::Index(Box::new(Expr::Identity), Box::new(key)))
+                    }
+                    Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                        let name = self.parse_ident()?;
+                        Ok(Expr::Index(
+                            Box::new(Expr::Identity),
+                            Box::new(Expr::Literal(Literal::String(name))),
+                        ))
+                    }
+                    _ => Ok(Expr::Identity),
+                }
+            }
+            Some(c) if c.is_ascii_digit() => self.parse_number(),
+            Some('"') => self.parse_string_expr(None),
+            Some('@') => {
+                self.position += 1;
+                let format = self.parse_ident()?;
+                self.skip_blanks();
+                if self.peek() == Some('"') {
+                    self.parse_string_expr(Some(format))
+                } else {
+                    Ok(Expr::Format(format))
+                }
+            }
+            Some('(') => {
+                self.position += 1;
+                let expr = self.parse_pipe(false)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some('[') => {
+                self.position += 1;
+                if self.symbol("]") {
+                    return Ok(Expr::Array(None));
+                }
+                let expr = self.parse_pipe(false)?;
+                self.expect("]")?;
+                Ok(Expr::Array(Some(Box::new(expr))))
+            }
+            Some('{') => self.parse_object(),
+            Some('$') => {
+                let name = self.parse_variable()?;
+                if name == "__loc__" {
+                    return self.error("$__loc__ isn't supported");
+                }
+                Ok(Expr::Variable(name))
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => self.parse_word(),
+            Some(_) => self.error("unexpected character"),
+            None => self.error("unexpected end of input"),
+        }
+    }
+
+    /// Parse a term starting with an identifier, which may be a keyword-introduced construct or a function call
+    fn parse_word(&mut self) -> ChiselResult<Expr> {
+        let word = self.peek_ident().unwrap_or_default();
+        match word.as_str() {
+            "if" => self.parse_if(),
+            "try" => {
+                self.position += 3;
+                let body = self.parse_postfix(false)?;
+                let handler = if self.keyword("catch") {
+                    Some(Box::new(self.parse_postfix(false)?))
+                } else {
+                    None
+                };
+                Ok(Expr::Try(Box::new(body), handler))
+            }
+            "reduce" => {
+                self.position += 6;
+                let source = self.parse_postfix(false)?;
+                self.expect_keyword("as")?;
+                let name = self.parse_variable()?;
+                self.expect("(")?;
+                let init = self.parse_pipe(false)?;
+                self.expect(";")?;
+                let update = self.parse_pipe(false)?;
+                self.expect(")")?;
+                Ok(Expr::Reduce(
+                    Box::new(source),
+                    name,
+                    Box::new(init),
+                    Box::new(update),
+                ))
+            }
+            "foreach" => {
+                self.position += 7;
+                let source = self.parse_postfix(false)?;
+                self.expect_keyword("as")?;
+                let name = self.parse_variable()?;
+                self.expect("(")?;
+                let init = self.parse_pipe(false)?;
+                self.expect(";")?;
+                let update = self.parse_pipe(false)?;
+                let extract = if self.symbol(";") {
+                    Some(Box::new(self.parse_pipe(false)?))
+                } else {
+                    None
+                };
+                self.expect(")")?;
+                Ok(Expr::Foreach(
+                    Box::new(source),
+                    name,
+                    Box::new(init),
+                    Box::new(update),
+                    extract,
+                ))
+            }
+            "def" => {
+                let def = self.parse_definition()?;
+                let rest = self.parse_pipe(false)?;
+                Ok(Expr::Define(Arc::new(def), Box::new(rest)))
+            }
+            "true" | "false" | "null" => {
+                self.position += word.len();
+                Ok(Expr::Literal(match word.as_str() {
+                    "true" => Literal::Boolean(true),
+                    "false" => Literal::Boolean(false),
+                    _ => Literal::Null,
+                }))
+            }
+            w if KEYWORDS.contains(&w) => self.error(&format!("unexpected keyword '{}'", w)),
+            _ => {
+                self.position += word.len();
+                let mut args = vec![];
+                if self.peek() == Some('(') {
+                    self.position += 1;
+                    loop {
+                        args.push(self.parse_pipe(false)?);
+                        if !self.symbol(";") {
+                            break;
+                        }
+                    }
+                    self.expect(")")?;
+                }
+                Ok(Expr::Call(word, args))
+            }
+        }
+    }
+
+    /// if := 'if' pipe 'then' pipe ('elif' pipe 'then' pipe)* ('else' pipe)? 'end'
+    fn parse_if(&mut self) -> ChiselResult<Expr> {
+        self.expect_keyword("if")?;
+        let mut branches = vec![];
+        let condition = self.parse_pipe(false)?;
+        self.expect_keyword("then")?;
+        branches.push((condition, self.parse_pipe(false)?));
+        let mut otherwise = None;
+        loop {
+            if self.keyword("elif") {
+                let condition = self.parse_pipe(false)?;
+                self.expect_keyword("then")?;
+                branches.push((condition, self.parse_pipe(false)?));
+            } else if self.keyword("else") {
+                otherwise = Some(Box::new(self.parse_pipe(false)?));
+                self.expect_keyword("end")?;
+                break;
+            } else {
+                self.expect_keyword("end")?;
+                break;
+            }
+        }
+        Ok(Expr::If(branches, otherwise))
+    }
+
+    /// Parse an object construction
+    fn parse_object(&mut self) -> ChiselResult<Expr> {
+        self.expect("{")?;
+        let mut entries = vec![];
+        if self.symbol("}") {
+            return Ok(Expr::Object(entries));
+        }
+        loop {
+            self.skip_blanks();
+            let key = match self.peek() {
+                Some('$') => ObjectKey::Variable(self.parse_variable()?),
+                Some('"') => match self.parse_string_expr(None)? {
+                    Expr::String(parts, format) => ObjectKey::String(parts, format),
+                    Expr::Literal(Literal::String(key)) => ObjectKey::Literal(key),
+                    _ => return self.error("expected a string key"),
+                },
+                Some('@') => {
+                    self.position += 1;
+                    let format = self.parse_ident()?;
+                    match self.parse_string_expr(Some(format))? {
+                        Expr::String(parts, format) => ObjectKey::String(parts, format),
+                        _ => return self.error("expected a string key"),
+                    }
+                }
+                Some('(') => {
+                    self.position += 1;
+                    let expr = self.parse_pipe(false)?;
+                    self.expect(")")?;
+                    ObjectKey::Computed(expr)
+                }
+                Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                    ObjectKey::Literal(self.parse_ident()?)
+                }
+                _ => return self.error("expected an object key"),
+            };
+            let value = if self.symbol(":") {
+                Some(self.parse_object_value()?)
+            } else {
+                if let ObjectKey::Computed(_) = key {
+                    return self.error("computed keys require a value");
+                }
+                None
+            };
+            entries.push((key, value));
+            if self.symbol("}") {
+                break;
+            }
+            self.expect(",")?;
+        }
+        Ok(Expr::Object(entries))
+    }
+
+    /// Object values can't contain unparenthesised commas, since these separate the entries
+    fn parse_object_value(&mut self) -> ChiselResult<Expr> {
+        self.parse_pipe(true)
+    }
+
+    /// Parse a number literal
+    fn parse_number(&mut self) -> ChiselResult<Expr> {
+        let start = self.position;
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        let mut integral = true;
+        if self.peek() == Some('.') && matches!(self.peek_at(1), Some(c) if c.is_ascii_digit()) {
+            integral = false;
+            self.position += 1;
+            while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+                self.position += 1;
+            }
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            integral = false;
+            self.position += 1;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.position += 1;
+            }
+            while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+                self.position += 1;
+            }
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        if integral {
+            if let Ok(value) = text.parse::<i64>() {
+                return Ok(Expr::Literal(Literal::Integer(value)));
+            }
+        }
+        match text.parse::<f64>() {
+            Ok(value) => Ok(Expr::Literal(Literal::Float(value))),
+            Err(_) => self.error("invalid number"),
+        }
+    }
+
+    /// Parse a string, which may contain interpolations.  Strings without any interpolation (or format) are
+    /// simplified down to a literal
+    fn parse_string_expr(&mut self, format: Option<String>) -> ChiselResult<Expr> {
+        self.skip_blanks();
+        if self.peek() != Some('"') {
+            return self.error("expected a string");
+        }
+        self.position += 1;
+        let mut parts = vec![];
+        let mut current = String::new();
+        loop {
+            match self.peek() {
+                None => return self.error("unterminated string"),
+                Some('"') => {
+                    self.position += 1;
+                    break;
+                }
+                Some('\\') => {
+                    self.position += 1;
+                    let escaped = self.peek();
+                    self.position += 1;
+                    match escaped {
+                        Some('n') => current.push('\n'),
+                        Some('t') => current.push('\t'),
+                        Some('r') => current.push('\r'),
+                        Some('b') => current.push('\u{08}'),
+                        Some('f') => current.push('\u{0c}'),
+                        Some('"') => current.push('"'),
+                        Some('\\') => current.push('\\'),
+                        Some('/') => current.push('/'),
+                        Some('u') => current.push(self.parse_unicode_escape()?),
+                        Some('(') => {
+                            if !current.is_empty() {
+                                parts.push(StringPart::Literal(std::mem::take(&mut current)));
+                            }
+                            let expr = self.parse_pipe(false)?;
+                            self.expect(")")?;
+                            parts.push(StringPart::Interpolation(expr));
+                        }
+                        _ => return self.error("invalid escape sequence"),
+                    }
+                }
+                Some(c) => {
+                    current.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+        if !current.is_empty() {
+            parts.push(StringPart::Literal(current));
+        }
+        let interpolated = parts
+            .iter()
+            .any(|p| matches!(p, StringPart::Interpolation(_)));
+        if !interpolated && format.is_none() {
+            let literal = match parts.pop() {
+                Some(StringPart::Literal(s)) => s,
+                _ => String::new(),
+            };
+            return Ok(Expr::Literal(Literal::String(literal)));
+        }
+        Ok(Expr::String(parts, format))
+    }
+
+    /// Parse the hex digits of a `\u` escape, along with any following low surrogate
+    fn parse_unicode_escape(&mut self) -> ChiselResult<char> {
+        let high = self.parse_hex4()?;
+        if (0xd800..0xdc00).contains(&high)
+            && self.peek() == Some('\\')
+            && self.peek_at(1) == Some('u')
+        {
+            self.position += 2;
+            let low = self.parse_hex4()?;
+            let code = 0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+            return Ok(char::from_u32(code).unwrap_or('\u{fffd}'));
+        }
+        Ok(char::from_u32(high).unwrap_or('\u{fffd}'))
+    }
+
+    fn parse_hex4(&mut self) -> ChiselResult<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            match self.peek().and_then(|c| c.to_digit(16)) {
+                Some(digit) => {
+                    code = (code << 4) | digit;
+                    self.position += 1;
+                }
+                None => return self.error("invalid unicode escape"),
+            }
+        }
+        Ok(code)
+    }
+}
@@ -0,0 +1,279 @@
+//! Indexing, slicing and path-based updates of [Value]s
+use super::evaluator::{Failure, Path};
+use super::value::Value;
+use std::rc::Rc;
+
+/// Index into a value, as performed by `.[key]`. Indexing `null` always results in `null`
+pub(super) fn index(target: &Value, key: &Value) -> Result<Value, Failure> {
+    match (target, key) {
+        (Value::Null, Value::String(_) | Value::Integer(_) | Value::Float(_) | Value::Null) => {
+            Ok(Value::Null)
+        }
+        (Value::Null, Value::Object(_)) => Ok(Value::Null),
+        (Value::Object(_), Value::String(k)) => Ok(target.get(k).cloned().unwrap_or(Value::Null)),
+        (Value::Array(values), Value::Integer(_) | Value::Float(_)) => {
+            Ok(match array_index(values.len(), key) {
+                Some(i) if i < values.len() => values[i].clone(),
+                _ => Value::Null,
+            })
+        }
+        (Value::Array(values), Value::Array(sub)) => Ok(subarray_indices(values, sub)),
+        (Value::Array(_) | Value::String(_), Value::Object(_)) => {
+            let (from, to) = slice_bounds(key);
+            slice(target, &from, &to)
+        }
+        _ => Err(cannot_index(target, key)),
+    }
+}
+
+/// Slice an array or string, as performed by `.[from:to]`
+pub(super) fn slice(target: &Value, from: &Value, to: &Value) -> Result<Value, Failure> {
+    match target {
+        Value::Null => Ok(Value::Null),
+        Value::Array(values) => {
+            let (start, end) = slice_range(values.len(), from, to)?;
+            Ok(Value::array(values[start..end].to_vec()))
+        }
+        Value::String(s) => {
+            let chars: Vec<char> = s.chars().collect();
+            let (start, end) = slice_range(chars.len(), from, to)?;
+            Ok(Value::string(chars[start..end].iter().collect::<String>()))
+        }
+        _ => Err(Failure::message(format!(
+            "Cannot index {} with object",
+            target.type_name()
+        ))),
+    }
+}
+
+/// The path component used to represent a slice
+pub(super) fn slice_key(from: Value, to: Value) -> Value {
+    Value::object(vec![
+        (String::from("start"), from),
+        (String::from("end"), to),
+    ])
+}
+
+/// Look up the value at a path. Missing members (and anything beneath a `null`) result in `null`
+pub(super) fn get_path(root: &Value, path: &[Value]) -> Result<Value, Failure> {
+    let mut current = root.clone();
+    for key in path {
+        if let Value::Null = current {
+            return Ok(Value::Null);
+        }
+        current = index(&current, key)?;
+    }
+    Ok(current)
+}
+
+/// Replace the value at a path, creating any intermediate objects or arrays as required
+pub(super) fn set_path(root: Value, path: &[Value], value: Value) -> Result<Value, Failure> {
+    let (key, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return Ok(value),
+    };
+    match (root, key) {
+        (Value::Null, Value::String(k)) => Ok(Value::object(vec![(
+            k.to_string(),
+            set_path(Value::Null, rest, value)?,
+        )])),
+        (Value::Object(mut members), Value::String(k)) => {
+            let entries = Rc::make_mut(&mut members);
+            match entries.iter().position(|(existing, _)| **existing == **k) {
+                Some(position) => {
+                    let child = std::mem::replace(&mut entries[position].1, Value::Null);
+                    entries[position].1 = set_path(child, rest, value)?;
+                }
+                None => entries.push((k.to_string(), set_path(Value::Null, rest, value)?)),
+            }
+            Ok(Value::Object(members))
+        }
+        (Value::Null, Value::Integer(_) | Value::Float(_) | Value::Object(_)) => {
+            set_path(Value::array(vec![]), path, value)
+        }
+        (Value::Array(mut values), Value::Integer(_) | Value::Float(_)) => {
+            let elements = Rc::make_mut(&mut values);
+            let position = match array_index(elements.len(), key) {
+                Some(position) => position,
+                None => return Err(Failure::message("Out of bounds negative array index")),
+            };
+            if position >= elements.len() {
+                elements.resize(position + 1, Value::Null);
+            }
+            let child = std::mem::replace(&mut elements[position], Value::Null);
+            elements[position] = set_path(child, rest, value)?;
+            Ok(Value::Array(values))
+        }
+        (Value::Array(mut values), Value::Object(_)) => {
+            let (from, to) = slice_bounds(key);
+            let elements = Rc::make_mut(&mut values);
+            let (start, end) = slice_range(elements.len(), &from, &to)?;
+            let current = Value::array(elements[start..end].to_vec());
+            match set_path(current, rest, value)? {
+                Value::Array(replacement) => {
+                    elements.splice(start..end, replacement.iter().cloned());
+                    Ok(Value::Array(values))
+                }
+                _ => Err(Failure::message(
+                    "A slice of an array can only be assigned another array",
+                )),
+            }
+        }
+        (root, key) => Err(cannot_index(&root, key)),
+    }
+}
+
+/// Delete the values at a set of paths. Deeper and later paths are removed first, so that earlier deletions don't
+/// disturb the locations referenced by later ones
+pub(super) fn delete_paths(root: Value, mut paths: Vec<Path>) -> Result<Value, Failure> {
+    paths.sort_by(|a, b| b.cmp(a));
+    let mut root = root;
+    for path in paths {
+        root = delete_path(root, &path)?;
+    }
+    Ok(root)
+}
+
+/// Delete the value at a single path.  Deleting something that isn't there is a no-op
+fn delete_path(root: Value, path: &[Value]) -> Result<Value, Failure> {
+    let (key, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return Ok(Value::Null),
+    };
+    match (root, key) {
+        (Value::Null, _) => Ok(Value::Null),
+        (Value::Object(mut members), Value::String(k)) => {
+            let entries = Rc::make_mut(&mut members);
+            if let Some(position) = entries.iter().position(|(existing, _)| **existing == **k) {
+                if rest.is_empty() {
+                    entries.remove(position);
+                } else {
+                    let child = std::mem::replace(&mut entries[position].1, Value::Null);
+                    entries[position].1 = delete_path(child, rest)?;
+                }
+            }
+            Ok(Value::Object(members))
+        }
+        (Value::Array(mut values), Value::Integer(_) | Value::Float(_)) => {
+            let elements = Rc::make_mut(&mut values);
+            match array_index(elements.len(), key) {
+                Some(position) if position < elements.len() => {
+                    if rest.is_empty() {
+                        elements.remove(position);
+                    } else {
+                        let child = std::mem::replace(&mut elements[position], Value::Null);
+                        elements[position] = delete_path(child, rest)?;
+                    }
+                }
+                Some(_) => {}
+                None => return Err(Failure::message("Out of bounds negative array index")),
+            }
+            Ok(Value::Array(values))
+        }
+        (Value::Array(mut values), Value::Object(_)) => {
+            let (from, to) = slice_bounds(key);
+            let elements = Rc::make_mut(&mut values);
+            let (start, end) = slice_range(elements.len(), &from, &to)?;
+            if rest.is_empty() {
+                elements.drain(start..end);
+            } else {
+                let current = Value::array(elements[start..end].to_vec());
+                match delete_path(current, rest)? {
+                    Value::Array(replacement) => {
+                        elements.splice(start..end, replacement.iter().cloned());
+                    }
+                    _ => {
+                        return Err(Failure::message(
+                            "A slice of an array can only be assigned another array",
+                        ))
+                    }
+                }
+            }
+            Ok(Value::Array(values))
+        }
+        (root, key) => Err(Failure::message(format!(
+            "Cannot delete field at {} index of {}",
+            key.type_name(),
+            root.type_name()
+        ))),
+    }
+}
+
+/// Interpret a value as a list of path components
+pub(super) fn path_components(value: &Value) -> Result<&[Value], Failure> {
+    match value {
+        Value::Array(components) => Ok(components),
+        _ => Err(Failure::message("Path must be specified as an array")),
+    }
+}
+
+/// Convert a numeric key into an array position, with negative indices counting back from the end of the array.
+/// Returns [None] for a negative index that lies before the start of the array
+fn array_index(len: usize, key: &Value) -> Option<usize> {
+    let index = key.as_f64().unwrap_or(0.0).floor();
+    if index < 0.0 {
+        let adjusted = len as f64 + index;
+        if adjusted < 0.0 {
+            None
+        } else {
+            Some(adjusted as usize)
+        }
+    } else {
+        Some(index as usize)
+    }
+}
+
+/// Extract the bounds from a slice key, i.e. `{"start": from, "end": to}`
+fn slice_bounds(key: &Value) -> (Value, Value) {
+    let from = key.get("start").cloned().unwrap_or(Value::Null);
+    let to = key.get("end").cloned().unwrap_or(Value::Null);
+    (from, to)
+}
+
+/// Resolve slice bounds against a length, clamping to the valid range
+fn slice_range(len: usize, from: &Value, to: &Value) -> Result<(usize, usize), Failure> {
+    let resolve = |bound: &Value, default: usize, round: fn(f64) -> f64| match bound {
+        Value::Null => Ok(default),
+        Value::Integer(_) | Value::Float(_) => {
+            let mut position = round(bound.as_f64().unwrap_or(0.0));
+            if position < 0.0 {
+                position += len as f64;
+            }
+            Ok(position.clamp(0.0, len as f64) as usize)
+        }
+        _ => Err(Failure::message(
+            "Start and end indices of an array slice must be numbers",
+        )),
+    };
+    let start = resolve(from, 0, f64::floor)?;
+    let end = resolve(to, len, f64::ceil)?;
+    Ok((start, end.max(start)))
+}
+
+/// Find the starting positions of every occurrence of a sub-array
+fn subarray_indices(values: &[Value], sub: &[Value]) -> Value {
+    if sub.is_empty() {
+        return Value::Null;
+    }
+    let positions = (0..values.len())
+        .filter(|&i| values[i..].starts_with(sub))
+        .map(|i| Value::Integer(i as i64))
+        .collect();
+    Value::array(positions)
+}
+
+/// Generate the error for an invalid index operation
+fn cannot_index(target: &Value, key: &Value) -> Failure {
+    match key {
+        Value::String(k) => Failure::message(format!(
+            "Cannot index {} with \"{}\"",
+            target.type_name(),
+            k
+        )),
+        _ => Failure::message(format!(
+            "Cannot index {} with {}",
+            target.type_name(),
+            key.type_name()
+        )),
+    }
+}
@@ -0,0 +1,317 @@
+//! The value representation used whilst evaluating programs.  The DOM holds strings and keys in their raw (quoted
+//! and escaped) form and can't be cheaply cloned, neither of which suits an evaluator that copies values around
+//! freely, so documents are converted on the way in and back again on the way out
+use crate::escapes::{quote_json_string, unquote_json_string};
+use chisel_json::JsonValue;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// Largest magnitude for which an integral float is held as an integer
+const MAX_SAFE_INTEGER: f64 = 9007199254740992.0;
+
+/// An immutable, cheaply cloned JSON value
+#[derive(Debug, Clone)]
+pub(super) enum Value {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(Rc<str>),
+    Array(Rc<Vec<Value>>),
+    /// Object members, in insertion order. Keys are unique
+    Object(Rc<Vec<(String, Value)>>),
+}
+
+impl Value {
+    /// Convert from a DOM value, decoding strings and keys
+    pub fn from_json(value: JsonValue) -> Value {
+        match value {
+            JsonValue::Object(pairs) => {
+                let mut members: Vec<(String, Value)> = Vec::with_capacity(pairs.len());
+                for (k, v) in pairs {
+                    let key = unquote_json_string(&k);
+                    let value = Value::from_json(v);
+                    match members.iter_mut().find(|(existing, _)| *existing == key) {
+                        Some(member) => member.1 = value,
+                        None => members.push((key, value)),
+                    }
+                }
+                Value::Object(Rc::new(members))
+            }
+            JsonValue::Array(values) => {
+                Value::Array(Rc::new(values.into_iter().map(Value::from_json).collect()))
+            }
+            JsonValue::String(s) => Value::string(unquote_json_string(&s)),
+            JsonValue::Float(f) => Value::Float(f),
+            JsonValue::Integer(i) => Value::Integer(i),
+            JsonValue::Boolean(b) => Value::Boolean(b),
+            JsonValue::Null => Value::Null,
+        }
+    }
+
+    /// Convert back into a DOM value, quoting strings and keys. NaN becomes null, and infinities are clamped to the
+    /// largest representable numbers
+    pub fn into_json(self) -> JsonValue<'static> {
+        match self {
+            Value::Object(members) => JsonValue::Object(
+                unwrap_rc(members)
+                    .into_iter()
+                    .map(|(k, v)| (quote_json_string(&k), v.into_json()))
+                    .collect(),
+            ),
+            Value::Array(values) => JsonValue::Array(
+                unwrap_rc(values)
+                    .into_iter()
+                    .map(Value::into_json)
+                    .collect(),
+            ),
+            Value::String(s) => JsonValue::String(Cow::Owned(quote_json_string(&s))),
+            Value::Float(f) if f.is_nan() => JsonValue::Null,
+            Value::Float(f) => JsonValue::Float(f.clamp(f64::MIN, f64::MAX)),
+            Value::Integer(i) => JsonValue::Integer(i),
+            Value::Boolean(b) => JsonValue::Boolean(b),
+            Value::Null => JsonValue::Null,
+        }
+    }
+
+    /// Construct a string value
+    pub fn string(s: impl Into<Rc<str>>) -> Value {
+        Value::String(s.into())
+    }
+
+    /// Construct a number, preferring an integer representation where there's no loss of precision
+    pub fn number(f: f64) -> Value {
+        if f.fract() == 0.0 && f.abs() < MAX_SAFE_INTEGER {
+            Value::Integer(f as i64)
+        } else {
+            Value::Float(f)
+        }
+    }
+
+    /// Construct an array value
+    pub fn array(values: Vec<Value>) -> Value {
+        Value::Array(Rc::new(values))
+    }
+
+    /// Construct an object value
+    pub fn object(members: Vec<(String, Value)>) -> Value {
+        Value::Object(Rc::new(members))
+    }
+
+    /// The name of the type of the value, as reported by `type`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Boolean(_) => "boolean",
+            Value::Integer(_) | Value::Float(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        }
+    }
+
+    /// Everything apart from `null` and `false` is truthy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Null | Value::Boolean(false))
+    }
+
+    /// The numeric value, if this is a number
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// The string value, if this is a string
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Look up an object member
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Position of each type within the overall sort order
+    fn type_rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Boolean(false) => 1,
+            Value::Boolean(true) => 2,
+            Value::Integer(_) | Value::Float(_) => 3,
+            Value::String(_) => 4,
+            Value::Array(_) => 5,
+            Value::Object(_) => 6,
+        }
+    }
+
+    /// Total ordering of values: null < false < true < numbers < strings < arrays < objects. Objects are compared
+    /// first by their sorted key sets, and then by their values in key order
+    pub fn compare(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Integer(l), Value::Integer(r)) => l.cmp(r),
+            (l, r) if l.type_rank() == 3 && r.type_rank() == 3 => {
+                let (l, r) = (l.as_f64().unwrap_or(0.0), r.as_f64().unwrap_or(0.0));
+                match (l.is_nan(), r.is_nan()) {
+                    (true, true) => Ordering::Equal,
+                    (true, false) => Ordering::Less,
+                    (false, true) => Ordering::Greater,
+                    _ => l.partial_cmp(&r).unwrap_or(Ordering::Equal),
+                }
+            }
+            (Value::String(l), Value::String(r)) => l.cmp(r),
+            (Value::Array(l), Value::Array(r)) => {
+                for (a, b) in l.iter().zip(r.iter()) {
+                    match a.compare(b) {
+                        Ordering::Equal => continue,
+                        ordering => return ordering,
+                    }
+                }
+                l.len().cmp(&r.len())
+            }
+            (Value::Object(_), Value::Object(_)) => {
+                let (lkeys, rkeys) = (self.sorted_keys(), other.sorted_keys());
+                match lkeys.cmp(&rkeys) {
+                    Ordering::Equal => {}
+                    ordering => return ordering,
+                }
+                for key in lkeys {
+                    let (a, b) = (self.get(key), other.get(key));
+                    match a.unwrap_or(&Value::Null).compare(b.unwrap_or(&Value::Null)) {
+                        Ordering::Equal => continue,
+                        ordering => return ordering,
+                    }
+                }
+                Ordering::Equal
+            }
+            _ => self.type_rank().cmp(&other.type_rank()),
+        }
+    }
+
+    /// The keys of an object, in codepoint order
+    pub fn sorted_keys(&self) -> Vec<&str> {
+        match self {
+            Value::Object(members) => {
+                let mut keys: Vec<&str> = members.iter().map(|(k, _)| k.as_str()).collect();
+                keys.sort_unstable();
+                keys
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Serialise as compact JSON text
+    pub fn to_json_text(&self) -> String {
+        let mut text = String::new();
+        self.write_json_text(&mut text);
+        text
+    }
+
+    fn write_json_text(&self, text: &mut String) {
+        match self {
+            Value::Null => text.push_str("null"),
+            Value::Boolean(b) => {
+                let _ = write!(text, "{}", b);
+            }
+            Value::Integer(i) => {
+                let _ = write!(text, "{}", i);
+            }
+            Value::Float(f) => text.push_str(&format_float(*f)),
+            Value::String(s) => text.push_str(&quote_json_string(s)),
+            Value::Array(values) => {
+                text.push('[');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        text.push(',');
+                    }
+                    value.write_json_text(text);
+                }
+                text.push(']');
+            }
+            Value::Object(members) => {
+                text.push('{');
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        text.push(',');
+                    }
+                    text.push_str(&quote_json_string(key));
+                    text.push(':');
+                    value.write_json_text(text);
+                }
+                text.push('}');
+            }
+        }
+    }
+
+    /// Describe the value for use within error messages, e.g. `number (42)`. Long values are truncated
+    pub fn describe(&self) -> String {
+        let text = self.to_json_text();
+        match text.char_indices().nth(11) {
+            Some((index, _)) => format!("{} ({}...)", self.type_name(), &text[..index]),
+            None => format!("{} ({})", self.type_name(), text),
+        }
+    }
+
+    /// Convert to a string, as performed by `tostring` and string interpolation
+    pub fn to_text(&self) -> String {
+        match self {
+            Value::String(s) => s.to_string(),
+            value => value.to_json_text(),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.compare(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.compare(other)
+    }
+}
+
+/// Format a float as JSON text.  Non-finite values have no JSON representation, so NaN becomes null and infinities
+/// are clamped
+pub(super) fn format_float(f: f64) -> String {
+    if f.is_nan() {
+        return String::from("null");
+    }
+    let f = f.clamp(f64::MIN, f64::MAX);
+    if f != 0.0 && (f.abs() >= 1e17 || f.abs() < 1e-5) {
+        let formatted = format!("{:e}", f);
+        match formatted.split_once('e') {
+            Some((mantissa, exponent)) if !exponent.starts_with('-') => {
+                format!("{}e+{}", mantissa, exponent)
+            }
+            _ => formatted,
+        }
+    } else {
+        f.to_string()
+    }
+}
+
+/// Take ownership of the contents of an [Rc], only cloning if it's shared
+fn unwrap_rc<T: Clone>(rc: Rc<T>) -> T {
+    Rc::try_unwrap(rc).unwrap_or_else(|rc| (*rc).clone())
+}